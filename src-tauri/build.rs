@@ -20,6 +20,17 @@ fn main() {
                     "add_processor_pack",
                     "remove_processor_pack",
                     "update_processor_pack_status",
+                    "update_processor_pack_continue",
+                    "reorder_processor_packs",
+                    "resume_breakpoint",
+                    "replay_request",
+                    "get_body",
+                    "get_sessions",
+                    "open_session",
+                    "remove_session",
+                    "import_har",
+                    "export_har",
+                    "request_snippet",
                     "turn_on_global_proxy",
                     "turn_off_global_proxy",
                     "set_app_setting",
@@ -1,4 +1,8 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+};
 
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
@@ -8,10 +12,91 @@ use crate::error::{
     ConfigurationError, Error,
 };
 
+/// Bodies larger than it are sent to the UI as a preview, the full ones are loaded on demand.
+const DEFAULT_BODY_PREVIEW_LIMIT: usize = 512 * 1024;
+
+/// The oldest sessions are removed once there are more of them.
+const DEFAULT_MAX_SESSIONS: usize = 20;
+
+/// Sessions together take at most this many bytes on disk, a session stops recording once it's
+/// over it on its own.
+const DEFAULT_MAX_SESSION_BYTES: u64 = 1024 * 1024 * 1024;
+
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct AppSetting {
     theme: String,
     layout: String,
+    #[serde(default = "default_body_preview_limit")]
+    body_preview_limit: usize,
+    /// Send responses to the client in their original `Content-Encoding`, rather than decoded.
+    #[serde(default)]
+    preserve_content_encoding: bool,
+    #[serde(default)]
+    upstream_proxies: Vec<UpstreamRule>,
+    /// Port of the SOCKS5 listener on localhost, which is off if it's absent.
+    #[serde(default)]
+    socks5_port: Option<u16>,
+    /// Port of the listener for connections redirected by iptables or nftables, Linux only.
+    #[serde(default)]
+    transparent_port: Option<u16>,
+    /// Address the transparent listener binds, loopback unless the redirected connections come
+    /// from other interfaces.
+    #[serde(default = "default_transparent_address")]
+    transparent_address: IpAddr,
+    /// Record every proxy run as a session.
+    #[serde(default = "default_record_sessions")]
+    record_sessions: bool,
+    #[serde(default = "default_max_sessions")]
+    max_sessions: usize,
+    #[serde(default = "default_max_session_bytes")]
+    max_session_bytes: u64,
+}
+
+/// Hosts matching the `host` pattern are connected through `proxy`, which is `direct`, or an
+/// `http`, `https` or `socks5` uri.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct UpstreamRule {
+    pub host: String,
+    pub proxy: String,
+}
+
+impl AppSetting {
+    pub fn body_preview_limit(&self) -> usize {
+        self.body_preview_limit
+    }
+
+    pub fn preserve_content_encoding(&self) -> bool {
+        self.preserve_content_encoding
+    }
+
+    pub fn upstream_proxies(&self) -> &[UpstreamRule] {
+        &self.upstream_proxies
+    }
+
+    pub fn socks5_port(&self) -> Option<u16> {
+        self.socks5_port
+    }
+
+    pub fn transparent_port(&self) -> Option<u16> {
+        self.transparent_port
+    }
+
+    pub fn transparent_address(&self) -> IpAddr {
+        self.transparent_address
+    }
+
+    pub fn record_sessions(&self) -> bool {
+        self.record_sessions
+    }
+
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    pub fn max_session_bytes(&self) -> u64 {
+        self.max_session_bytes
+    }
 }
 
 impl Default for AppSetting {
@@ -19,10 +104,39 @@ impl Default for AppSetting {
         Self {
             theme: String::from("dark"),
             layout: String::from("right"),
+            body_preview_limit: DEFAULT_BODY_PREVIEW_LIMIT,
+            preserve_content_encoding: false,
+            upstream_proxies: vec![],
+            socks5_port: None,
+            transparent_port: None,
+            transparent_address: default_transparent_address(),
+            record_sessions: true,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            max_session_bytes: DEFAULT_MAX_SESSION_BYTES,
         }
     }
 }
 
+fn default_body_preview_limit() -> usize {
+    DEFAULT_BODY_PREVIEW_LIMIT
+}
+
+fn default_transparent_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_record_sessions() -> bool {
+    true
+}
+
+fn default_max_sessions() -> usize {
+    DEFAULT_MAX_SESSIONS
+}
+
+fn default_max_session_bytes() -> u64 {
+    DEFAULT_MAX_SESSION_BYTES
+}
+
 pub(super) fn read_app_setting<P: AsRef<Path>>(path: P) -> Result<AppSetting, Error> {
     let content_raw = fs::read(path)
         .context(AppConfIoError {})
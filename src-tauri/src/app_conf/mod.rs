@@ -5,7 +5,7 @@ use crate::error::{self, configuration_error::AppConfIoError, ConfigurationError
 
 use self::app_setting::{read_app_setting, write_app_setting};
 
-pub(crate) use self::app_setting::AppSetting;
+pub(crate) use self::app_setting::{AppSetting, UpstreamRule};
 
 mod app_setting;
 
@@ -25,10 +25,31 @@ pub fn app_value_dir() -> PathBuf {
     get_app_path("value")
 }
 
+pub fn app_session_dir() -> PathBuf {
+    get_app_path("session")
+}
+
+/// Full bodies of the captures which are too large to be sent to the UI.
+pub fn app_body_spool_dir() -> PathBuf {
+    get_app_path("spool")
+}
+
+pub fn app_har_dir() -> PathBuf {
+    get_app_path("har")
+}
+
 pub fn app_processor_pack_status_file() -> PathBuf {
     get_app_path("processor_pack_status.json")
 }
 
+pub fn app_processor_pack_order_file() -> PathBuf {
+    get_app_path("processor_pack_order.json")
+}
+
+pub fn app_processor_pack_continue_file() -> PathBuf {
+    get_app_path("processor_pack_continue.json")
+}
+
 fn app_setting_file() -> PathBuf {
     get_app_path("settings.json")
 }
@@ -1,9 +1,10 @@
-use crate::app_conf;
+use crate::{app_conf, events::body::set_preview_limit};
 
 #[tauri::command]
 pub async fn set_app_setting(setting: app_conf::AppSetting) {
     log::trace!("set_app_setting, {:?}", setting);
 
+    set_preview_limit(setting.body_preview_limit());
     app_conf::save_app_setting(setting);
 }
 
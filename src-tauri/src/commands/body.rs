@@ -0,0 +1,21 @@
+use uuid::Uuid;
+
+use crate::{
+    events::body::EventBody, processors::http_processor::HttpPhase, session::SessionStore,
+};
+
+/// The full body of an exchange whose event only carries a preview, it's captured or stored in
+/// `session`.
+#[tauri::command]
+pub(crate) async fn get_body(
+    id: Uuid,
+    phase: HttpPhase,
+    session: Option<Uuid>,
+) -> Result<EventBody, String> {
+    let body = match session {
+        Some(session) => SessionStore::default().body(session, id, phase).await,
+        None => EventBody::spooled(id, phase).await,
+    };
+
+    body.map_err(|e| format!("body of {id} isn't retained: {e}"))
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use tauri::State;
+use uuid::Uuid;
+
+use crate::proxy::breakpoint::{BreakpointAction, Breakpoints};
+
+#[tauri::command]
+pub(crate) async fn resume_breakpoint(
+    breakpoints: State<'_, Arc<Breakpoints>>,
+    id: Uuid,
+    action: BreakpointAction,
+) -> Result<(), String> {
+    if breakpoints.resume(id, action) {
+        Ok(())
+    } else {
+        Err(format!("{id} isn't paused, it may have timed out"))
+    }
+}
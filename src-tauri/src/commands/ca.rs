@@ -18,7 +18,7 @@ pub async fn check_cert_installed<R: Runtime>(app: AppHandle<R>) -> Result<bool,
     Ok(child
         .ok()
         .and_then(|c| String::from_utf8(c.stdout).ok())
-        .map_or(false, |output| {
+        .is_some_and(|output| {
             log::info!("security verify-cert output: {output}");
             output.contains("certificate verification successful")
         }))
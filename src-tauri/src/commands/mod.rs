@@ -1,5 +1,10 @@
 pub mod app_setting;
+pub mod body;
+pub mod breakpoint;
 pub mod ca;
 pub mod global_proxy;
 pub mod processor;
+pub mod replay;
+pub mod session;
+pub mod snippet;
 pub mod values;
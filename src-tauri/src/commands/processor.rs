@@ -1,20 +1,30 @@
 use std::{future::Future, sync::Arc};
 
+use arc_swap::ArcSwap;
 use serde::Serialize;
-use tauri::{async_runtime::Mutex, State};
+use tauri::State;
 use tokio::sync::mpsc::{self, Sender};
 
+use crate::error::{processor_error::ProcessorErrorKind, Error};
 use crate::processors::parser::ProcessorRuleParser;
 use crate::processors::persist::processor_persist::{
-    create_pack_dir, delete_pack_dir, write_processor_pack_status,
+    create_pack_dir, delete_pack_dir, prepend_processor_pack_order, write_processor_pack_continue,
+    write_processor_pack_order, write_processor_pack_status,
 };
 use crate::processors::processor_pack::ProcessorPack;
 use crate::{
     processors::{
         http_processor::{
+            body::{BodyProcessor, BodyRule},
+            breakpoint::{BreakpointProcessor, BreakpointRule},
             delay::{RequestDelayProcessor, RequestDelayRule},
-            redirect::RequestRedirectProcessor,
-            response::ResponseProcessor,
+            header::{HeaderProcessor, HeaderRule},
+            map_local::{MapLocalProcessor, MapLocalRule},
+            playback::{PlaybackProcessor, PlaybackRule},
+            redirect::{RequestRedirectProcessor, RequestRedirectRule},
+            response::{ResponseProcessor, ResponseRule},
+            script::{Script, ScriptProcessor},
+            status::{ResponseStatusProcessor, ResponseStatusRule},
             HttpProcessor,
         },
         persist::processor_persist::{
@@ -27,23 +37,32 @@ use crate::{
 
 pub(crate) enum ProcessorChannelMessage {
     // processor message
-    Redirect(String, Vec<[String; 2]>),
+    Redirect(String, RequestRedirectRule),
     Delay(String, RequestDelayRule),
-    Response(String, Vec<[String; 2]>),
+    Response(String, ResponseRule),
+    Status(String, ResponseStatusRule),
+    Header(String, HeaderRule),
+    Body(String, BodyRule),
+    MapLocal(String, MapLocalRule),
+    Playback(String, PlaybackRule),
+    Script(String, Option<Script>),
+    Breakpoint(String, BreakpointRule),
     // packs message
     AddPack(String, bool),
     RemovePack(String),
     UpdatePackStatus(String, bool),
+    UpdatePackContinue(String, bool),
+    ReorderPacks(Vec<String>),
 }
 
 pub(crate) fn init() -> (
-    Arc<Mutex<HttpProcessor>>,
+    Arc<ArcSwap<HttpProcessor>>,
     Sender<ProcessorChannelMessage>,
     impl Future<Output = ()>,
 ) {
     let packs = read_processors_from_appdir();
 
-    let processor = Arc::new(Mutex::new(HttpProcessor::new(packs)));
+    let processor = Arc::new(ArcSwap::from_pointee(HttpProcessor::new(packs)));
 
     let (tx, mut rx) = mpsc::channel::<ProcessorChannelMessage>(1);
 
@@ -52,38 +71,83 @@ pub(crate) fn init() -> (
         log::debug!("Start listening ProcessorChannelMessage ...");
 
         while let Some(message) = rx.recv().await {
-            let mut processor_setter = processor_setter.lock().await;
+            // Requests in flight keep using the current snapshot, the updated one is published
+            // as a whole once it's ready.
+            let mut next = HttpProcessor::clone(&processor_setter.load());
 
             match message {
                 ProcessorChannelMessage::AddPack(pack_name, enable) => {
-                    processor_setter.add_pack(ProcessorPack::new(pack_name, enable));
+                    next.add_pack(ProcessorPack::new(pack_name, enable));
                 }
                 ProcessorChannelMessage::RemovePack(pack_name) => {
-                    processor_setter.remove_pack(pack_name);
+                    next.remove_pack(pack_name);
                 }
                 ProcessorChannelMessage::UpdatePackStatus(pack_name, status) => {
                     if status {
-                        processor_setter.enable_pack(pack_name);
+                        next.enable_pack(pack_name);
                     } else {
-                        processor_setter.disable_pack(pack_name);
+                        next.disable_pack(pack_name);
                     }
                 }
+                ProcessorChannelMessage::UpdatePackContinue(pack_name, continue_matching) => {
+                    next.set_pack_continue(pack_name, continue_matching);
+                }
+                ProcessorChannelMessage::ReorderPacks(order) => {
+                    next.reorder_packs(&order);
+                }
                 ProcessorChannelMessage::Redirect(pack_name, mapping) => {
-                    if let Some(redirect) = processor_setter.get_redirect_mut(pack_name) {
+                    if let Some(redirect) = next.get_redirect_mut(pack_name) {
                         redirect.set_redirects_mapping(mapping);
                     }
                 }
                 ProcessorChannelMessage::Delay(pack_name, mappings) => {
-                    if let Some(delay) = processor_setter.get_delay_mut(pack_name) {
+                    if let Some(delay) = next.get_delay_mut(pack_name) {
                         delay.set_delay_mapping(mappings);
                     }
                 }
                 ProcessorChannelMessage::Response(pack_name, mapping) => {
-                    if let Some(response) = processor_setter.get_response_mut(pack_name) {
+                    if let Some(response) = next.get_response_mut(pack_name) {
                         response.set_mapping(mapping);
                     }
                 }
+                ProcessorChannelMessage::Status(pack_name, mappings) => {
+                    if let Some(status) = next.get_status_mut(pack_name) {
+                        status.set_status_mapping(mappings);
+                    }
+                }
+                ProcessorChannelMessage::Header(pack_name, mappings) => {
+                    if let Some(header) = next.get_header_mut(pack_name) {
+                        header.set_header_mapping(mappings);
+                    }
+                }
+                ProcessorChannelMessage::Body(pack_name, mappings) => {
+                    if let Some(body) = next.get_body_mut(pack_name) {
+                        body.set_body_mapping(mappings);
+                    }
+                }
+                ProcessorChannelMessage::MapLocal(pack_name, mappings) => {
+                    if let Some(map_local) = next.get_map_local_mut(pack_name) {
+                        map_local.set_map_local_mapping(mappings);
+                    }
+                }
+                ProcessorChannelMessage::Playback(pack_name, mappings) => {
+                    if let Some(playback) = next.get_playback_mut(pack_name) {
+                        playback.set_playback_mapping(mappings);
+                    }
+                }
+                ProcessorChannelMessage::Script(pack_name, script) => {
+                    if let Some(processor) = next.get_script_mut(pack_name) {
+                        processor.set_script(script);
+                    }
+                }
+                ProcessorChannelMessage::Breakpoint(pack_name, mappings) => {
+                    if let Some(breakpoint) = next.get_breakpoint_mut(pack_name) {
+                        breakpoint.set_breakpoint_mapping(mappings);
+                    }
+                }
             }
+
+            processor_setter.store(Arc::new(next));
         }
     };
 
@@ -106,6 +170,10 @@ pub(crate) async fn set_processor(
 
     let processor_id = ProcessorID::try_from(mode)?;
 
+    // Reject the whole content if any line is invalid, so the editor could point them out.
+    let msg = parse_processor_message(processor_id, pack_name.clone(), content.as_str())
+        .map_err(|e| e.to_json())?;
+
     let save_ret = write_processor(processor_id, content.as_str(), pack_name.as_str());
 
     if let Err(e) = save_ret {
@@ -120,48 +188,78 @@ pub(crate) async fn set_processor(
 
     let mut state = state.lock().await;
 
-    if state.is_some() {
-        let msg = {
-            match processor_id {
-                ProcessorID::REDIRECT => Some(ProcessorChannelMessage::Redirect(
-                    pack_name,
-                    RequestRedirectProcessor::parse_rule(content.as_str()),
-                )),
-                ProcessorID::DELAY => Some(ProcessorChannelMessage::Delay(
-                    pack_name,
-                    RequestDelayProcessor::parse_rule(content.as_str()),
-                )),
-                ProcessorID::RESPONSE => Some(ProcessorChannelMessage::Response(
-                    pack_name,
-                    ResponseProcessor::parse_rule(content.as_str()),
-                )),
-                _ => return Err("Unsupport processor".into()),
-            }
-        };
-
-        match msg {
-            Some(msg) => match state.as_mut() {
-                Some((_, processor, _, _, _)) => {
-                    if let Err(e) = processor.send(msg).await {
-                        log::error!("Set Processor failed: {e}");
-                        return Err(format!("Set Processor failed: {e}"));
-                    }
-                }
-                None => {
-                    log::error!("Set Processor failed: No ProcessorChannelMessage Sender found");
-                    return Err("No ProcessorChannelMessage Sender found".to_string());
-                }
-            },
-            None => {
-                log::error!("Parsing Processor rule failed");
-                return Err("Parsing Processor rule failed".to_string());
-            }
+    if let Some((_, processor, _, _, _, _)) = state.as_mut() {
+        if let Err(e) = processor.send(msg).await {
+            log::error!("Set Processor failed: {e}");
+            return Err(format!("Set Processor failed: {e}"));
         }
     }
 
     Ok(true)
 }
 
+fn parse_processor_message(
+    id: ProcessorID,
+    pack_name: String,
+    content: &str,
+) -> Result<ProcessorChannelMessage, Error> {
+    let invalid = |diagnostics| Error::Processor {
+        id,
+        source: ProcessorErrorKind::InvalidRule { diagnostics },
+    };
+
+    let msg = match id {
+        ProcessorID::REDIRECT => ProcessorChannelMessage::Redirect(
+            pack_name,
+            RequestRedirectProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::DELAY => ProcessorChannelMessage::Delay(
+            pack_name,
+            RequestDelayProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::RESPONSE => ProcessorChannelMessage::Response(
+            pack_name,
+            ResponseProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::STATUS => ProcessorChannelMessage::Status(
+            pack_name,
+            ResponseStatusProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::HEADER => ProcessorChannelMessage::Header(
+            pack_name,
+            HeaderProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::BODY => ProcessorChannelMessage::Body(
+            pack_name,
+            BodyProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::MAP_LOCAL => ProcessorChannelMessage::MapLocal(
+            pack_name,
+            MapLocalProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::PLAYBACK => ProcessorChannelMessage::Playback(
+            pack_name,
+            PlaybackProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        ProcessorID::SCRIPT => ProcessorChannelMessage::Script(
+            pack_name,
+            ScriptProcessor::validate_script(content).map_err(invalid)?,
+        ),
+        ProcessorID::BREAKPOINT => ProcessorChannelMessage::Breakpoint(
+            pack_name,
+            BreakpointProcessor::validate_rule(content).map_err(invalid)?,
+        ),
+        _ => {
+            return Err(Error::Processor {
+                id,
+                source: ProcessorErrorKind::Unsupport {},
+            })
+        }
+    };
+
+    Ok(msg)
+}
+
 #[tauri::command]
 pub fn get_processor_content(mode: String, pack_name: String) -> Result<String, String> {
     let processor_id = ProcessorID::try_from(mode)?;
@@ -174,6 +272,7 @@ pub fn get_processor_content(mode: String, pack_name: String) -> Result<String,
 pub struct ProcessorPackTransfer {
     pack_name: String,
     enable: bool,
+    continue_matching: bool,
 }
 
 #[tauri::command]
@@ -186,6 +285,7 @@ pub fn get_processor_packs() -> Vec<ProcessorPackTransfer> {
         ret.push(ProcessorPackTransfer {
             pack_name: pack.pack_name.to_string(),
             enable: pack.is_enable(),
+            continue_matching: pack.is_continue(),
         })
     }
 
@@ -204,13 +304,17 @@ pub async fn add_processor_pack(
         return Err(format!("create pack dir failed: {err}"));
     }
 
+    if let Err(err) = prepend_processor_pack_order(pack_name.as_str()) {
+        return Err(format!("write pack order failed: {err}"));
+    }
+
     let mut state = state.lock().await;
 
     if state.is_some() {
         let msg = ProcessorChannelMessage::AddPack(pack_name, enable);
 
         match state.as_mut() {
-            Some((_, sender, _, _, _)) => {
+            Some((_, sender, _, _, _, _)) => {
                 if let Err(err) = sender.send(msg).await {
                     return Err(format!("mpsc send message failed: {err}"));
                 }
@@ -239,7 +343,7 @@ pub async fn remove_processor_pack(
         let msg = ProcessorChannelMessage::RemovePack(pack_name);
 
         match state.as_mut() {
-            Some((_, sender, _, _, _)) => {
+            Some((_, sender, _, _, _, _)) => {
                 if let Err(err) = sender.send(msg).await {
                     return Err(format!("mpsc send message failed: {err}"));
                 }
@@ -269,7 +373,67 @@ pub async fn update_processor_pack_status(
         let msg = ProcessorChannelMessage::UpdatePackStatus(pack_name, status);
 
         match state.as_mut() {
-            Some((_, sender, _, _, _)) => {
+            Some((_, sender, _, _, _, _)) => {
+                if let Err(err) = sender.send(msg).await {
+                    return Err(format!("mpsc send message failed: {err}"));
+                }
+            }
+            None => return Err("get mpsc message sender failed".to_string()),
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn update_processor_pack_continue(
+    state: State<'_, ProxyState>,
+    pack_name: String,
+    continue_matching: bool,
+) -> Result<(), String> {
+    let ret = write_processor_pack_continue(pack_name.as_str(), continue_matching);
+
+    if let Err(err) = ret {
+        return Err(format!("write pack continue flag failed: {err}"));
+    }
+
+    let mut state = state.lock().await;
+
+    if state.is_some() {
+        let msg = ProcessorChannelMessage::UpdatePackContinue(pack_name, continue_matching);
+
+        match state.as_mut() {
+            Some((_, sender, _, _, _, _)) => {
+                if let Err(err) = sender.send(msg).await {
+                    return Err(format!("mpsc send message failed: {err}"));
+                }
+            }
+            None => return Err("get mpsc message sender failed".to_string()),
+        }
+    }
+
+    Ok(())
+}
+
+/// `pack_names` are ordered from the highest priority to the lowest.
+#[tauri::command]
+pub async fn reorder_processor_packs(
+    state: State<'_, ProxyState>,
+    pack_names: Vec<String>,
+) -> Result<(), String> {
+    let ret = write_processor_pack_order(&pack_names);
+
+    if let Err(err) = ret {
+        return Err(format!("write pack order failed: {err}"));
+    }
+
+    let mut state = state.lock().await;
+
+    if state.is_some() {
+        let msg = ProcessorChannelMessage::ReorderPacks(pack_names);
+
+        match state.as_mut() {
+            Some((_, sender, _, _, _, _)) => {
                 if let Err(err) = sender.send(msg).await {
                     return Err(format!("mpsc send message failed: {err}"));
                 }
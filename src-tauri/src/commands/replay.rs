@@ -0,0 +1,55 @@
+use hyper::{Body, Request};
+use tauri::State;
+use uuid::Uuid;
+
+use crate::proxy::{
+    capture::{CapturedRequests, Replay},
+    edit::MessageEdit,
+    ProxyState,
+};
+
+/// Send a captured request again, or a request made from scratch if `id` is missing.
+/// Returns the id of the replayed connection.
+#[tauri::command]
+pub(crate) async fn replay_request(
+    proxy: State<'_, ProxyState>,
+    captured: State<'_, CapturedRequests>,
+    id: Option<Uuid>,
+    edit: Option<MessageEdit>,
+) -> Result<Uuid, String> {
+    let req = match id {
+        Some(id) => captured
+            .get(&id)
+            .ok_or_else(|| format!("{id} is no longer retained"))?
+            .to_request(),
+        None => Request::new(Body::empty()),
+    };
+
+    let req = match edit {
+        Some(edit) => edit.apply_to_request(req),
+        None => req,
+    };
+
+    if req.uri().authority().is_none() {
+        return Err(format!("{} isn't an absolute uri", req.uri()));
+    }
+
+    let replay = Replay {
+        id: Uuid::new_v4(),
+        replay_of: id,
+        req,
+    };
+    let replay_id = replay.id;
+
+    match proxy.lock().await.as_ref() {
+        Some((_, _, _, _, _, replayer)) => {
+            if let Err(e) = replayer.send(replay).await {
+                log::error!("Replay request failed: {e}");
+                return Err(format!("Replay request failed: {e}"));
+            }
+        }
+        None => return Err(String::from("proxy isn't running")),
+    }
+
+    Ok(replay_id)
+}
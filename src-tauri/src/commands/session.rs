@@ -0,0 +1,55 @@
+use serde_json::Value;
+use std::fs;
+use uuid::Uuid;
+
+use crate::{
+    app_conf::app_har_dir,
+    events::har::Har,
+    processors::persist::ensure_dir,
+    session::{SessionInfo, SessionStore},
+};
+
+#[tauri::command]
+pub(crate) async fn get_sessions() -> Result<Vec<SessionInfo>, String> {
+    SessionStore::default().list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) async fn open_session(id: Uuid) -> Result<Vec<Value>, String> {
+    SessionStore::default().read(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) async fn remove_session(id: Uuid) -> Result<(), String> {
+    SessionStore::default()
+        .remove(id)
+        .map_err(|e| e.to_string())
+}
+
+/// Import the content of a HAR file as a new session.
+#[tauri::command]
+pub(crate) async fn import_har(content: String) -> Result<Uuid, String> {
+    let har: Har = serde_json::from_str(&content).map_err(|e| format!("invalid HAR: {e}"))?;
+
+    SessionStore::default()
+        .import(&har)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Export the session as a HAR file, returns where it's written to.
+#[tauri::command]
+pub(crate) async fn export_har(id: Uuid) -> Result<String, String> {
+    let exchanges = SessionStore::default()
+        .exchanges(id)
+        .map_err(|e| e.to_string())?;
+    let har = serde_json::to_vec_pretty(&Har::new(exchanges)).map_err(|e| e.to_string())?;
+
+    let dir = app_har_dir();
+    ensure_dir(&dir).map_err(|e| e.to_string())?;
+
+    let file = dir.join(format!("{id}.har"));
+    fs::write(&file, har).map_err(|e| e.to_string())?;
+
+    Ok(file.to_string_lossy().into_owned())
+}
@@ -0,0 +1,11 @@
+use crate::events::{snippet::SnippetFormat, RequestEvent};
+
+/// Render the request as a command line or code which sends it again.
+#[tauri::command]
+pub(crate) async fn request_snippet(
+    request: RequestEvent,
+    format: SnippetFormat,
+) -> Result<String, String> {
+    let request = request.load_body().await.map_err(|e| e.to_string())?;
+    Ok(request.to_snippet(format))
+}
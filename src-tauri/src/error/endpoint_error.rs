@@ -22,9 +22,10 @@ pub enum EndpointError {
     WebsocketProtocol {
         source: hyper_tungstenite::tungstenite::error::ProtocolError,
     },
-    #[snafu(display("Error occurred when decoding {}", scenario))]
+    #[snafu(display("Error occurred when decoding, {} `{}`", scenario, encoding))]
     Decoder {
         scenario: &'static str,
+        encoding: String,
     },
     // #[snafu(display("{}", reason))]
     // Proxyman {
@@ -68,9 +69,9 @@ impl Serialize for EndpointError {
                 state.serialize_field("message", source.to_string().as_str())?;
                 state.end()
             }
-            Self::Decoder { scenario } => {
-                let mut state = serializer.serialize_struct("WebsocketProtocol", 1)?;
-                state.serialize_field("message", *scenario)?;
+            Self::Decoder { .. } => {
+                let mut state = serializer.serialize_struct("Decoder", 1)?;
+                state.serialize_field("message", self.to_string().as_str())?;
                 state.end()
             }
         }
@@ -1,6 +1,8 @@
 use serde::{ser::SerializeStruct, Serialize};
 use snafu::Snafu;
 
+use crate::processors::parser::RuleDiagnostic;
+
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)), context(suffix(Error)))]
 pub enum ProcessorErrorKind {
@@ -10,6 +12,7 @@ pub enum ProcessorErrorKind {
     ReadStatus { source: std::io::Error },
     NotFound {},
     Fmt {},
+    InvalidRule { diagnostics: Vec<RuleDiagnostic> },
     Unsupport {},
     Unknown {},
 }
@@ -45,6 +48,12 @@ impl Serialize for ProcessorErrorKind {
                 state.serialize_field("message", "Format error")?;
                 state.end()
             }
+            Self::InvalidRule { diagnostics } => {
+                let mut state = serializer.serialize_struct("InvalidRule", 2)?;
+                state.serialize_field("message", "Invalid rule")?;
+                state.serialize_field("diagnostics", diagnostics)?;
+                state.end()
+            }
             Self::NotFound {} => {
                 let mut state = serializer.serialize_struct("NotFound", 1)?;
                 state.serialize_field("message", "Not found")?;
@@ -0,0 +1,672 @@
+use std::{
+    borrow::Cow,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use hyper::{
+    body::{to_bytes, HttpBody},
+    Body,
+};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{
+    app_conf::app_body_spool_dir, processors::http_processor::HttpPhase, session::SessionStore,
+};
+
+/// Captured bodies over it are sent as a preview of this many bytes, it's unlimited until the
+/// app setting is applied.
+static PREVIEW_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+pub fn set_preview_limit(limit: usize) {
+    PREVIEW_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+pub fn preview_limit() -> usize {
+    PREVIEW_LIMIT.load(Ordering::Relaxed)
+}
+
+/// Bodies are loaded into memory as a whole up to it, e.g. for scripts, larger ones are refused.
+pub const LOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+/// How a body is put into a string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl BodyEncoding {
+    pub fn decode(self, body: &str) -> Result<Bytes, base64::DecodeError> {
+        match self {
+            BodyEncoding::Utf8 => Ok(Bytes::copy_from_slice(body.as_bytes())),
+            BodyEncoding::Base64 => BASE64.decode(body).map(Bytes::from),
+        }
+    }
+}
+
+/// A message body which survives the trip to the UI byte for byte, it's flattened into the
+/// events. Text bodies are sent as is, others are base64 encoded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventBody {
+    body: String,
+    // sessions recorded before the encoding are text
+    #[serde(default)]
+    body_encoding: BodyEncoding,
+    /// Length in bytes before encoding.
+    #[serde(default)]
+    body_length: usize,
+    /// The essence of `Content-Type`, sniffed from the content if it's absent.
+    #[serde(default)]
+    mime: String,
+    /// Only the head of the body is sent, the full one is in the spool.
+    #[serde(default)]
+    truncated: bool,
+    /// The session the full body is stored in, if it's opened from one rather than captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<Uuid>,
+}
+
+impl EventBody {
+    pub fn new(bytes: &[u8], headers: &HeaderMap) -> Self {
+        let (body, body_encoding) = encode(bytes);
+
+        Self {
+            body,
+            body_encoding,
+            body_length: bytes.len(),
+            mime: detect_mime(headers, bytes),
+            truncated: false,
+            session: None,
+        }
+    }
+
+    /// The body of a captured exchange. Bodies over the preview limit are cut, and the full
+    /// ones are spooled to disk, see [`EventBody::load`].
+    pub async fn capture(id: Uuid, phase: HttpPhase, bytes: &[u8], headers: &HeaderMap) -> Self {
+        let mut recorder = BodyRecorder::new(id, phase);
+        recorder.write(bytes).await;
+        recorder.finish(headers).await
+    }
+
+    /// The body of a captured exchange which is already spooled, see [`SpooledBody::read`].
+    pub fn spooled_preview(body: &SpooledBody, headers: &HeaderMap) -> Self {
+        match body {
+            SpooledBody::Memory(bytes) => Self::new(bytes, headers),
+            SpooledBody::File { head, len, .. } => Self::preview(head, *len, headers),
+        }
+    }
+
+    /// The head of a body which is `len` bytes as a whole.
+    pub fn preview(head: &[u8], len: usize, headers: &HeaderMap) -> Self {
+        let (body, body_encoding) = encode_preview(head);
+        Self {
+            body,
+            body_encoding,
+            body_length: len,
+            mime: detect_mime(headers, head),
+            truncated: true,
+            session: None,
+        }
+    }
+
+    /// The full body is loaded from the session rather than the spool.
+    pub fn in_session(mut self, session: Uuid) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// The full body of a captured exchange, read from the spool.
+    pub async fn spooled(id: Uuid, phase: HttpPhase) -> io::Result<Self> {
+        let bytes = tokio::fs::read(spool_file(id, phase)).await?;
+        Ok(Self::new(&bytes, &HeaderMap::new()))
+    }
+
+    /// Put the full body back if it's truncated.
+    pub async fn load(self, id: Uuid, phase: HttpPhase) -> io::Result<Self> {
+        if !self.truncated {
+            return Ok(self);
+        }
+
+        let full = match self.session {
+            Some(session) => SessionStore::default().body(session, id, phase).await?,
+            None => Self::spooled(id, phase).await?,
+        };
+        Ok(Self {
+            mime: self.mime,
+            ..full
+        })
+    }
+
+    /// Save the full body into `path` as it is, the spool is linked rather than read if the
+    /// body is truncated.
+    pub async fn persist(&self, id: Uuid, phase: HttpPhase, path: &Path) -> io::Result<()> {
+        if !self.truncated {
+            return tokio::fs::write(path, self.bytes()).await;
+        }
+
+        let spooled = spool_file(id, phase);
+        // the spool may be on another file system
+        if tokio::fs::hard_link(&spooled, path).await.is_ok() {
+            return Ok(());
+        }
+        if let Err(e) = tokio::fs::copy(&spooled, path).await {
+            log::warn!("only the preview of the body of {id} is kept: {e}");
+            tokio::fs::write(path, self.bytes()).await?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.body.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.body_length
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.body_encoding == BodyEncoding::Base64
+    }
+
+    /// The body as it's encoded for the UI.
+    pub fn encoded(&self) -> &str {
+        &self.body
+    }
+
+    pub fn bytes(&self) -> Cow<'_, [u8]> {
+        match self.body_encoding {
+            BodyEncoding::Utf8 => Cow::Borrowed(self.body.as_bytes()),
+            BodyEncoding::Base64 => Cow::Owned(BASE64.decode(&self.body).unwrap_or_default()),
+        }
+    }
+}
+
+/// Read the whole body and put it back, so that it's still readable by others. The body is left
+/// empty if it fails midway.
+pub async fn buffer_body(body: &mut Body) -> Result<Bytes, hyper::Error> {
+    let bytes = to_bytes(std::mem::take(body)).await?;
+    *body = Body::from(bytes.clone());
+    Ok(bytes)
+}
+
+/// Read the whole body up to [`LOAD_LIMIT`], e.g. for scripts.
+pub async fn load_body(mut body: Body) -> io::Result<Bytes> {
+    let mut bytes = BytesMut::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        if bytes.len() + chunk.len() > LOAD_LIMIT {
+            return Err(over_load_limit());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes.freeze())
+}
+
+/// Read the body up to `limit` bytes and put them back in front of the rest, so that it's
+/// still readable by others without being held in memory as a whole.
+pub async fn peek_body(body: &mut Body, limit: usize) -> Result<Bytes, hyper::Error> {
+    let mut raw = std::mem::take(body);
+    let mut head = BytesMut::new();
+
+    while head.len() < limit {
+        match raw.data().await {
+            Some(Ok(chunk)) => head.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                // others fail on it as well, rather than reading a cut off body
+                let error = io::Error::other(e.to_string());
+                let rest = futures::stream::iter([Ok(head.freeze()), Err(error)]);
+                *body = Body::wrap_stream(rest);
+                return Err(e);
+            }
+            None => {
+                let head = head.freeze();
+                *body = Body::from(head.clone());
+                return Ok(head);
+            }
+        }
+    }
+
+    let head = head.freeze();
+    let rest = futures::stream::once(futures::future::ready(Ok(head.clone())))
+        .chain(raw.map(|chunk| chunk.map_err(io::Error::other)));
+    *body = Body::wrap_stream(rest);
+    Ok(head.slice(..limit))
+}
+
+fn over_load_limit() -> io::Error {
+    io::Error::other(format!("body is over {LOAD_LIMIT} bytes"))
+}
+
+/// A body read off a request, which is kept in memory up to the preview limit and spooled to
+/// disk over it, so that large uploads aren't held in memory.
+#[derive(Debug, Clone)]
+pub enum SpooledBody {
+    Memory(Bytes),
+    File {
+        spool: Arc<Spool>,
+        /// The preview.
+        head: Bytes,
+        len: usize,
+    },
+}
+
+/// A spool file, removed along with the last body referring to it if it's temporary.
+#[derive(Debug)]
+pub struct Spool {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Default for SpooledBody {
+    fn default() -> Self {
+        Self::Memory(Bytes::new())
+    }
+}
+
+impl SpooledBody {
+    /// Read the whole body and put it back, it's spooled to the file the full body of a capture
+    /// is loaded from.
+    pub async fn read(body: &mut Body, id: Uuid, phase: HttpPhase) -> io::Result<Self> {
+        let spool = Spool {
+            path: spool_file(id, phase),
+            temporary: false,
+        };
+        Self::read_into(body, spool, preview_limit()).await
+    }
+
+    /// Like [`SpooledBody::read`], but the spool is gone with the body.
+    pub async fn read_temporary(body: &mut Body) -> io::Result<Self> {
+        let spool = Spool {
+            path: app_body_spool_dir().join(format!("{}.tmp", Uuid::new_v4())),
+            temporary: true,
+        };
+        Self::read_into(body, spool, preview_limit()).await
+    }
+
+    async fn read_into(body: &mut Body, spool: Spool, limit: usize) -> io::Result<Self> {
+        let mut raw = std::mem::take(body);
+        let mut head = Vec::new();
+        let mut len = 0;
+        let mut file = None;
+
+        while let Some(chunk) = raw.data().await {
+            let chunk = chunk.map_err(io::Error::other)?;
+            len += chunk.len();
+
+            if file.is_none() && len > limit {
+                // kept in memory as a whole if it can't be spooled
+                match open_spool(&spool.path, &head).await {
+                    Ok(opened) => file = Some(opened),
+                    Err(e) => log::error!("spool body to {:?} failed: {e}", spool.path),
+                }
+            }
+
+            if let Some(file) = file.as_mut() {
+                file.write_all(&chunk).await?;
+                let room = limit.saturating_sub(head.len());
+                head.extend_from_slice(&chunk[..room.min(chunk.len())]);
+            } else {
+                head.extend_from_slice(&chunk);
+            }
+        }
+
+        let spooled = match file {
+            Some(mut file) => {
+                file.flush().await?;
+                Self::File {
+                    spool: Arc::new(spool),
+                    head: Bytes::from(head),
+                    len,
+                }
+            }
+            None => Self::Memory(Bytes::from(head)),
+        };
+
+        *body = spooled.to_body();
+        Ok(spooled)
+    }
+
+    /// Bytes held in memory, spooled ones aren't.
+    pub fn memory_len(&self) -> usize {
+        match self {
+            Self::Memory(bytes) => bytes.len(),
+            Self::File { head, .. } => head.len(),
+        }
+    }
+
+    /// A body streamed from the spool, which is kept until the body is dropped.
+    pub fn to_body(&self) -> Body {
+        self.to_tracked_body().0
+    }
+
+    /// Like [`SpooledBody::to_body`], along with a handle which is alive as long as the body,
+    /// so that the body can be told apart from the one it's replaced with. Bodies in memory are
+    /// cheap to read again, they aren't tracked.
+    pub fn to_tracked_body(&self) -> (Body, Weak<()>) {
+        match self {
+            Self::Memory(bytes) => (Body::from(bytes.clone()), Weak::new()),
+            Self::File { spool, .. } => match std::fs::File::open(&spool.path) {
+                Ok(file) => {
+                    let tracker = Arc::new(());
+                    let body = Body::wrap_stream(SpoolStream {
+                        _spool: Arc::clone(spool),
+                        _tracker: Arc::clone(&tracker),
+                        file: ReaderStream::new(tokio::fs::File::from_std(file)),
+                    });
+                    (body, Arc::downgrade(&tracker))
+                }
+                Err(e) => (
+                    Body::wrap_stream(futures::stream::once(async { Err::<Bytes, _>(e) })),
+                    Weak::new(),
+                ),
+            },
+        }
+    }
+
+    /// Up to the preview limit of the body, which is in memory.
+    pub fn preview(&self) -> &Bytes {
+        match self {
+            Self::Memory(bytes) => bytes,
+            Self::File { head, .. } => head,
+        }
+    }
+
+    /// The whole body, read from the spool if it's there. Bodies over [`LOAD_LIMIT`] are
+    /// refused rather than loaded into memory.
+    pub async fn load(&self) -> io::Result<Bytes> {
+        match self {
+            Self::Memory(bytes) => Ok(bytes.clone()),
+            Self::File { len, .. } if *len > LOAD_LIMIT => Err(over_load_limit()),
+            Self::File { spool, .. } => tokio::fs::read(&spool.path).await.map(Bytes::from),
+        }
+    }
+}
+
+/// Streams a spool file, which is kept as long as the stream.
+struct SpoolStream {
+    _spool: Arc<Spool>,
+    _tracker: Arc<()>,
+    file: ReaderStream<tokio::fs::File>,
+}
+
+impl Stream for SpoolStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.file).poll_next(cx)
+    }
+}
+
+/// The spool starts with what's been kept in memory so far.
+async fn open_spool(path: &Path, head: &[u8]) -> io::Result<tokio::fs::File> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let mut spool = tokio::fs::File::create(path).await?;
+    spool.write_all(head).await?;
+
+    Ok(spool)
+}
+
+/// Records a body chunk by chunk. The head is kept for the preview, and once the body is over
+/// the limit, it's spooled to disk as a whole.
+pub struct BodyRecorder {
+    id: Uuid,
+    phase: HttpPhase,
+    limit: usize,
+    head: Vec<u8>,
+    length: usize,
+    spool: Option<tokio::fs::File>,
+    spool_failed: bool,
+}
+
+impl BodyRecorder {
+    pub fn new(id: Uuid, phase: HttpPhase) -> Self {
+        Self {
+            id,
+            phase,
+            limit: preview_limit(),
+            head: vec![],
+            length: 0,
+            spool: None,
+            spool_failed: false,
+        }
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) {
+        self.length += chunk.len();
+
+        if self.spool.is_none() && !self.spool_failed && self.length > self.limit {
+            match self.open_spool().await {
+                Ok(spool) => self.spool = Some(spool),
+                Err(e) => {
+                    log::error!("spool body of {} failed: {e}", self.id);
+                    self.spool_failed = true;
+                }
+            }
+        }
+
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.write_all(chunk).await {
+                log::error!("spool body of {} failed: {e}", self.id);
+                self.spool = None;
+                self.spool_failed = true;
+            }
+        }
+
+        let room = self.limit - self.head.len();
+        self.head.extend_from_slice(&chunk[..room.min(chunk.len())]);
+    }
+
+    async fn open_spool(&self) -> io::Result<tokio::fs::File> {
+        open_spool(&spool_file(self.id, self.phase), &self.head).await
+    }
+
+    pub async fn finish(self, headers: &HeaderMap) -> EventBody {
+        if let Some(mut spool) = self.spool {
+            if let Err(e) = spool.flush().await {
+                log::error!("spool body of {} failed: {e}", self.id);
+            }
+        }
+
+        let truncated = self.length > self.head.len();
+        let (body, body_encoding) = if truncated {
+            encode_preview(&self.head)
+        } else {
+            encode(&self.head)
+        };
+
+        EventBody {
+            body,
+            body_encoding,
+            body_length: self.length,
+            mime: detect_mime(headers, &self.head),
+            truncated,
+            session: None,
+        }
+    }
+}
+
+/// Spooled bodies only live as long as the app, which is where the captures are kept.
+pub fn clear_spool() {
+    let dir = app_body_spool_dir();
+    if dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(dir) {
+            log::error!("clear body spool failed: {e}");
+        }
+    }
+}
+
+fn spool_file(id: Uuid, phase: HttpPhase) -> PathBuf {
+    let ext = match phase {
+        HttpPhase::Request => "req",
+        HttpPhase::Response => "res",
+    };
+    app_body_spool_dir().join(format!("{id}.{ext}"))
+}
+
+fn encode(bytes: &[u8]) -> (String, BodyEncoding) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), BodyEncoding::Utf8),
+        Err(_) => (BASE64.encode(bytes), BodyEncoding::Base64),
+    }
+}
+
+/// Like `encode`, but a text preview may end in the middle of a character, which is dropped.
+fn encode_preview(head: &[u8]) -> (String, BodyEncoding) {
+    match std::str::from_utf8(head) {
+        Err(e) if e.error_len().is_none() => encode(&head[..e.valid_up_to()]),
+        _ => encode(head),
+    }
+}
+
+/// Magic numbers of the common binary formats.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x00asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+fn detect_mime(headers: &HeaderMap, bytes: &[u8]) -> String {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+        .filter(|mime| !mime.is_empty());
+
+    content_type.unwrap_or_else(|| sniff_mime(bytes).to_string())
+}
+
+fn sniff_mime(bytes: &[u8]) -> &'static str {
+    if bytes.is_empty() {
+        return "";
+    }
+
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+    {
+        return mime;
+    }
+
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) if serde_json::from_str::<IgnoredAny>(text).is_ok() => "application/json",
+        Ok(_) => "text/plain",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_body() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\xff";
+        let body = EventBody::new(png, &HeaderMap::new());
+
+        assert!(body.is_binary());
+        assert_eq!(body.len(), png.len());
+        assert_eq!(body.mime, "image/png");
+        assert_eq!(body.bytes().as_ref(), png);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "Text/HTML; charset=utf-8".parse().unwrap());
+        let body = EventBody::new("<p>你好</p>".as_bytes(), &headers);
+
+        assert!(!body.is_binary());
+        assert_eq!(body.encoded(), "<p>你好</p>");
+        assert_eq!(body.mime, "text/html");
+
+        assert_eq!(sniff_mime(br#"{"a":1}"#), "application/json");
+
+        // `好` is cut in the middle
+        let (preview, encoding) = encode_preview(&"你好".as_bytes()[..4]);
+        assert_eq!(encoding, BodyEncoding::Utf8);
+        assert_eq!(preview, "你");
+    }
+
+    #[tokio::test]
+    async fn test_spooled_body() {
+        let path = std::env::temp_dir().join(format!("{}.tmp", Uuid::new_v4()));
+        let spool = Spool {
+            path: path.clone(),
+            temporary: true,
+        };
+
+        let mut body = Body::from("hello world");
+        let spooled = SpooledBody::read_into(&mut body, spool, 5).await.unwrap();
+
+        assert!(matches!(spooled, SpooledBody::File { ref head, len: 11, .. } if head == "hello"));
+        assert_eq!(spooled.memory_len(), 5);
+        assert_eq!(to_bytes(body).await.unwrap(), "hello world");
+        assert_eq!(spooled.load().await.unwrap(), "hello world");
+        assert_eq!(spooled.preview(), "hello");
+
+        // replacing the body is told by the tracker
+        let (tracked, original) = spooled.to_tracked_body();
+        assert_eq!(original.strong_count(), 1);
+        drop(tracked);
+        assert_eq!(original.strong_count(), 0);
+
+        let preview = EventBody::spooled_preview(&spooled, &HeaderMap::new());
+        assert!(preview.truncated);
+        assert_eq!(preview.len(), 11);
+
+        drop(spooled);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_peek_body() {
+        let chunks = ["hel", "lo ", "world"].map(Ok::<_, io::Error>);
+        let mut body = Body::wrap_stream(futures::stream::iter(chunks));
+
+        assert_eq!(peek_body(&mut body, 5).await.unwrap(), "hello");
+        assert_eq!(to_bytes(body).await.unwrap(), "hello world");
+
+        let mut body = Body::from("hi");
+        assert_eq!(peek_body(&mut body, 5).await.unwrap(), "hi");
+        assert_eq!(to_bytes(body).await.unwrap(), "hi");
+    }
+}
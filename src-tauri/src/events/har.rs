@@ -0,0 +1,548 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{SecondsFormat, TimeZone};
+use http::{
+    header::{
+        CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE,
+        TRANSFER_ENCODING,
+    },
+    HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, Version,
+};
+use hyper::{Body, Response};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::processors::processor_effect::ProcessorEffects;
+
+use super::{body::EventBody, RequestEvent, ResponseEvent};
+
+/// HTTP Archive 1.2, see http://www.softwareishard.com/blog/har-12-spec/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarEntry {
+    pub started_date_time: String,
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: HarCache,
+    #[serde(default)]
+    pub timings: HarTimings,
+    /// What the proxy did to the exchange, ignored by other tools.
+    #[serde(rename = "_proxyman", default, skip_serializing_if = "Option::is_none")]
+    pub proxyman: Option<HarProxyman>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarNameValue>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    #[serde(default)]
+    pub query_string: Vec<HarNameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarNameValue>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL", default)]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarPostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub params: Vec<HarNameValue>,
+    /// `base64` for binary bodies, which HAR has no field for, absent for text.
+    #[serde(rename = "_encoding", default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarContent {
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `base64` for binary content, absent for text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct HarCache {}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct HarTimings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HarProxyman {
+    pub id: Uuid,
+    #[serde(default)]
+    pub replay_of: Option<Uuid>,
+    #[serde(default)]
+    pub effects: Option<ProcessorEffects>,
+}
+
+impl Har {
+    /// Exchanges without a response yet are left out, HAR requires one for every entry.
+    pub fn new(exchanges: Vec<(RequestEvent, Option<ResponseEvent>)>) -> Self {
+        let entries = exchanges
+            .into_iter()
+            .filter_map(|(req, res)| Some(HarEntry::new(req, res?)))
+            .collect();
+
+        Self {
+            log: HarLog {
+                version: "1.2".into(),
+                creator: HarCreator {
+                    name: "proxyman".into(),
+                    version: env!("CARGO_PKG_VERSION").into(),
+                },
+                entries,
+            },
+        }
+    }
+}
+
+impl HarEntry {
+    fn new(req: RequestEvent, res: ResponseEvent) -> Self {
+        // the proxy only knows when the request arrives, when the response head arrives and when
+        // the body is complete
+        let wait = (res.time - req.time).max(0) as f64;
+        let receive = res.complete_time.map_or(0.0, |complete_time| {
+            (complete_time - res.time).max(0) as f64
+        });
+        let time = wait + receive;
+
+        Self {
+            started_date_time: chrono::Local
+                .timestamp_millis_opt(req.time)
+                .single()
+                .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, false))
+                .unwrap_or_default(),
+            time,
+            request: HarRequest::new(&req),
+            response: HarResponse::new(&res),
+            cache: HarCache::default(),
+            timings: HarTimings {
+                send: 0.0,
+                wait,
+                receive,
+            },
+            proxyman: Some(HarProxyman {
+                id: req.id,
+                replay_of: req.replay_of,
+                effects: res.effects,
+            }),
+        }
+    }
+
+    /// Turn an archived entry back into events, e.g. to browse it like captured exchanges.
+    pub fn to_events(&self) -> Result<(RequestEvent, ResponseEvent), String> {
+        let proxyman = self.proxyman.as_ref();
+
+        let id = proxyman.map_or_else(Uuid::new_v4, |proxyman| proxyman.id);
+        let replay_of = proxyman.and_then(|proxyman| proxyman.replay_of);
+        let time = chrono::DateTime::parse_from_rfc3339(&self.started_date_time)
+            .map(|time| time.timestamp_millis())
+            .unwrap_or_else(|_| chrono::Local::now().timestamp_millis());
+
+        let uri: Uri = self
+            .request
+            .url
+            .parse()
+            .map_err(|e| format!("invalid url `{}`: {e}", self.request.url))?;
+
+        let req_headers = header_map(&self.request.headers);
+        let req_body = self
+            .request
+            .post_data
+            .as_ref()
+            .map(HarPostData::bytes)
+            .unwrap_or_default();
+        let res_headers = header_map(&self.response.headers);
+
+        let req = RequestEvent {
+            id,
+            method: self
+                .request
+                .method
+                .parse()
+                .map_err(|_| format!("invalid method `{}`", self.request.method))?,
+            uri: uri.clone(),
+            version: parse_http_version(&self.request.http_version),
+            body: EventBody::new(&req_body, &req_headers),
+            headers: req_headers,
+            time,
+            replay_of,
+            decode_error: None,
+        };
+
+        let res = ResponseEvent {
+            id,
+            uri,
+            status: StatusCode::from_u16(self.response.status)
+                .map_err(|_| format!("invalid status `{}`", self.response.status))?,
+            version: parse_http_version(&self.response.http_version),
+            body: EventBody::new(&self.response.content.bytes(), &res_headers),
+            headers: res_headers,
+            effects: proxyman.and_then(|proxyman| proxyman.effects.clone()),
+            time: time + self.time as i64,
+            replay_of,
+            streaming: false,
+            complete_time: None,
+            decode_error: None,
+        };
+
+        Ok((req, res))
+    }
+}
+
+impl HarRequest {
+    fn new(req: &RequestEvent) -> Self {
+        let post_data = (!req.body.is_empty())
+            .then(|| HarPostData::new(mime_type(&req.headers), &req.body.bytes()));
+
+        Self {
+            method: req.method.to_string(),
+            url: req.uri.to_string(),
+            http_version: http_version(req.version),
+            cookies: req
+                .headers
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|cookie| cookie.to_str().ok())
+                .flat_map(|cookie| cookie.split(';'))
+                .filter_map(parse_cookie)
+                .collect(),
+            headers: headers(&req.headers),
+            query_string: req.uri.query().map(parse_query).unwrap_or_default(),
+            post_data,
+            headers_size: -1,
+            body_size: req.body.len() as i64,
+        }
+    }
+}
+
+impl HarResponse {
+    fn new(res: &ResponseEvent) -> Self {
+        Self {
+            status: res.status.as_u16(),
+            status_text: res.status.canonical_reason().unwrap_or_default().into(),
+            http_version: http_version(res.version),
+            cookies: res
+                .headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|cookie| cookie.to_str().ok())
+                .filter_map(|cookie| parse_cookie(cookie.split(';').next()?))
+                .collect(),
+            headers: headers(&res.headers),
+            content: HarContent::new(mime_type(&res.headers), &res.body.bytes()),
+            redirect_url: res
+                .headers
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .unwrap_or_default()
+                .into(),
+            headers_size: -1,
+            body_size: res.body.len() as i64,
+        }
+    }
+}
+
+impl HarResponse {
+    /// Answer with the archived response, its content is decoded already.
+    pub fn to_response(&self) -> Response<Body> {
+        let body = self.content.bytes();
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        *res.headers_mut() = header_map(&self.headers);
+
+        let headers = res.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(TRANSFER_ENCODING);
+        headers.insert(CONTENT_LENGTH, body.len().into());
+
+        *res.body_mut() = Body::from(body);
+        res
+    }
+}
+
+impl HarPostData {
+    pub fn bytes(&self) -> Vec<u8> {
+        match self.encoding.as_deref() {
+            Some("base64") => BASE64.decode(&self.text).unwrap_or_default(),
+            _ => self.text.as_bytes().to_vec(),
+        }
+    }
+
+    fn new(mime_type: String, body: &[u8]) -> Self {
+        let (text, encoding) = match std::str::from_utf8(body) {
+            Ok(text) if is_text(&mime_type) => (text.to_string(), None),
+            _ => (BASE64.encode(body), Some("base64".into())),
+        };
+        let params =
+            if encoding.is_none() && mime_type.starts_with("application/x-www-form-urlencoded") {
+                parse_query(&text)
+            } else {
+                vec![]
+            };
+
+        Self {
+            mime_type,
+            text,
+            params,
+            encoding,
+        }
+    }
+}
+
+impl HarContent {
+    pub fn bytes(&self) -> Vec<u8> {
+        match (&self.text, self.encoding.as_deref()) {
+            (Some(text), Some("base64")) => BASE64.decode(text).unwrap_or_default(),
+            (Some(text), _) => text.as_bytes().to_vec(),
+            (None, _) => vec![],
+        }
+    }
+
+    fn new(mime_type: String, body: &[u8]) -> Self {
+        let (text, encoding) = match std::str::from_utf8(body) {
+            Ok(text) if is_text(&mime_type) => (text.to_string(), None),
+            _ => (BASE64.encode(body), Some("base64".into())),
+        };
+
+        Self {
+            size: body.len() as i64,
+            mime_type,
+            text: Some(text),
+            encoding,
+        }
+    }
+}
+
+fn is_text(mime_type: &str) -> bool {
+    let mime_type = mime_type.to_ascii_lowercase();
+
+    mime_type.is_empty()
+        || mime_type.starts_with("text/")
+        || ["json", "xml", "javascript", "x-www-form-urlencoded"]
+            .iter()
+            .any(|text| mime_type.contains(text))
+}
+
+fn mime_type(headers: &HeaderMap) -> String {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|mime| mime.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn http_version(version: Version) -> String {
+    format!("{:?}", version)
+}
+
+fn parse_http_version(version: &str) -> Version {
+    match version.to_ascii_uppercase().as_str() {
+        "HTTP/0.9" => Version::HTTP_09,
+        "HTTP/1.0" => Version::HTTP_10,
+        "HTTP/2" | "HTTP/2.0" | "H2" => Version::HTTP_2,
+        "HTTP/3" | "HTTP/3.0" | "H3" => Version::HTTP_3,
+        _ => Version::HTTP_11,
+    }
+}
+
+/// Invalid headers are dropped, e.g. pseudo headers of HTTP/2 exported by browsers.
+fn header_map(headers: &[HarNameValue]) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|header| {
+            Some((
+                HeaderName::from_bytes(header.name.as_bytes()).ok()?,
+                HeaderValue::from_str(&header.value).ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn headers(headers: &HeaderMap) -> Vec<HarNameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| HarNameValue {
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+fn parse_cookie(cookie: &str) -> Option<HarNameValue> {
+    let (name, value) = cookie.trim().split_once('=')?;
+
+    Some(HarNameValue {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_query(query: &str) -> Vec<HarNameValue> {
+    let decode = |s: &str| {
+        percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            HarNameValue {
+                name: decode(name),
+                value: decode(value),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request, Response};
+
+    use super::*;
+    use crate::events::ResponseCompleteEvent;
+
+    #[tokio::test]
+    async fn test_har_entry() {
+        let id = Uuid::new_v4();
+
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("https://www.x.com/search?q=a+b&lang=zh%2Dcn")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(COOKIE, "session=1; theme=dark")
+            .body(Body::from("page=1"))
+            .unwrap();
+        let req = RequestEvent::new(id, &mut req).await;
+
+        let mut res = Response::builder()
+            .header(CONTENT_TYPE, "image/png")
+            .header(SET_COOKIE, "visited=1; Path=/")
+            .body(Body::from(vec![0x89, 0x50, 0x4e, 0x47]))
+            .unwrap();
+        let res = ResponseEvent::new(id, req.uri.clone(), &mut res, None).await;
+
+        let har = serde_json::to_value(Har::new(vec![(req, Some(res))])).unwrap();
+        let entry = &har["log"]["entries"][0];
+
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(entry["request"]["httpVersion"], "HTTP/1.1");
+        assert_eq!(
+            entry["request"]["queryString"][1],
+            serde_json::json!({"name": "lang", "value": "zh-cn"})
+        );
+        assert_eq!(entry["request"]["cookies"][1]["name"], "theme");
+        assert_eq!(entry["request"]["postData"]["params"][0]["value"], "1");
+        assert!(entry["request"]["postData"].get("_encoding").is_none());
+        assert_eq!(entry["response"]["cookies"][0]["value"], "1");
+        assert_eq!(entry["response"]["content"]["encoding"], "base64");
+        assert_eq!(entry["_proxyman"]["id"], id.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_har_binary_and_streamed_entry() {
+        let id = Uuid::new_v4();
+        let body = vec![0x00, 0xff, 0xfe, 0x01];
+
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("https://www.x.com/upload")
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(body.clone()))
+            .unwrap();
+        let mut req = RequestEvent::new(id, &mut req).await;
+        req.time = 1_000;
+
+        let mut res = Response::builder()
+            .header(CONTENT_TYPE, "text/event-stream")
+            .body(Body::from("data: 1\n\n"))
+            .unwrap();
+        let mut res = ResponseEvent::new(id, req.uri.clone(), &mut res, None).await;
+        res.time = 1_100;
+        let mut complete = ResponseCompleteEvent::new(id, res.body.clone(), None);
+        complete.time = 1_600;
+        res.complete(complete);
+
+        let har = Har::new(vec![(req, Some(res))]);
+        let entry = &har.log.entries[0];
+
+        let post_data = entry.request.post_data.as_ref().unwrap();
+        assert_eq!(post_data.encoding.as_deref(), Some("base64"));
+        assert_eq!(post_data.bytes(), body);
+        assert_eq!(entry.time, 600.0);
+        assert_eq!(entry.timings.wait, 100.0);
+        assert_eq!(entry.timings.receive, 500.0);
+
+        let (req, _) = entry.to_events().unwrap();
+        assert_eq!(req.body.bytes().as_ref(), body.as_slice());
+    }
+}
@@ -1,15 +1,29 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use bytes::Bytes;
 use http::{HeaderMap, Method, StatusCode, Uri, Version};
-use hyper::{body::to_bytes, Body, Request, Response};
-use serde::Serialize;
+use hyper::{Body, Request, Response};
+use serde::{Deserialize, Serialize};
+use std::{io, time::Duration};
+use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
-use crate::processors::processor_effect::ProcessorEffects;
+use crate::processors::{http_processor::HttpPhase, processor_effect::ProcessorEffects};
+
+use self::body::{buffer_body, EventBody, SpooledBody};
+
+pub(crate) mod body;
+pub(crate) mod har;
+pub(crate) mod snippet;
 
 #[derive(Debug, Serialize, Clone)]
 pub enum Events {
     NewRequest(RequestEvent),
     NewResponse(ResponseEvent),
+    ResponseComplete(ResponseCompleteEvent),
+    WebsocketOpen(WebsocketOpenEvent),
+    WebsocketMessage(WebsocketMessageEvent),
+    WebsocketClose(WebsocketCloseEvent),
+    Breakpoint(BreakpointEvent),
 }
 
 impl From<RequestEvent> for Events {
@@ -24,7 +38,51 @@ impl From<ResponseEvent> for Events {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+impl From<ResponseCompleteEvent> for Events {
+    fn from(value: ResponseCompleteEvent) -> Self {
+        Self::ResponseComplete(value)
+    }
+}
+
+impl From<WebsocketOpenEvent> for Events {
+    fn from(value: WebsocketOpenEvent) -> Self {
+        Self::WebsocketOpen(value)
+    }
+}
+
+impl From<WebsocketMessageEvent> for Events {
+    fn from(value: WebsocketMessageEvent) -> Self {
+        Self::WebsocketMessage(value)
+    }
+}
+
+impl From<WebsocketCloseEvent> for Events {
+    fn from(value: WebsocketCloseEvent) -> Self {
+        Self::WebsocketClose(value)
+    }
+}
+
+impl From<BreakpointEvent> for Events {
+    fn from(value: BreakpointEvent) -> Self {
+        Self::Breakpoint(value)
+    }
+}
+
+impl Events {
+    /// The body carried by the event, along with where it's spooled if it's truncated.
+    pub fn body(&self) -> Option<(Uuid, HttpPhase, &EventBody)> {
+        match self {
+            Self::NewRequest(req) => Some((req.id, HttpPhase::Request, &req.body)),
+            Self::NewResponse(res) => Some((res.id, HttpPhase::Response, &res.body)),
+            Self::ResponseComplete(complete) => {
+                Some((complete.id, HttpPhase::Response, &complete.body))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct RequestEvent {
     id: Uuid,
@@ -36,31 +94,63 @@ pub struct RequestEvent {
     version: Version,
     #[serde(with = "http_serde::header_map")]
     headers: HeaderMap,
-    body: String,
+    #[serde(flatten)]
+    body: EventBody,
     time: i64,
+    /// The captured request which this one is replayed from.
+    replay_of: Option<Uuid>,
+    /// Why the body is passed through as it's encoded.
+    decode_error: Option<String>,
 }
 
 impl RequestEvent {
     pub async fn new(id: Uuid, req: &mut Request<Body>) -> Self {
-        let mut body = req.body_mut();
-        let body_bytes = to_bytes(&mut body).await.unwrap_or_default();
-        *body = Body::from(body_bytes.clone());
+        let body = capture_body(id, req.body_mut()).await;
+        let body = EventBody::capture(id, HttpPhase::Request, &body, req.headers()).await;
+        Self::with_body(id, req, body)
+    }
 
-        let body_str = transform_bytes_to_string(body_bytes);
+    /// Like `new`, but the body is already read and spooled.
+    pub fn spooled<T>(id: Uuid, req: &Request<T>, body: &SpooledBody) -> Self {
+        Self::with_body(id, req, EventBody::spooled_preview(body, req.headers()))
+    }
 
+    fn with_body<T>(id: Uuid, req: &Request<T>, body: EventBody) -> Self {
         Self {
             id,
             method: req.method().clone(),
             uri: req.uri().clone(),
             version: req.version(),
             headers: req.headers().clone(),
-            body: body_str,
+            body,
             time: chrono::Local::now().timestamp_millis(),
+            replay_of: None,
+            decode_error: None,
         }
     }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Put the full body back if only a preview is carried.
+    pub async fn load_body(mut self) -> io::Result<Self> {
+        self.body = self.body.load(self.id, HttpPhase::Request).await?;
+        Ok(self)
+    }
+
+    pub fn replay_of(mut self, replay_of: Option<Uuid>) -> Self {
+        self.replay_of = replay_of;
+        self
+    }
+
+    pub fn decode_error(mut self, decode_error: Option<String>) -> Self {
+        self.decode_error = decode_error;
+        self
+    }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseEvent {
     id: Uuid,
@@ -72,9 +162,19 @@ pub struct ResponseEvent {
     version: Version,
     #[serde(with = "http_serde::header_map")]
     headers: HeaderMap,
-    body: String,
+    #[serde(flatten)]
+    body: EventBody,
     effects: Option<ProcessorEffects>,
     time: i64,
+    replay_of: Option<Uuid>,
+    /// The body is still on the way, it comes with `ResponseCompleteEvent`.
+    #[serde(default)]
+    streaming: bool,
+    /// When the streamed body is complete, only known to exported exchanges.
+    #[serde(skip)]
+    complete_time: Option<i64>,
+    /// Why the body is passed through as it's encoded.
+    decode_error: Option<String>,
 }
 
 impl ResponseEvent {
@@ -84,27 +184,259 @@ impl ResponseEvent {
         res: &mut Response<Body>,
         effects: Option<ProcessorEffects>,
     ) -> Self {
-        let mut body = res.body_mut();
-        let body_bytes = to_bytes(&mut body).await.unwrap_or_default();
-        *body = Body::from(body_bytes.clone());
+        let body = capture_body(id, res.body_mut()).await;
 
-        let body_str = transform_bytes_to_string(body_bytes);
+        Self {
+            id,
+            uri,
+            status: res.status(),
+            version: res.version(),
+            headers: res.headers().clone(),
+            body: EventBody::capture(id, HttpPhase::Response, &body, res.headers()).await,
+            effects,
+            time: chrono::Local::now().timestamp_millis(),
+            replay_of: None,
+            streaming: false,
+            complete_time: None,
+            decode_error: None,
+        }
+    }
 
+    /// A response whose body is streamed to the client, only the head is captured for now.
+    pub fn streaming<T>(
+        id: Uuid,
+        uri: Uri,
+        res: &Response<T>,
+        effects: Option<ProcessorEffects>,
+    ) -> Self {
         Self {
             id,
             uri,
             status: res.status(),
             version: res.version(),
             headers: res.headers().clone(),
-            body: body_str,
+            body: EventBody::default(),
             effects,
             time: chrono::Local::now().timestamp_millis(),
+            replay_of: None,
+            streaming: true,
+            complete_time: None,
+            decode_error: None,
+        }
+    }
+
+    pub fn decode_error(mut self, decode_error: Option<String>) -> Self {
+        self.decode_error = decode_error;
+        self
+    }
+
+    pub fn complete(&mut self, complete: ResponseCompleteEvent) {
+        self.body = complete.body;
+        self.streaming = false;
+        self.complete_time = Some(complete.time);
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn replay_of(mut self, replay_of: Option<Uuid>) -> Self {
+        self.replay_of = replay_of;
+        self
+    }
+}
+
+/// The body of a streamed response has ended, the head was sent earlier as `NewResponse`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCompleteEvent {
+    id: Uuid,
+    #[serde(flatten)]
+    body: EventBody,
+    /// Why the stream is cut off, if it is.
+    error: Option<String>,
+    time: i64,
+}
+
+impl ResponseCompleteEvent {
+    pub fn new(id: Uuid, body: EventBody, error: Option<String>) -> Self {
+        Self {
+            id,
+            body,
+            error,
+            time: chrono::Local::now().timestamp_millis(),
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebsocketDirection {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Upstream accepted the websocket handshake, `id` is the same as the upgrade request.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsocketOpenEvent {
+    id: Uuid,
+    #[serde(with = "http_serde::uri")]
+    uri: Uri,
+    #[serde(with = "http_serde::status_code")]
+    status: StatusCode,
+    #[serde(with = "http_serde::header_map")]
+    headers: HeaderMap,
+    time: i64,
+}
+
+impl WebsocketOpenEvent {
+    pub fn new<T>(id: Uuid, uri: Uri, res: &Response<T>) -> Self {
+        Self {
+            id,
+            uri,
+            status: res.status(),
+            headers: res.headers().clone(),
+            time: chrono::Local::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsocketMessageEvent {
+    id: Uuid,
+    direction: WebsocketDirection,
+    opcode: &'static str,
+    /// Text frames are sent as is, others are base64 encoded.
+    payload: String,
+    payload_encoding: &'static str,
+    /// Payload length in bytes before encoding.
+    length: usize,
+    time: i64,
+}
+
+impl WebsocketMessageEvent {
+    pub fn new(id: Uuid, direction: WebsocketDirection, message: &Message) -> Self {
+        let (opcode, payload, payload_encoding, length) = match message {
+            Message::Text(text) => ("text", text.clone(), "utf8", text.len()),
+            Message::Binary(data) => ("binary", BASE64.encode(data), "base64", data.len()),
+            Message::Ping(data) => ("ping", BASE64.encode(data), "base64", data.len()),
+            Message::Pong(data) => ("pong", BASE64.encode(data), "base64", data.len()),
+            Message::Close(frame) => {
+                let reason = frame
+                    .as_ref()
+                    .map(|frame| frame.reason.to_string())
+                    .unwrap_or_default();
+                let length = reason.len();
+                ("close", reason, "utf8", length)
+            }
+            Message::Frame(frame) => (
+                "frame",
+                BASE64.encode(frame.payload()),
+                "base64",
+                frame.payload().len(),
+            ),
+        };
+
+        Self {
+            id,
+            direction,
+            opcode,
+            payload,
+            payload_encoding,
+            length,
+            time: chrono::Local::now().timestamp_millis(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsocketCloseEvent {
+    id: Uuid,
+    /// The side which closed the connection first, `None` if it's closed by the proxy.
+    direction: Option<WebsocketDirection>,
+    code: Option<u16>,
+    reason: Option<String>,
+    time: i64,
+}
+
+impl WebsocketCloseEvent {
+    pub fn new(
+        id: Uuid,
+        direction: Option<WebsocketDirection>,
+        code: Option<u16>,
+        reason: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            direction,
+            code,
+            reason,
+            time: chrono::Local::now().timestamp_millis(),
+        }
+    }
+}
+
+/// An exchange is paused, the UI is expected to resume it within `timeout` milliseconds.
+/// `method` is only set for requests, and `status` for responses.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakpointEvent {
+    id: Uuid,
+    phase: HttpPhase,
+    method: Option<String>,
+    #[serde(with = "http_serde::uri")]
+    uri: Uri,
+    status: Option<u16>,
+    #[serde(with = "http_serde::header_map")]
+    headers: HeaderMap,
+    #[serde(flatten)]
+    body: EventBody,
+    timeout: u64,
+    time: i64,
+}
+
+impl BreakpointEvent {
+    pub async fn request(id: Uuid, req: &mut Request<Body>, timeout: Duration) -> Self {
+        Self {
+            id,
+            phase: HttpPhase::Request,
+            method: Some(req.method().to_string()),
+            uri: req.uri().clone(),
+            status: None,
+            headers: req.headers().clone(),
+            body: EventBody::new(&capture_body(id, req.body_mut()).await, req.headers()),
+            timeout: timeout.as_millis() as u64,
+            time: chrono::Local::now().timestamp_millis(),
+        }
+    }
+
+    pub async fn response(id: Uuid, uri: Uri, res: &mut Response<Body>, timeout: Duration) -> Self {
+        Self {
+            id,
+            phase: HttpPhase::Response,
+            method: None,
+            uri,
+            status: Some(res.status().as_u16()),
+            headers: res.headers().clone(),
+            body: EventBody::new(&capture_body(id, res.body_mut()).await, res.headers()),
+            timeout: timeout.as_millis() as u64,
+            time: chrono::Local::now().timestamp_millis(),
         }
     }
 }
 
-fn transform_bytes_to_string(bytes: Bytes) -> String {
-    String::from_utf8(bytes.into())
-        .map_err(|non_utf8| String::from_utf8_lossy(non_utf8.as_bytes()).into_owned())
-        .unwrap_or_else(|_| "parsing failed".into())
+/// Read the whole body for capturing, it's captured as empty if it fails midway, e.g. the
+/// client is gone.
+async fn capture_body(id: Uuid, body: &mut Body) -> Bytes {
+    buffer_body(body).await.unwrap_or_else(|e| {
+        log::warn!("capture the body of {id} failed: {e}");
+        Bytes::new()
+    })
 }
@@ -0,0 +1,228 @@
+use http::{
+    header::{ACCEPT_ENCODING, CONTENT_LENGTH, HOST},
+    HeaderName, Method,
+};
+use serde::{Deserialize, Serialize};
+
+use super::RequestEvent;
+
+/// Code which sends the same request as the captured one.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum SnippetFormat {
+    Curl,
+    Httpie,
+    Fetch,
+}
+
+impl RequestEvent {
+    pub fn to_snippet(&self, format: SnippetFormat) -> String {
+        match format {
+            SnippetFormat::Curl => self.to_curl(),
+            SnippetFormat::Httpie => self.to_httpie(),
+            SnippetFormat::Fetch => self.to_fetch(),
+        }
+    }
+
+    /// Headers worth copying in order, each value of a repeated header on its own, the ones
+    /// derived from the url and the body are left to the tools.
+    fn snippet_headers(&self) -> impl Iterator<Item = (&HeaderName, String)> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| **name != HOST && **name != CONTENT_LENGTH)
+            .map(|(name, value)| (name, String::from_utf8_lossy(value.as_bytes()).into_owned()))
+    }
+
+    fn to_curl(&self) -> String {
+        // `[]` and `{}` in the url are globs to curl otherwise
+        let mut args = vec![format!(
+            "curl --globoff {}",
+            shell_quote(&self.uri.to_string())
+        )];
+
+        // curl waits for the body of a `HEAD` response with `-X HEAD`
+        if self.method == Method::HEAD {
+            args.push("--head".into());
+        } else if self.method != Method::GET {
+            args.push(format!("-X {}", self.method));
+        }
+
+        for (name, value) in self.snippet_headers() {
+            args.push(format!("-H {}", shell_quote(&format!("{name}: {value}"))));
+        }
+
+        if self.headers.contains_key(ACCEPT_ENCODING) {
+            // otherwise curl prints the compressed body as is
+            args.push("--compressed".into());
+        }
+
+        if self.body.is_binary() {
+            args.push("--data-binary @-".into());
+        } else if !self.body.is_empty() {
+            args.push(format!("--data-raw {}", shell_quote(self.body.encoded())));
+        }
+
+        self.pipe_binary_body(args.join(" \\\n  "))
+    }
+
+    fn to_httpie(&self) -> String {
+        let mut args = vec![format!(
+            "http {} {}",
+            self.method,
+            shell_quote(&self.uri.to_string())
+        )];
+
+        for (name, value) in self.snippet_headers() {
+            // `Name;` sends a header with an empty value, `Name:` would remove it instead
+            let item = if value.is_empty() {
+                format!("{name};")
+            } else {
+                format!("{name}:{value}")
+            };
+            args.push(shell_quote(&item));
+        }
+
+        // binary bodies are piped in, which httpie reads as the body
+        if !self.body.is_binary() && !self.body.is_empty() {
+            args.push(format!("--raw {}", shell_quote(self.body.encoded())));
+        }
+
+        self.pipe_binary_body(args.join(" \\\n  "))
+    }
+
+    /// Shells can't hold arbitrary bytes in arguments, binary bodies are decoded from base64
+    /// into the stdin of the command instead.
+    fn pipe_binary_body(&self, command: String) -> String {
+        if self.body.is_binary() {
+            format!(
+                "echo {} | base64 -d | {command}",
+                shell_quote(self.body.encoded())
+            )
+        } else {
+            command
+        }
+    }
+
+    fn to_fetch(&self) -> String {
+        let options = FetchOptions {
+            method: self.method.as_str(),
+            headers: self
+                .snippet_headers()
+                .map(|(name, value)| (name.as_str(), value))
+                .collect(),
+            body: Some(self.body.encoded())
+                .filter(|body| !body.is_empty() && !self.body.is_binary()),
+        };
+
+        // JSON is valid JavaScript, and takes care of the escaping
+        let url = serde_json::Value::from(self.uri.to_string());
+        let options = serde_json::to_string_pretty(&options).unwrap_or_default();
+
+        if self.body.is_binary() {
+            format!(
+                "const body = Uint8Array.from(atob({}), (c) => c.charCodeAt(0));\n\
+                 fetch({url}, {{ ...{options}, body }});",
+                serde_json::Value::from(self.body.encoded())
+            )
+        } else {
+            format!("fetch({url}, {options});")
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FetchOptions<'a> {
+    method: &'a str,
+    /// `[name, value]` pairs rather than an object, which can't hold a header twice.
+    headers: Vec<(&'a str, String)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+}
+
+/// Quote for POSIX shells, nothing is special within single quotes but the quote itself.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_snippet() {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("https://www.x.com/api?q=it's")
+            .header(HOST, "www.x.com")
+            .header("x-token", "a b")
+            .header(CONTENT_LENGTH, "9")
+            .body(Body::from(r#"{"a":"'"}"#))
+            .unwrap();
+        let event = RequestEvent::new(Uuid::new_v4(), &mut req).await;
+
+        assert_eq!(
+            event.to_snippet(SnippetFormat::Curl),
+            "curl --globoff 'https://www.x.com/api?q=it'\\''s' \\\n  \
+             -X POST \\\n  \
+             -H 'x-token: a b' \\\n  \
+             --data-raw '{\"a\":\"'\\''\"}'"
+        );
+        assert_eq!(
+            event.to_snippet(SnippetFormat::Httpie),
+            "http POST 'https://www.x.com/api?q=it'\\''s' \\\n  \
+             'x-token:a b' \\\n  \
+             --raw '{\"a\":\"'\\''\"}'"
+        );
+        assert!(event
+            .to_snippet(SnippetFormat::Fetch)
+            .contains(r#""body": "{\"a\":\"'\"}""#));
+    }
+
+    #[tokio::test]
+    async fn test_curl_head_snippet() {
+        let mut req = Request::builder()
+            .method("HEAD")
+            .uri("https://www.x.com/?ids[]=1")
+            .body(Body::empty())
+            .unwrap();
+        let event = RequestEvent::new(Uuid::new_v4(), &mut req).await;
+
+        assert_eq!(
+            event.to_snippet(SnippetFormat::Curl),
+            "curl --globoff 'https://www.x.com/?ids[]=1' \\\n  --head"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snippet_repeated_headers() {
+        let mut req = Request::builder()
+            .uri("https://www.x.com/")
+            .header("x-tag", "a")
+            .header("x-tag", "b")
+            .body(Body::empty())
+            .unwrap();
+        let event = RequestEvent::new(Uuid::new_v4(), &mut req).await;
+
+        assert_eq!(
+            event.to_snippet(SnippetFormat::Curl),
+            "curl --globoff 'https://www.x.com/' \\\n  -H 'x-tag: a' \\\n  -H 'x-tag: b'"
+        );
+        assert_eq!(
+            event.to_snippet(SnippetFormat::Httpie),
+            "http GET 'https://www.x.com/' \\\n  'x-tag:a' \\\n  'x-tag:b'"
+        );
+        let fetch = event.to_snippet(SnippetFormat::Fetch);
+        let options = fetch
+            .strip_prefix("fetch(\"https://www.x.com/\", ")
+            .and_then(|fetch| fetch.strip_suffix(");"))
+            .unwrap();
+        let options: serde_json::Value = serde_json::from_str(options).unwrap();
+        assert_eq!(
+            options["headers"],
+            serde_json::json!([["x-tag", "a"], ["x-tag", "b"]])
+        );
+    }
+}
@@ -8,6 +8,7 @@ mod error;
 mod events;
 mod processors;
 mod proxy;
+mod session;
 mod sys_events;
 mod window;
 
@@ -25,6 +26,9 @@ fn main() {
                 return Err(Box::new(e));
             }
 
+            events::body::set_preview_limit(app_conf::get_app_setting().body_preview_limit());
+            events::body::clear_spool();
+
             proxy::set_proxy_state(app);
 
             let menu = window::build_menu(app)?;
@@ -60,6 +64,17 @@ fn main() {
             commands::processor::add_processor_pack,
             commands::processor::remove_processor_pack,
             commands::processor::update_processor_pack_status,
+            commands::processor::update_processor_pack_continue,
+            commands::processor::reorder_processor_packs,
+            commands::breakpoint::resume_breakpoint,
+            commands::replay::replay_request,
+            commands::body::get_body,
+            commands::session::get_sessions,
+            commands::session::open_session,
+            commands::session::remove_session,
+            commands::session::import_har,
+            commands::session::export_har,
+            commands::snippet::request_snippet,
             commands::global_proxy::turn_on_global_proxy,
             commands::global_proxy::turn_off_global_proxy,
             commands::app_setting::set_app_setting,
@@ -2,21 +2,37 @@ use std::collections::HashMap;
 
 use async_trait::async_trait;
 use hyper::{Body, Request, Response};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    processors::processor::{self, RequestOrResponse},
+    processors::matcher::RequestSnapshot,
+    processors::processor::{self, ProcessedResponse, RequestOrResponse},
     processors::processor_effect::{ProcessorEffect, ProcessorEffects},
 };
 
-use super::{processor_id::ProcessorID, processor_pack::ProcessorPack, Processor};
+use super::{
+    processor_id::ProcessorID,
+    processor_pack::{sort_packs, ProcessorPack},
+    Processor,
+};
 
 use self::{
-    delay::RequestDelayProcessor, redirect::RequestRedirectProcessor, response::ResponseProcessor,
+    body::BodyProcessor, breakpoint::BreakpointProcessor, delay::RequestDelayProcessor,
+    header::HeaderProcessor, map_local::MapLocalProcessor, playback::PlaybackProcessor,
+    redirect::RequestRedirectProcessor, response::ResponseProcessor, script::ScriptProcessor,
+    status::ResponseStatusProcessor,
 };
 
+pub mod body;
+pub mod breakpoint;
 pub mod delay;
+pub mod header;
+pub mod map_local;
+pub mod playback;
 pub mod redirect;
 pub mod response;
+pub mod script;
+pub mod status;
 
 pub type RequestProcessResult = (
     RequestOrResponse,
@@ -33,14 +49,37 @@ pub type ResponseProcessResult = (
     Option<HashMap<String, String>>,
 );
 
+/// The phase of an exchange which a rule applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum HttpPhase {
+    Request,
+    Response,
+}
+
+impl HttpPhase {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "req" | "request" => Ok(Self::Request),
+            "res" | "response" => Ok(Self::Response),
+            _ => Err(format!("unknown phase `{s}`, expect req or res")),
+        }
+    }
+}
+
 #[async_trait]
-pub trait HttpRequestProcessor: Send + Sync + std::fmt::Debug {
+pub trait HttpRequestProcessor: Processor {
     async fn process_request(&self, req: Request<Body>) -> RequestProcessResult;
 }
 
 #[async_trait]
-pub trait HttpResponseProcessor: Send + Sync + std::fmt::Debug + Processor {
-    async fn process_response(&self, res: Response<Body>) -> ResponseProcessResult;
+pub trait HttpResponseProcessor: Processor {
+    /// `req` is the snapshot of the request actually sent to upstream.
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        res: Response<Body>,
+    ) -> ResponseProcessResult;
 }
 
 #[derive(Debug, Clone)]
@@ -70,7 +109,19 @@ impl HttpProcessor {
     }
 
     pub fn add_pack(&mut self, pack: ProcessorPack) {
-        self.packs.push(pack);
+        self.packs.insert(0, pack);
+    }
+
+    pub fn reorder_packs(&mut self, order: &[String]) {
+        sort_packs(&mut self.packs, order);
+    }
+
+    pub fn set_pack_continue(&mut self, pack_name: String, continue_matching: bool) {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                pack.set_continue(continue_matching)
+            }
+        }
     }
 
     pub fn remove_pack(&mut self, pack_name: String) {
@@ -112,6 +163,85 @@ impl HttpProcessor {
 
         None
     }
+
+    pub(crate) fn get_header_mut(&mut self, pack_name: String) -> Option<&mut HeaderProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_header_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_body_mut(&mut self, pack_name: String) -> Option<&mut BodyProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_body_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_map_local_mut(
+        &mut self,
+        pack_name: String,
+    ) -> Option<&mut MapLocalProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_map_local_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_playback_mut(&mut self, pack_name: String) -> Option<&mut PlaybackProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_playback_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_script_mut(&mut self, pack_name: String) -> Option<&mut ScriptProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_script_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_breakpoint_mut(
+        &mut self,
+        pack_name: String,
+    ) -> Option<&mut BreakpointProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_breakpoint_mut());
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn get_status_mut(
+        &mut self,
+        pack_name: String,
+    ) -> Option<&mut ResponseStatusProcessor> {
+        for pack in self.packs.iter_mut() {
+            if pack.pack_name == pack_name {
+                return Some(pack.get_status_mut());
+            }
+        }
+
+        None
+    }
 }
 
 #[async_trait]
@@ -123,8 +253,16 @@ impl processor::HttpProcessor for HttpProcessor {
         for pack in self.packs.iter() {
             if pack.is_enable() {
                 // process request
-                let processors: Vec<&dyn Processor> =
-                    vec![pack.get_redirect(), pack.get_response(), pack.get_delay()];
+                let processors: Vec<&dyn HttpRequestProcessor> = vec![
+                    pack.get_header(),
+                    pack.get_body(),
+                    pack.get_redirect(),
+                    pack.get_map_local(),
+                    pack.get_playback(),
+                    pack.get_response(),
+                    pack.get_script(),
+                    pack.get_delay(),
+                ];
 
                 let mut pack_effect = Vec::<ProcessorEffect>::new();
 
@@ -155,8 +293,68 @@ impl processor::HttpProcessor for HttpProcessor {
 
                 if !pack_effect.is_empty() {
                     effects.insert(pack.pack_name.clone(), pack_effect);
-                    // Break the matching if any a pack could be matched successfully.
-                    break;
+                    // Packs are matched by priority, the matching stops at the first hit pack
+                    // unless it's marked to continue. A mocked response ends it anyway.
+                    if !pack.is_continue() || processed_ret.res.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !effects.is_empty() {
+            processed_ret.processor_effects = Some(effects);
+        }
+
+        processed_ret
+    }
+
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        res: Response<Body>,
+    ) -> ProcessedResponse {
+        let mut processed_ret: ProcessedResponse = res.into();
+        let mut effects: ProcessorEffects = HashMap::new();
+
+        for pack in self.packs.iter() {
+            if pack.is_enable() {
+                // process response
+                let processors: Vec<&dyn HttpResponseProcessor> = vec![
+                    pack.get_status(),
+                    pack.get_header(),
+                    pack.get_body(),
+                    pack.get_script(),
+                ];
+
+                let mut pack_effect = Vec::<ProcessorEffect>::new();
+
+                // Match all rules that could be matched in a single pack.
+                for processor in processors.iter() {
+                    let (res, hit, info) = processor.process_response(req, processed_ret.res).await;
+
+                    log::trace!(
+                        "process_response result: pack({}), processor({}), hit({hit}), info({:?})",
+                        pack.pack_name,
+                        processor.name(),
+                        info
+                    );
+
+                    if hit {
+                        pack_effect.push(ProcessorEffect {
+                            name: processor.name(),
+                            info,
+                        });
+                    }
+
+                    processed_ret.res = res;
+                }
+
+                if !pack_effect.is_empty() {
+                    effects.insert(pack.pack_name.clone(), pack_effect);
+                    if !pack.is_continue() {
+                        break;
+                    }
                 }
             }
         }
@@ -168,7 +366,22 @@ impl processor::HttpProcessor for HttpProcessor {
         processed_ret
     }
 
-    async fn process_response(&self, res: Response<Body>) -> Response<Body> {
-        res
+    async fn request_breakpoint(&self, req: &mut Request<Body>) -> bool {
+        for pack in self.packs.iter() {
+            if pack.is_enable() && pack.get_breakpoint().matches_request(req).await {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    async fn response_breakpoint(&self, req: &RequestSnapshot) -> bool {
+        self.packs
+            .iter()
+            .any(|pack| pack.is_enable() && pack.get_breakpoint().matches_response(req))
     }
 }
+
+#[async_trait]
+impl processor::WebsocketProcessor for HttpProcessor {}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{
+    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    HeaderMap, StatusCode,
+};
+use hyper::{body::to_bytes, Body, Request, Response};
+use json_patch::{AddOperation, Patch, PatchOperation};
+use serde_json::Value;
+
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher, RequestSnapshot},
+    parser::{expect_token, ProcessorRuleParser},
+    Processor,
+};
+
+use super::{
+    HttpPhase, HttpRequestProcessor, HttpResponseProcessor, ProcessorID, RequestProcessResult,
+    ResponseProcessResult,
+};
+
+impl ProcessorID {
+    pub const BODY: ProcessorID = ProcessorID("Body");
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum BodyAction {
+    /// Replace all matches, `$1` like captures are supported in the replacement.
+    Regex {
+        pattern: regex::bytes::Regex,
+        replacement: String,
+    },
+    /// RFC 6902 JSON Patch.
+    JsonPatch(Patch),
+    /// Set the value at a JSON pointer, the member is created if it doesn't exist.
+    JsonSet { pointer: String, value: Value },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BodyMapping {
+    pub matcher: RequestMatcher,
+    pub phase: HttpPhase,
+    pub action: BodyAction,
+}
+
+impl AsRef<RequestMatcher> for BodyMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type BodyRule = Vec<BodyMapping>;
+
+/// Rewrites decoded request and response bodies, every matched rule is applied in order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BodyProcessor {
+    // compiled separately, so that rules of the other phase are never evaluated
+    request: CompiledRules<BodyMapping>,
+    response: CompiledRules<BodyMapping>,
+}
+
+impl BodyProcessor {
+    pub fn set_body_mapping(&mut self, mappings: BodyRule) {
+        let (request, response): (Vec<_>, Vec<_>) = mappings
+            .into_iter()
+            .partition(|mapping| mapping.phase == HttpPhase::Request);
+
+        self.request = CompiledRules::new(request);
+        self.response = CompiledRules::new(response);
+    }
+}
+
+/// Returns the hit info if the body is rewritten, or the error if the body can't be read, which
+/// leaves the body empty.
+async fn apply_actions(
+    actions: Vec<&BodyAction>,
+    headers: &mut HeaderMap,
+    body: &mut Body,
+) -> Result<Option<HashMap<String, String>>, hyper::Error> {
+    // Don't buffer the body if there is nothing to rewrite.
+    if actions.is_empty() {
+        return Ok(None);
+    }
+
+    // It's passed through as it's encoded, rewriting the encoded bytes would corrupt it.
+    if headers.contains_key(CONTENT_ENCODING) {
+        log::debug!("skip rewriting the body which isn't decoded");
+        return Ok(None);
+    }
+
+    let mut bytes = to_bytes(std::mem::take(body)).await?;
+
+    let mut hit_info = HashMap::<String, String>::new();
+
+    for action in actions {
+        if let Some((key, value)) = rewrite_bytes(action, &mut bytes) {
+            hit_info.insert(key, value);
+        }
+    }
+
+    if !hit_info.is_empty() {
+        headers.remove(CONTENT_LENGTH);
+    }
+
+    *body = Body::from(bytes);
+
+    if hit_info.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(hit_info))
+    }
+}
+
+/// Answer with 502 rather than sending on a message whose body is lost.
+fn unreadable_body(e: hyper::Error) -> (Response<Body>, HashMap<String, String>) {
+    log::error!("read body for rewriting failed: {e}");
+
+    let res = Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::empty())
+        .expect("Failed to build response");
+
+    (res, HashMap::from([(String::from("error"), e.to_string())]))
+}
+
+/// Returns the hit info of the action if the body is rewritten.
+fn rewrite_bytes(action: &BodyAction, bytes: &mut Bytes) -> Option<(String, String)> {
+    match action {
+        BodyAction::Regex {
+            pattern,
+            replacement,
+        } => {
+            if !pattern.is_match(bytes) {
+                return None;
+            }
+
+            *bytes = Bytes::from(
+                pattern
+                    .replace_all(bytes, replacement.as_bytes())
+                    .into_owned(),
+            );
+
+            Some((format!("regex {pattern}"), replacement.clone()))
+        }
+        BodyAction::JsonPatch(patch) => {
+            let mut json = parse_json(bytes)?;
+
+            if let Err(e) = json_patch::patch(&mut json, &patch.0) {
+                log::debug!("apply json patch failed: {e}");
+                return None;
+            }
+
+            *bytes = Bytes::from(serde_json::to_vec(&json).ok()?);
+
+            Some((
+                String::from("json-patch"),
+                serde_json::to_string(patch).unwrap_or_default(),
+            ))
+        }
+        BodyAction::JsonSet { pointer, value } => {
+            let mut json = parse_json(bytes)?;
+
+            match json.pointer_mut(pointer) {
+                Some(target) => *target = value.clone(),
+                None => {
+                    let add = PatchOperation::Add(AddOperation {
+                        path: pointer.clone(),
+                        value: value.clone(),
+                    });
+
+                    if let Err(e) = json_patch::patch(&mut json, &[add]) {
+                        log::debug!("set json pointer({pointer}) failed: {e}");
+                        return None;
+                    }
+                }
+            }
+
+            *bytes = Bytes::from(serde_json::to_vec(&json).ok()?);
+
+            Some((format!("json-set {pointer}"), value.to_string()))
+        }
+    }
+}
+
+fn parse_json(bytes: &Bytes) -> Option<Value> {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(json) => Some(json),
+        Err(e) => {
+            log::debug!("body is not json: {e}");
+            None
+        }
+    }
+}
+
+impl Processor for BodyProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::BODY
+    }
+}
+
+#[async_trait]
+impl HttpRequestProcessor for BodyProcessor {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
+        let actions: Vec<_> = self
+            .request
+            .matches(&mut req)
+            .await
+            .into_iter()
+            .map(|mapping| &mapping.action)
+            .collect();
+
+        let (mut parts, mut body) = req.into_parts();
+        let hit_info = apply_actions(actions, &mut parts.headers, &mut body).await;
+
+        let req = Request::from_parts(parts, body);
+
+        match hit_info {
+            Ok(Some(hit_info)) => (req.into(), true, Some(hit_info)),
+            Ok(None) => (req.into(), false, None),
+            Err(e) => {
+                let (res, hit_info) = unreadable_body(e);
+                ((req, res).into(), true, Some(hit_info))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HttpResponseProcessor for BodyProcessor {
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        res: Response<Body>,
+    ) -> ResponseProcessResult {
+        let actions = self
+            .response
+            .matches_snapshot(req)
+            .map(|mapping| &mapping.action)
+            .collect();
+
+        let (mut parts, mut body) = res.into_parts();
+        let hit_info = apply_actions(actions, &mut parts.headers, &mut body).await;
+
+        let res = Response::from_parts(parts, body);
+
+        match hit_info {
+            Ok(Some(hit_info)) => (res, true, Some(hit_info)),
+            Ok(None) => (res, false, None),
+            Err(e) => {
+                let (res, hit_info) = unreadable_body(e);
+                (res, true, Some(hit_info))
+            }
+        }
+    }
+}
+
+impl ProcessorRuleParser for BodyProcessor {
+    type Mapping = BodyMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## <matcher> <req|res> regex <pattern> <replacement>
+    /// ## <matcher> <req|res> json-set <json pointer> <json value>
+    /// ## <matcher> <req|res> json-patch <json patch>
+    /// https://api.x.com/user res regex "name":"[^"]*" "name":"mock"
+    /// https://api.x.com/user res json-set /data/name "mock"
+    /// https://api.x.com/user res json-patch [{"op": "remove", "path": "/data/age"}]
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        parse_body_line(line)
+    }
+}
+
+fn parse_body_line(line: &str) -> Result<BodyMapping, String> {
+    let (matcher, rest) = RequestMatcher::parse(line)?;
+    let (phase, rest) = expect_token(rest, "phase")?;
+    let (mode, rest) = expect_token(rest, "mode")?;
+
+    let phase = HttpPhase::parse(phase)?;
+
+    let action = match mode {
+        "regex" => {
+            let (pattern, replacement) = expect_token(rest, "pattern")?;
+            BodyAction::Regex {
+                pattern: regex::bytes::Regex::new(pattern)
+                    .map_err(|e| format!("invalid pattern `{pattern}`: {e}"))?,
+                replacement: replacement.to_string(),
+            }
+        }
+        "json-set" => {
+            let (pointer, value) = expect_token(rest, "json pointer")?;
+            if !pointer.starts_with('/') {
+                return Err(format!("json pointer `{pointer}` should start with `/`"));
+            }
+            BodyAction::JsonSet {
+                pointer: pointer.to_string(),
+                value: serde_json::from_str(value)
+                    .map_err(|e| format!("invalid json value `{value}`: {e}"))?,
+            }
+        }
+        "json-patch" => BodyAction::JsonPatch(
+            serde_json::from_str(rest).map_err(|e| format!("invalid json patch: {e}"))?,
+        ),
+        _ => {
+            return Err(format!(
+                "unknown mode `{mode}`, expect regex, json-set or json-patch"
+            ))
+        }
+    };
+
+    Ok(BodyMapping {
+        matcher,
+        phase,
+        action,
+    })
+}
+
+impl From<String> for BodyProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return BodyProcessor::default();
+        }
+
+        let mut processor = BodyProcessor::default();
+        processor.set_body_mapping(Self::parse_rule(value.as_str()));
+        processor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(rule: &str, body: &str) -> String {
+        let mapping = parse_body_line(rule).unwrap();
+        let mut bytes = Bytes::from(body.to_string());
+        rewrite_bytes(&mapping.action, &mut bytes);
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    fn rewrite_json(rule: &str, body: &str) -> Value {
+        serde_json::from_str(&rewrite(rule, body)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_skip_encoded_body() {
+        let mapping = parse_body_line(".* res regex a b").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, "x-custom".parse().unwrap());
+        let mut body = Body::from("aaa");
+
+        let hit_info = apply_actions(vec![&mapping.action], &mut headers, &mut body).await;
+
+        assert!(hit_info.unwrap().is_none());
+        assert_eq!(to_bytes(body).await.unwrap(), "aaa");
+    }
+
+    #[test]
+    fn test_rewrite_body() {
+        assert_eq!(
+            rewrite(
+                r#".* res regex "name":"([^"]*)" "name":"$1-mock""#,
+                r#"{"name":"a"}"#
+            ),
+            r#"{"name":"a-mock"}"#
+        );
+        assert_eq!(
+            rewrite_json(
+                r#".* res json-set /data/name {"first": "b"}"#,
+                r#"{"data":{"name":"a","age":1}}"#
+            ),
+            serde_json::json!({"data": {"name": {"first": "b"}, "age": 1}})
+        );
+        assert_eq!(
+            rewrite_json(r#".* res json-set /data/id 1"#, r#"{"data":{}}"#),
+            serde_json::json!({"data": {"id": 1}})
+        );
+        assert_eq!(
+            rewrite_json(
+                r#".* res json-patch [{"op": "remove", "path": "/data/age"}]"#,
+                r#"{"data":{"name":"a","age":1}}"#
+            ),
+            serde_json::json!({"data": {"name": "a"}})
+        );
+        assert_eq!(
+            rewrite(r#".* res json-set /data/id 1"#, "not json"),
+            "not json"
+        );
+        assert!(parse_body_line(".* res json-set data/id 1").is_err());
+        assert!(parse_body_line(".* res regex (unclosed x").is_err());
+    }
+}
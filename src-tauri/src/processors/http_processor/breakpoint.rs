@@ -0,0 +1,85 @@
+use hyper::{Body, Request};
+
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher, RequestSnapshot},
+    parser::{expect_last_token, ProcessorRuleParser},
+};
+
+use super::{HttpPhase, ProcessorID};
+
+impl ProcessorID {
+    pub const BREAKPOINT: ProcessorID = ProcessorID("Breakpoint");
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BreakpointMapping {
+    pub matcher: RequestMatcher,
+    pub phase: HttpPhase,
+}
+
+impl AsRef<RequestMatcher> for BreakpointMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type BreakpointRule = Vec<BreakpointMapping>;
+
+/// Decides which exchanges are paused for editing, the pausing itself is up to the tunnel.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BreakpointProcessor {
+    // compiled separately, so that rules of the other phase are never evaluated
+    request: CompiledRules<BreakpointMapping>,
+    response: CompiledRules<BreakpointMapping>,
+}
+
+impl BreakpointProcessor {
+    pub fn set_breakpoint_mapping(&mut self, mappings: BreakpointRule) {
+        let (request, response): (Vec<_>, Vec<_>) = mappings
+            .into_iter()
+            .partition(|mapping| mapping.phase == HttpPhase::Request);
+
+        self.request = CompiledRules::new(request);
+        self.response = CompiledRules::new(response);
+    }
+
+    pub async fn matches_request(&self, req: &mut Request<Body>) -> bool {
+        self.request.first_match(req).await.is_some()
+    }
+
+    pub fn matches_response(&self, req: &RequestSnapshot) -> bool {
+        self.response.matches_snapshot(req).next().is_some()
+    }
+}
+
+impl ProcessorRuleParser for BreakpointProcessor {
+    type Mapping = BreakpointMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## <matcher> <req|res>
+    /// https://api.x.com/user req
+    /// POST https://api.x.com/order json:/id=1 res
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let phase = expect_last_token(rest, "phase")?;
+
+        Ok(BreakpointMapping {
+            matcher,
+            phase: HttpPhase::parse(phase)?,
+        })
+    }
+}
+
+impl From<String> for BreakpointProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return BreakpointProcessor::default();
+        }
+
+        let mut processor = BreakpointProcessor::default();
+        processor.set_breakpoint_mapping(Self::parse_rule(value.as_str()));
+        processor
+    }
+}
@@ -1,11 +1,14 @@
 use async_trait::async_trait;
 use std::time::Duration;
 
-use regex::Regex;
 use tokio::time::sleep;
 
 use super::{HttpRequestProcessor, ProcessorID, RequestProcessResult};
-use crate::processors::{parser::ProcessorRuleParser, Processor};
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher},
+    parser::{expect_last_token, ProcessorRuleParser},
+    Processor,
+};
 
 impl ProcessorID {
     pub const DELAY: ProcessorID = ProcessorID("Delay");
@@ -13,20 +16,26 @@ impl ProcessorID {
 
 #[derive(Debug, Clone)]
 pub(crate) struct RequestDelayMapping {
-    pub req_pattern: String,
+    pub matcher: RequestMatcher,
     pub delay_millsec: u64,
 }
 
+impl AsRef<RequestMatcher> for RequestDelayMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
 pub(crate) type RequestDelayRule = Vec<RequestDelayMapping>;
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct RequestDelayProcessor {
-    mappings: Option<RequestDelayRule>,
+    mappings: Option<CompiledRules<RequestDelayMapping>>,
 }
 
 impl RequestDelayProcessor {
     pub fn set_delay_mapping(&mut self, mappings: RequestDelayRule) {
-        self.mappings = Some(mappings);
+        self.mappings = Some(CompiledRules::new(mappings));
     }
 }
 
@@ -38,20 +47,11 @@ impl Processor for RequestDelayProcessor {
 
 #[async_trait]
 impl HttpRequestProcessor for RequestDelayProcessor {
-    async fn process_request(&self, req: http::Request<hyper::Body>) -> RequestProcessResult {
+    async fn process_request(&self, mut req: http::Request<hyper::Body>) -> RequestProcessResult {
         if let Some(ref mappings) = self.mappings {
-            for RequestDelayMapping {
-                req_pattern,
-                delay_millsec,
-            } in mappings.iter()
+            if let Some(RequestDelayMapping { delay_millsec, .. }) =
+                mappings.first_match(&mut req).await
             {
-                let re = Regex::new(req_pattern).unwrap();
-                let uri = req.uri().to_string();
-
-                if !re.is_match(&uri) {
-                    continue;
-                }
-
                 let delay_millsec = *delay_millsec;
 
                 tokio::spawn(async move {
@@ -69,49 +69,27 @@ impl HttpRequestProcessor for RequestDelayProcessor {
 }
 
 impl ProcessorRuleParser for RequestDelayProcessor {
-    type Rule = RequestDelayRule;
+    type Mapping = RequestDelayMapping;
 
     /// Parse configuration like this:
     /// ```shell
     /// # strip this line
     /// https://www.x.com 200
     ///    https://wwww.m.com  300
+    /// POST https://www.n.com header:x-slow 1000
     /// ```
-    fn parse_rule(content: &str) -> Self::Rule {
-        let lines = content.split('\n');
-
-        let mut mappings: RequestDelayRule = Vec::new();
-
-        for line in lines.into_iter() {
-            let line = line.trim();
-
-            if !line.starts_with('#') {
-                let mut parts = line.split_whitespace();
-                let mut req_pattern = String::new();
-
-                if let Some(source) = parts.next() {
-                    req_pattern = source.into();
-                }
-
-                if let Some(delay_str) = parts.next() {
-                    let delay = delay_str.parse::<u64>();
-
-                    match delay {
-                        Ok(delay) => {
-                            mappings.push(RequestDelayMapping {
-                                req_pattern,
-                                delay_millsec: delay,
-                            });
-                        }
-                        Err(_) => {
-                            log::error!("parse delay - {} failed", delay_str);
-                        }
-                    }
-                }
-            }
-        }
-
-        mappings
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let delay_str = expect_last_token(rest, "delay milliseconds")?;
+
+        let delay_millsec = delay_str
+            .parse::<u64>()
+            .map_err(|_| format!("invalid delay milliseconds `{delay_str}`"))?;
+
+        Ok(RequestDelayMapping {
+            matcher,
+            delay_millsec,
+        })
     }
 }
 
@@ -127,7 +105,7 @@ impl From<String> for RequestDelayProcessor {
             mappings: if mappings.is_empty() {
                 None
             } else {
-                Some(mappings)
+                Some(CompiledRules::new(mappings))
             },
         }
     }
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Request, Response};
+
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher, RequestSnapshot},
+    parser::{expect_token, ProcessorRuleParser},
+    Processor,
+};
+
+use super::{
+    HttpPhase, HttpRequestProcessor, HttpResponseProcessor, ProcessorID, RequestProcessResult,
+    ResponseProcessResult,
+};
+
+impl ProcessorID {
+    pub const HEADER: ProcessorID = ProcessorID("Header");
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum HeaderAction {
+    /// Append a value, existing values with the same name are kept.
+    Add(HeaderName, HeaderValue),
+    /// Replace all values with the same name.
+    Set(HeaderName, HeaderValue),
+    Remove(HeaderName),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderMapping {
+    pub matcher: RequestMatcher,
+    pub phase: HttpPhase,
+    pub action: HeaderAction,
+}
+
+impl AsRef<RequestMatcher> for HeaderMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type HeaderRule = Vec<HeaderMapping>;
+
+/// Adds, replaces or removes request and response headers.
+/// Unlike redirect or delay, every matched rule is applied.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HeaderProcessor {
+    // compiled separately, so that rules of the other phase are never evaluated
+    request: CompiledRules<HeaderMapping>,
+    response: CompiledRules<HeaderMapping>,
+}
+
+impl HeaderProcessor {
+    pub fn set_header_mapping(&mut self, mappings: HeaderRule) {
+        let (request, response): (Vec<_>, Vec<_>) = mappings
+            .into_iter()
+            .partition(|mapping| mapping.phase == HttpPhase::Request);
+
+        self.request = CompiledRules::new(request);
+        self.response = CompiledRules::new(response);
+    }
+}
+
+fn apply_actions<'a>(
+    actions: impl IntoIterator<Item = &'a HeaderAction>,
+    headers: &mut HeaderMap,
+) -> Option<HashMap<String, String>> {
+    let mut hit_info = HashMap::<String, String>::new();
+
+    for action in actions {
+        match action {
+            HeaderAction::Add(name, value) => {
+                headers.append(name.clone(), value.clone());
+                hit_info.insert(
+                    format!("add {name}"),
+                    value.to_str().unwrap_or_default().to_string(),
+                );
+            }
+            HeaderAction::Set(name, value) => {
+                headers.insert(name.clone(), value.clone());
+                hit_info.insert(
+                    format!("set {name}"),
+                    value.to_str().unwrap_or_default().to_string(),
+                );
+            }
+            HeaderAction::Remove(name) => {
+                headers.remove(name);
+                hit_info.insert(format!("remove {name}"), String::new());
+            }
+        }
+    }
+
+    if hit_info.is_empty() {
+        None
+    } else {
+        Some(hit_info)
+    }
+}
+
+impl Processor for HeaderProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::HEADER
+    }
+}
+
+#[async_trait]
+impl HttpRequestProcessor for HeaderProcessor {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
+        // Match all rules against the original request before any of them is applied.
+        let actions: Vec<_> = self
+            .request
+            .matches(&mut req)
+            .await
+            .into_iter()
+            .map(|mapping| &mapping.action)
+            .collect();
+
+        match apply_actions(actions, req.headers_mut()) {
+            Some(hit_info) => (req.into(), true, Some(hit_info)),
+            None => (req.into(), false, None),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpResponseProcessor for HeaderProcessor {
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        mut res: Response<Body>,
+    ) -> ResponseProcessResult {
+        let actions = self
+            .response
+            .matches_snapshot(req)
+            .map(|mapping| &mapping.action);
+
+        match apply_actions(actions, res.headers_mut()) {
+            Some(hit_info) => (res, true, Some(hit_info)),
+            None => (res, false, None),
+        }
+    }
+}
+
+impl ProcessorRuleParser for HeaderProcessor {
+    type Mapping = HeaderMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## <matcher> <req|res> <add|set|remove> <header name> [header value]
+    /// https://api.x.com/(.*) req set Authorization Bearer xxx
+    /// https://www.x.com res remove Content-Security-Policy
+    /// .* req set User-Agent Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7)
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        parse_header_line(line)
+    }
+}
+
+fn parse_header_line(line: &str) -> Result<HeaderMapping, String> {
+    let (matcher, rest) = RequestMatcher::parse(line)?;
+    let (phase, rest) = expect_token(rest, "phase")?;
+    let (action, rest) = expect_token(rest, "action")?;
+    let (name, value) = expect_token(rest, "header name")?;
+
+    let phase = HttpPhase::parse(phase)?;
+
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name `{name}`"))?;
+    let value =
+        || HeaderValue::from_str(value).map_err(|_| format!("invalid header value `{value}`"));
+
+    let action = match action {
+        "add" => HeaderAction::Add(name, value()?),
+        "set" => HeaderAction::Set(name, value()?),
+        "remove" => HeaderAction::Remove(name),
+        _ => {
+            return Err(format!(
+                "unknown action `{action}`, expect add, set or remove"
+            ))
+        }
+    };
+
+    Ok(HeaderMapping {
+        matcher,
+        phase,
+        action,
+    })
+}
+
+impl From<String> for HeaderProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return HeaderProcessor::default();
+        }
+
+        let mut processor = HeaderProcessor::default();
+        processor.set_header_mapping(Self::parse_rule(value.as_str()));
+        processor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_and_apply_header_rule() {
+        let processor = HeaderProcessor::from(String::from(
+            "# comment\n\
+             https://www.x.com/(.*) req set User-Agent Mozilla/5.0 (Macintosh)\n\
+             https://www.x.com/(.*) req remove Cookie\n\
+             https://www.x.com/(.*) res add X-Debug 1\n\
+             https://www.x.com/(.*) req unknown X-Debug 1\n",
+        ));
+
+        let req = Request::builder()
+            .uri("https://www.x.com/a")
+            .header("cookie", "a=1")
+            .header("x-debug", "0")
+            .body(Body::empty())
+            .unwrap();
+
+        let (mut req_or_res, hit, _) = processor.process_request(req).await;
+        let headers = req_or_res.req.headers();
+
+        assert!(hit);
+        assert_eq!(
+            headers.get("user-agent").unwrap(),
+            "Mozilla/5.0 (Macintosh)"
+        );
+        assert!(headers.get("cookie").is_none());
+        assert_eq!(headers.get_all("x-debug").iter().count(), 1);
+
+        let snapshot = RequestSnapshot::capture(&mut req_or_res.req).await;
+        let res = Response::builder()
+            .header("x-debug", "0")
+            .body(Body::empty())
+            .unwrap();
+        let (res, hit, _) = processor.process_response(&snapshot, res).await;
+        assert!(hit);
+        assert_eq!(res.headers().get_all("x-debug").iter().count(), 2);
+
+        let req = Request::builder()
+            .uri("https://www.y.com/a")
+            .body(Body::empty())
+            .unwrap();
+        let (_, hit, _) = processor.process_request(req).await;
+        assert!(!hit);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    StatusCode,
+};
+use hyper::{Body, Request, Response};
+use percent_encoding::percent_decode_str;
+
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher},
+    parser::{expect_last_token, ProcessorRuleParser},
+    Processor,
+};
+
+use super::{HttpRequestProcessor, ProcessorID, RequestProcessResult};
+
+impl ProcessorID {
+    pub const MAP_LOCAL: ProcessorID = ProcessorID("MapLocal");
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MapLocalMapping {
+    pub matcher: RequestMatcher,
+    /// Local file or directory, `$1` like captures of the uri pattern are supported.
+    pub path: String,
+}
+
+impl AsRef<RequestMatcher> for MapLocalMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type MapLocalRule = Vec<MapLocalMapping>;
+
+/// Serves matched requests from local files, `index.html` is served for directories.
+/// Requests are sent upstream as usual if the file doesn't exist.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MapLocalProcessor {
+    mappings: Option<CompiledRules<MapLocalMapping>>,
+}
+
+impl MapLocalProcessor {
+    pub fn set_map_local_mapping(&mut self, mappings: MapLocalRule) {
+        self.mappings = Some(CompiledRules::new(mappings));
+    }
+}
+
+impl Processor for MapLocalProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::MAP_LOCAL
+    }
+}
+
+#[async_trait]
+impl HttpRequestProcessor for MapLocalProcessor {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
+        if let Some(ref mappings) = self.mappings {
+            for MapLocalMapping { matcher, path } in mappings.matches(&mut req).await {
+                let Some(file) = resolve_local_path(matcher, path, &req.uri().to_string()) else {
+                    log::debug!("map local - {} to {path} is rejected", req.uri());
+                    continue;
+                };
+
+                let content = match tokio::fs::read(&file).await {
+                    Ok(content) => content,
+                    Err(e) => {
+                        log::debug!("map local - read {} failed: {e}", file.display());
+                        continue;
+                    }
+                };
+
+                let mime = mime_guess::from_path(&file).first_or_octet_stream();
+
+                let res = Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, mime.as_ref())
+                    .header(CONTENT_LENGTH, content.len())
+                    .body(Body::from(content))
+                    .expect("Failed to build response");
+
+                let mut hit_info = HashMap::<String, String>::new();
+                hit_info.insert(String::from("path"), file.display().to_string());
+
+                return ((req, res).into(), true, Some(hit_info));
+            }
+        }
+
+        (req.into(), false, None)
+    }
+}
+
+/// Substitute captures of the uri into the path, the query of the uri is never part of it.
+fn resolve_local_path(matcher: &RequestMatcher, path: &str, uri: &str) -> Option<PathBuf> {
+    let uri = uri.split_once('?').map_or(uri, |(uri, _)| uri);
+
+    let replaced = matcher.uri_regex().replace(uri, path);
+    let replaced = percent_decode_str(&replaced).decode_utf8().ok()?;
+
+    // Captures must not climb out of the mapped directory.
+    if has_parent_dir(Path::new(replaced.as_ref())) && !has_parent_dir(Path::new(path)) {
+        return None;
+    }
+
+    let file = expand_home(&replaced);
+
+    if file.is_dir() {
+        Some(file.join("index.html"))
+    } else {
+        Some(file)
+    }
+}
+
+fn has_parent_dir(path: &Path) -> bool {
+    path.components().any(|c| c == Component::ParentDir)
+}
+
+pub(super) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+impl ProcessorRuleParser for MapLocalProcessor {
+    type Mapping = MapLocalMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## <matcher> <local file or directory>
+    /// https://www.x.com/static/(.*) ~/projects/x/dist/$1
+    /// https://www.x.com/ /Users/x/projects/x/dist/index.html
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let path = expect_last_token(rest, "local path")?;
+
+        Ok(MapLocalMapping {
+            matcher,
+            path: path.to_string(),
+        })
+    }
+
+    fn check_mapping(mapping: &Self::Mapping) -> Result<(), String> {
+        // Paths with captures can only be checked against requests.
+        if mapping.path.contains('$') || expand_home(&mapping.path).exists() {
+            Ok(())
+        } else {
+            Err(format!("`{}` doesn't exist", mapping.path))
+        }
+    }
+}
+
+impl From<String> for MapLocalProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return MapLocalProcessor::default();
+        }
+
+        let mappings = Self::parse_rule(value.as_str());
+
+        MapLocalProcessor {
+            mappings: if mappings.is_empty() {
+                None
+            } else {
+                Some(CompiledRules::new(mappings))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_local_path() {
+        let mapping =
+            MapLocalProcessor::parse_line("https://www.x.com/static/(.*) /srv/dist/$1").unwrap();
+        let resolve = |uri| resolve_local_path(&mapping.matcher, &mapping.path, uri);
+
+        assert_eq!(
+            resolve("https://www.x.com/static/js/app%20main.js?v=1"),
+            Some(PathBuf::from("/srv/dist/js/app main.js"))
+        );
+        assert_eq!(resolve("https://www.x.com/static/../../etc/passwd"), None);
+    }
+}
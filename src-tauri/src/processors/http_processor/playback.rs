@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::StatusCode;
+use hyper::{body::to_bytes, Body, Request, Response};
+
+use crate::{
+    events::har::{Har, HarEntry, HarPostData},
+    processors::{
+        matcher::{CompiledRules, RequestMatcher},
+        parser::{expect_token, next_token, ProcessorRuleParser},
+        Processor,
+    },
+};
+
+use super::{map_local::expand_home, HttpRequestProcessor, ProcessorID, RequestProcessResult};
+
+impl ProcessorID {
+    pub const PLAYBACK: ProcessorID = ProcessorID("Playback");
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PlaybackMapping {
+    pub matcher: RequestMatcher,
+    /// The HAR file to answer from.
+    pub path: String,
+    /// Entries must have the same body as the request besides the method and url.
+    pub match_body: bool,
+}
+
+impl AsRef<RequestMatcher> for PlaybackMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type PlaybackRule = Vec<PlaybackMapping>;
+
+/// Answers matched requests with the responses archived in HAR files, requests are sent
+/// upstream as usual if no entry matches.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PlaybackProcessor {
+    mappings: Option<CompiledRules<PlaybackMapping>>,
+    /// Loaded along with the rules rather than per request, keyed by the path in rules.
+    archives: HashMap<String, Arc<Vec<HarEntry>>>,
+}
+
+impl PlaybackProcessor {
+    pub fn set_playback_mapping(&mut self, mappings: PlaybackRule) {
+        let mut archives = HashMap::new();
+
+        for mapping in mappings.iter() {
+            if archives.contains_key(&mapping.path) {
+                continue;
+            }
+
+            match read_har(&mapping.path) {
+                Ok(entries) => {
+                    archives.insert(mapping.path.clone(), Arc::new(entries));
+                }
+                Err(e) => log::error!("playback - load {} failed: {e}", mapping.path),
+            }
+        }
+
+        self.archives = archives;
+        self.mappings = Some(CompiledRules::new(mappings));
+    }
+}
+
+fn read_har(path: &str) -> Result<Vec<HarEntry>, String> {
+    let content = fs::read(expand_home(path)).map_err(|e| format!("read `{path}` failed: {e}"))?;
+    let har: Har =
+        serde_json::from_slice(&content).map_err(|e| format!("`{path}` isn't a HAR: {e}"))?;
+
+    Ok(har.log.entries)
+}
+
+/// The first archived entry of the same request, `body` is only compared if it's given.
+fn find_entry<'a>(
+    entries: &'a [HarEntry],
+    method: &str,
+    url: &str,
+    body: Option<&[u8]>,
+) -> Option<&'a HarEntry> {
+    entries.iter().find(|entry| {
+        let req = &entry.request;
+        let post_body = req
+            .post_data
+            .as_ref()
+            .map(HarPostData::bytes)
+            .unwrap_or_default();
+
+        req.method.eq_ignore_ascii_case(method)
+            && req.url == url
+            && body.is_none_or(|body| body == post_body)
+    })
+}
+
+impl Processor for PlaybackProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::PLAYBACK
+    }
+}
+
+#[async_trait]
+impl HttpRequestProcessor for PlaybackProcessor {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
+        let Some(ref mappings) = self.mappings else {
+            return (req.into(), false, None);
+        };
+
+        let mut body: Option<Bytes> = None;
+
+        for mapping in mappings.matches(&mut req).await {
+            let Some(entries) = self.archives.get(&mapping.path) else {
+                continue;
+            };
+
+            if mapping.match_body && body.is_none() {
+                let bytes = match to_bytes(std::mem::take(req.body_mut())).await {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        let (res, hit_info) = unreadable_body(e);
+                        return ((req, res).into(), true, Some(hit_info));
+                    }
+                };
+                body = Some(bytes.clone());
+                *req.body_mut() = Body::from(bytes);
+            }
+
+            let Some(entry) = find_entry(
+                entries,
+                req.method().as_str(),
+                &req.uri().to_string(),
+                body.as_deref().filter(|_| mapping.match_body),
+            ) else {
+                continue;
+            };
+
+            let mut hit_info = HashMap::<String, String>::new();
+            hit_info.insert(String::from("har"), mapping.path.clone());
+            hit_info.insert(String::from("started"), entry.started_date_time.clone());
+
+            let res = entry.response.to_response();
+
+            return ((req, res).into(), true, Some(hit_info));
+        }
+
+        (req.into(), false, None)
+    }
+}
+
+/// Answer with 502 rather than sending on a request whose body is lost.
+fn unreadable_body(e: hyper::Error) -> (Response<Body>, HashMap<String, String>) {
+    log::error!("read body for playback failed: {e}");
+
+    let res = Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::empty())
+        .expect("Failed to build response");
+
+    (res, HashMap::from([(String::from("error"), e.to_string())]))
+}
+
+impl ProcessorRuleParser for PlaybackProcessor {
+    type Mapping = PlaybackMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## <matcher> <har file> [body]
+    /// https://api.x.com/(.*) ~/Downloads/customer.har
+    /// POST https://api.x.com/graphql ~/Downloads/customer.har body
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let (path, rest) = expect_token(rest, "HAR file")?;
+
+        let match_body = match next_token(rest) {
+            None => false,
+            Some(("body", "")) => true,
+            Some(("body", extra)) => return Err(format!("unexpected `{extra}` after `body`")),
+            Some((other, _)) => return Err(format!("unknown option `{other}`")),
+        };
+
+        Ok(PlaybackMapping {
+            matcher,
+            path: path.to_string(),
+            match_body,
+        })
+    }
+
+    fn check_mapping(mapping: &Self::Mapping) -> Result<(), String> {
+        read_har(&mapping.path).map(|_| ())
+    }
+}
+
+impl From<String> for PlaybackProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return PlaybackProcessor::default();
+        }
+
+        let mut processor = PlaybackProcessor::default();
+        processor.set_playback_mapping(Self::parse_rule(value.as_str()));
+        processor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_entry() {
+        let har: Har = serde_json::from_value(serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "x", "version": "1" },
+                "entries": [
+                    {
+                        "startedDateTime": "2024-01-01T00:00:00.000Z",
+                        "time": 10,
+                        "request": {
+                            "method": "POST",
+                            "url": "https://api.x.com/graphql",
+                            "httpVersion": "HTTP/1.1",
+                            "postData": { "mimeType": "application/json", "text": "{\"q\":1}" },
+                            "headersSize": -1,
+                            "bodySize": 7
+                        },
+                        "response": {
+                            "status": 201,
+                            "httpVersion": "HTTP/1.1",
+                            "headers": [{ "name": "content-encoding", "value": "gzip" }],
+                            "content": { "size": 2, "mimeType": "text/plain", "text": "b2s=", "encoding": "base64" },
+                            "headersSize": -1,
+                            "bodySize": 2
+                        }
+                    }
+                ]
+            }
+        }))
+        .unwrap();
+        let entries = har.log.entries;
+
+        let url = "https://api.x.com/graphql";
+        assert!(find_entry(&entries, "post", url, Some(&b"{\"q\":1}"[..])).is_some());
+        assert!(find_entry(&entries, "POST", url, Some(&b"{\"q\":2}"[..])).is_none());
+        assert!(find_entry(&entries, "POST", url, None).is_some());
+        assert!(find_entry(&entries, "GET", url, None).is_none());
+
+        let res = find_entry(&entries, "POST", url, None)
+            .unwrap()
+            .response
+            .to_response();
+        assert_eq!(res.status(), 201);
+        assert!(res.headers().get("content-encoding").is_none());
+        assert_eq!(res.headers().get("content-length").unwrap(), "2");
+    }
+}
@@ -6,7 +6,11 @@ use http::Uri;
 use hyper::{Body, Request};
 use regex::Regex;
 
-use crate::processors::{parser::ProcessorRuleParser, Processor};
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher},
+    parser::{expect_last_token, ProcessorRuleParser},
+    Processor,
+};
 
 use super::{HttpRequestProcessor, ProcessorID, RequestProcessResult};
 
@@ -14,14 +18,28 @@ impl ProcessorID {
     pub const REDIRECT: ProcessorID = ProcessorID("Redirect");
 }
 
+#[derive(Debug, Clone)]
+pub(crate) struct RequestRedirectMapping {
+    pub matcher: RequestMatcher,
+    pub dest: String,
+}
+
+impl AsRef<RequestMatcher> for RequestRedirectMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type RequestRedirectRule = Vec<RequestRedirectMapping>;
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct RequestRedirectProcessor {
-    mappings: Option<Vec<[String; 2]>>,
+    mappings: Option<CompiledRules<RequestRedirectMapping>>,
 }
 
 impl RequestRedirectProcessor {
-    pub fn set_redirects_mapping(&mut self, mapping: Vec<[String; 2]>) {
-        self.mappings = Some(mapping);
+    pub fn set_redirects_mapping(&mut self, mapping: RequestRedirectRule) {
+        self.mappings = Some(CompiledRules::new(mapping));
     }
 }
 
@@ -35,17 +53,22 @@ impl Processor for RequestRedirectProcessor {
 impl HttpRequestProcessor for RequestRedirectProcessor {
     async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
         if let Some(ref mappings) = self.mappings {
-            for [reg_str, dest] in mappings.iter() {
-                match replace_with_reg_str(reg_str, dest, req.uri().to_string()) {
-                    None => continue,
-                    Some(ret) => {
-                        *req.uri_mut() = Uri::from_str(&ret).unwrap();
+            if let Some(RequestRedirectMapping { matcher, dest }) =
+                mappings.first_match(&mut req).await
+            {
+                let ret = replace_with_reg(matcher.uri_regex(), dest, &req.uri().to_string());
+
+                match ret.as_deref().map(Uri::from_str) {
+                    Some(Ok(uri)) => {
+                        *req.uri_mut() = uri;
 
                         let mut hit_info = HashMap::<String, String>::new();
-                        hit_info.insert(String::from("uri"), ret);
+                        hit_info.insert(String::from("uri"), ret.unwrap_or_default());
 
                         return (req.into(), true, Some(hit_info));
                     }
+                    Some(Err(e)) => log::error!("redirect to invalid uri({ret:?}): {e}"),
+                    None => {}
                 }
             }
         }
@@ -55,39 +78,32 @@ impl HttpRequestProcessor for RequestRedirectProcessor {
 }
 
 impl ProcessorRuleParser for RequestRedirectProcessor {
-    type Rule = Vec<[String; 2]>;
+    type Mapping = RequestRedirectMapping;
 
     /// Parse configuration like this:
     /// ```shell
     /// ## This is a comment line
     /// https://www.x.com https://www.y.com
     /// https://wwww.m.com https://www.n.com
+    /// GET https://www.x.com/api/(.*) query:env=dev https://dev.x.com/api/$1
     /// ```
-    fn parse_rule(content: &str) -> Self::Rule {
-        let lines = content.split('\n');
-
-        let mut mappings: Vec<[String; 2]> = Vec::new();
-
-        for line in lines.into_iter() {
-            let line = line.trim();
-
-            if !line.starts_with('#') {
-                let mut parts = line.split_whitespace();
-                let mut mapping: [String; 2] = [String::new(), String::new()];
-
-                if let Some(source) = parts.next() {
-                    mapping[0] = source.into();
-                }
-
-                if let Some(dest) = parts.next() {
-                    mapping[1] = dest.into();
-
-                    mappings.push(mapping);
-                }
-            }
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let dest = expect_last_token(rest, "redirect destination")?;
+
+        // Captures are unknown until matching, check the destination with them substituted.
+        let sample = Regex::new(r"\$(\d+|\{\w+\})")
+            .unwrap()
+            .replace_all(dest, "0");
+        match Uri::from_str(&sample) {
+            Ok(uri) if uri.scheme().is_some() && uri.authority().is_some() => {}
+            _ => return Err(format!("invalid redirect destination `{dest}`")),
         }
 
-        mappings
+        Ok(RequestRedirectMapping {
+            matcher,
+            dest: dest.into(),
+        })
     }
 }
 
@@ -103,18 +119,16 @@ impl From<String> for RequestRedirectProcessor {
             mappings: if mappings.is_empty() {
                 None
             } else {
-                Some(mappings)
+                Some(CompiledRules::new(mappings))
             },
         }
     }
 }
 
-fn replace_with_reg_str(reg_str: &str, dest: &String, source: String) -> Option<String> {
-    let re = Regex::new(reg_str).unwrap();
-
-    match re.is_match(&source) {
+fn replace_with_reg(re: &Regex, dest: &str, source: &str) -> Option<String> {
+    match re.is_match(source) {
         false => None,
-        true => Some(re.replace(source.as_str(), dest).to_string()),
+        true => Some(re.replace(source, dest).to_string()),
     }
 }
 
@@ -123,12 +137,12 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_replace_with_reg_str() {
+    fn test_replace_with_reg() {
         let reg_str = "https://www.google.com/(.*)";
         let dest = "https://www.baidu.com/$1";
         let source = "https://www.google.com/a=1&b=2";
 
-        let result = replace_with_reg_str(reg_str, &dest.to_string(), source.to_string());
+        let result = replace_with_reg(&Regex::new(reg_str).unwrap(), dest, source);
 
         assert_eq!(result, Some("https://www.baidu.com/a=1&b=2".to_string()));
     }
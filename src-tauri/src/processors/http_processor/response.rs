@@ -1,12 +1,15 @@
 use std::{collections::HashMap, str::FromStr};
 
-use crate::processors::{persist::value_persist::read_value, Processor};
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher},
+    persist::value_persist::{read_value, read_value_list_from_appdir},
+    Processor,
+};
 use async_trait::async_trait;
 
-use crate::processors::parser::ProcessorRuleParser;
+use crate::processors::parser::{expect_last_token, ProcessorRuleParser};
 use http::{header::CONTENT_LENGTH, HeaderName, HeaderValue, StatusCode, Version};
 use hyper::{Body, Request, Response};
-use regex::Regex;
 
 use super::{HttpRequestProcessor, ProcessorID, RequestProcessResult};
 
@@ -14,14 +17,28 @@ impl ProcessorID {
     pub const RESPONSE: ProcessorID = ProcessorID("Response");
 }
 
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseMapping {
+    pub matcher: RequestMatcher,
+    pub value_name: String,
+}
+
+impl AsRef<RequestMatcher> for ResponseMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type ResponseRule = Vec<ResponseMapping>;
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ResponseProcessor {
-    mappings: Option<Vec<[String; 2]>>,
+    mappings: Option<CompiledRules<ResponseMapping>>,
 }
 
 impl ResponseProcessor {
-    pub fn set_mapping(&mut self, mapping: Vec<[String; 2]>) {
-        self.mappings = Some(mapping);
+    pub fn set_mapping(&mut self, mapping: ResponseRule) {
+        self.mappings = Some(CompiledRules::new(mapping));
     }
 }
 
@@ -33,15 +50,9 @@ impl Processor for ResponseProcessor {
 
 #[async_trait]
 impl HttpRequestProcessor for ResponseProcessor {
-    async fn process_request(&self, req: Request<Body>) -> RequestProcessResult {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
         if let Some(ref mappings) = self.mappings {
-            for [req_pattern, value_name] in mappings.iter() {
-                let re = Regex::new(req_pattern).unwrap();
-
-                if !re.is_match(&req.uri().to_string()) {
-                    continue;
-                }
-
+            for ResponseMapping { value_name, .. } in mappings.matches(&mut req).await {
                 let value_content = read_value(value_name);
 
                 match value_content {
@@ -56,10 +67,14 @@ impl HttpRequestProcessor for ResponseProcessor {
 
                                 let headers = response.headers_mut().unwrap();
                                 for (k, v) in parsed.headers.iter() {
-                                    headers.insert(
-                                        HeaderName::from_str(k).unwrap(),
-                                        HeaderValue::from_str(v).unwrap(),
-                                    );
+                                    match (HeaderName::from_str(k), HeaderValue::from_str(v)) {
+                                        (Ok(k), Ok(v)) => {
+                                            headers.insert(k, v);
+                                        }
+                                        _ => log::debug!(
+                                            "skip invalid header({k}: {v}) of value({value_name})"
+                                        ),
+                                    }
                                 }
 
                                 let mut res = response.body(Body::from(parsed.body)).unwrap();
@@ -89,39 +104,33 @@ impl HttpRequestProcessor for ResponseProcessor {
 }
 
 impl ProcessorRuleParser for ResponseProcessor {
-    type Rule = Vec<[String; 2]>;
+    type Mapping = ResponseMapping;
 
     /// Parse configuration like this:
     /// ```shell
     /// ## This is a comment line
     /// https://www.x.com x-response-value.json
     /// https://www.y.com y-response-value
+    /// POST https://api.x.com/graphql json:/operationName=GetUser user.json
     /// ```
-    fn parse_rule(content: &str) -> Self::Rule {
-        let lines = content.split('\n');
-
-        let mut mappings: Vec<[String; 2]> = Vec::new();
-
-        for line in lines.into_iter() {
-            let line = line.trim();
-
-            if !line.starts_with('#') {
-                let mut parts = line.split_whitespace();
-                let mut mapping: [String; 2] = [String::new(), String::new()];
-
-                if let Some(source) = parts.next() {
-                    mapping[0] = source.into();
-                }
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let value_name = expect_last_token(rest, "value name")?;
+
+        Ok(ResponseMapping {
+            matcher,
+            value_name: value_name.into(),
+        })
+    }
 
-                if let Some(dest) = parts.next() {
-                    mapping[1] = dest.into();
+    fn check_mapping(mapping: &Self::Mapping) -> Result<(), String> {
+        let values = read_value_list_from_appdir().unwrap_or_default();
 
-                    mappings.push(mapping);
-                }
-            }
+        if values.contains(&mapping.value_name) {
+            Ok(())
+        } else {
+            Err(format!("value `{}` doesn't exist", mapping.value_name))
         }
-
-        mappings
     }
 }
 
@@ -137,7 +146,7 @@ impl From<String> for ResponseProcessor {
             mappings: if mappings.is_empty() {
                 None
             } else {
-                Some(mappings)
+                Some(CompiledRules::new(mappings))
             },
         }
     }
@@ -151,7 +160,7 @@ struct ParsedResponse<'a> {
     body: String,
 }
 
-fn parse_str_as_response(content: &str) -> Option<ParsedResponse> {
+fn parse_str_as_response(content: &str) -> Option<ParsedResponse<'_>> {
     #[derive(Debug, PartialEq)]
     enum ParseState {
         VersionStatus,
@@ -0,0 +1,712 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{header::CONTENT_LENGTH, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use hyper::{Body, Request, Response};
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST};
+use tokio::time::sleep;
+
+use crate::{
+    events::body::load_body,
+    processors::{
+        matcher::{CompiledRules, RequestMatcher, RequestSnapshot},
+        parser::RuleDiagnostic,
+        Processor,
+    },
+};
+
+use super::{
+    HttpRequestProcessor, HttpResponseProcessor, ProcessorID, RequestProcessResult,
+    ResponseProcessResult,
+};
+
+impl ProcessorID {
+    pub const SCRIPT: ProcessorID = ProcessorID("Script");
+}
+
+const ON_REQUEST: &str = "onRequest";
+const ON_RESPONSE: &str = "onResponse";
+
+/// Comment lines starting with it narrow the script down to the matched exchanges.
+const MATCH_DIRECTIVE: &str = "// match ";
+
+/// Operations a single hook may run, so that a runaway script can't hang the exchange.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A compiled [Rhai](https://rhai.rs) script.
+#[derive(Debug, Clone)]
+pub(crate) struct Script {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    on_request: bool,
+    on_response: bool,
+    /// Exchanges the hooks run against, all of them if there is none.
+    matchers: CompiledRules<RequestMatcher>,
+    /// Bodies are only buffered for the hooks if the script is narrowed down by matchers, so
+    /// that streamed bodies aren't held back by a script which runs against everything.
+    with_body: bool,
+}
+
+impl Script {
+    /// Compile the script, syntax errors and invalid matchers are reported with their lines.
+    pub fn compile(content: &str) -> Result<Self, Vec<RuleDiagnostic>> {
+        let engine = new_engine();
+
+        let diagnostic = |line: Option<usize>, message: String| {
+            let line = line.unwrap_or(1);
+            RuleDiagnostic {
+                line,
+                content: content
+                    .lines()
+                    .nth(line - 1)
+                    .unwrap_or_default()
+                    .to_string(),
+                message,
+            }
+        };
+
+        let mut matchers = Vec::new();
+        let mut diagnostics = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let Some(matcher) = line.trim().strip_prefix(MATCH_DIRECTIVE) else {
+                continue;
+            };
+
+            match RequestMatcher::parse(matcher) {
+                Ok((matcher, "")) => matchers.push(matcher),
+                Ok((_, rest)) => diagnostics.push(diagnostic(
+                    Some(idx + 1),
+                    format!("unexpected `{rest}` after matcher"),
+                )),
+                Err(message) => diagnostics.push(diagnostic(Some(idx + 1), message)),
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        let ast = engine
+            .compile(content)
+            .map_err(|e| vec![diagnostic(e.position().line(), e.err_type().to_string())])?;
+
+        let defines = |name: &str, params: usize| {
+            ast.iter_functions()
+                .any(|f| f.name == name && f.params.len() == params)
+        };
+        let on_request = defines(ON_REQUEST, 0);
+        let on_response = defines(ON_RESPONSE, 1);
+
+        if !on_request && !on_response {
+            return Err(vec![diagnostic(
+                None,
+                format!("neither `{ON_REQUEST}()` nor `{ON_RESPONSE}(req)` is defined"),
+            )]);
+        }
+
+        Ok(Self {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            on_request,
+            on_response,
+            with_body: !matchers.is_empty(),
+            matchers: CompiledRules::new(matchers),
+        })
+    }
+
+    async fn matches_request(&self, req: &mut Request<Body>) -> bool {
+        !self.with_body || self.matchers.first_match(req).await.is_some()
+    }
+
+    fn matches_snapshot(&self, req: &RequestSnapshot) -> bool {
+        !self.with_body || self.matchers.matches_snapshot(req).next().is_some()
+    }
+
+    async fn read_body(&self, body: Body) -> io::Result<HookBody> {
+        if self.with_body {
+            load_body(body).await.map(HookBody::Buffered)
+        } else {
+            Ok(HookBody::Streamed(body))
+        }
+    }
+
+    /// Call the hook with `this` bound to `view`, returns the view updated by the script.
+    async fn call(
+        &self,
+        name: &'static str,
+        view: Map,
+        args: impl FuncArgs + Send + 'static,
+    ) -> Result<Map, String> {
+        let engine = Arc::clone(&self.engine);
+        let ast = Arc::clone(&self.ast);
+
+        // Scripts are CPU bound, keep them away from the proxy's workers.
+        tokio::task::spawn_blocking(move || {
+            let mut this = Dynamic::from_map(view);
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut this);
+
+            // Hooks work on `this`, what they return is ignored.
+            let _ = engine
+                .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &ast, name, args)
+                .map_err(|e| e.to_string())?;
+
+            this.try_cast::<Map>()
+                .ok_or_else(|| format!("`this` of {name} is no longer a map"))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|s| log::info!("script - {s}"));
+    engine.on_debug(|s, _, pos| log::debug!("script - {pos}: {s}"));
+    engine
+}
+
+/// The body as the hooks see it.
+enum HookBody {
+    Buffered(Bytes),
+    /// Passed on untouched, unless the hook sets one.
+    Streamed(Body),
+}
+
+impl HookBody {
+    fn bytes(&self) -> Option<&Bytes> {
+        match self {
+            Self::Buffered(bytes) => Some(bytes),
+            Self::Streamed(_) => None,
+        }
+    }
+}
+
+impl From<HookBody> for Body {
+    fn from(value: HookBody) -> Self {
+        match value {
+            HookBody::Buffered(bytes) => Body::from(bytes),
+            HookBody::Streamed(body) => body,
+        }
+    }
+}
+
+/// Runs the `onRequest` and `onResponse` hooks of a script against every exchange, or the ones
+/// matched by its `// match <matcher>` lines.
+///
+/// Hooks get the request or response as `this`, whose fields could be rewritten in place:
+/// `method`, `uri`, `status`, `headers` and `body`. A header with several values is an array of
+/// them, and `body` is only there if the script has matchers. Besides, setting `this.response`
+/// in `onRequest` mocks the response, `this.delay` delays the exchange by milliseconds and
+/// `this.drop` answers it with an empty 502 response.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScriptProcessor {
+    script: Option<Script>,
+}
+
+impl ScriptProcessor {
+    pub fn set_script(&mut self, script: Option<Script>) {
+        self.script = script;
+    }
+
+    /// Empty content disables the script.
+    pub fn validate_script(content: &str) -> Result<Option<Script>, Vec<RuleDiagnostic>> {
+        if content.trim().is_empty() {
+            Ok(None)
+        } else {
+            Script::compile(content).map(Some)
+        }
+    }
+}
+
+impl Processor for ScriptProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::SCRIPT
+    }
+}
+
+#[async_trait]
+impl HttpRequestProcessor for ScriptProcessor {
+    async fn process_request(&self, mut req: Request<Body>) -> RequestProcessResult {
+        let Some(script) = self.script.as_ref().filter(|script| script.on_request) else {
+            return (req.into(), false, None);
+        };
+
+        if !script.matches_request(&mut req).await {
+            return (req.into(), false, None);
+        }
+
+        let (mut parts, body) = req.into_parts();
+        let body = match script.read_body(body).await {
+            Ok(body) => body,
+            Err(e) => {
+                let (res, hit_info) = unreadable_body(ON_REQUEST, e);
+                let req = Request::from_parts(parts, Body::empty());
+                return ((req, res).into(), true, Some(hit_info));
+            }
+        };
+
+        let origin = message_view(&parts.headers, body.bytes());
+        let mut view = origin.clone();
+        view.insert("method".into(), parts.method.to_string().into());
+        view.insert("uri".into(), parts.uri.to_string().into());
+
+        let view = match script.call(ON_REQUEST, view, ()).await {
+            Ok(view) => view,
+            Err(e) => {
+                log::error!("script - {ON_REQUEST} of {} failed: {e}", parts.uri);
+                return (Request::from_parts(parts, body.into()).into(), false, None);
+            }
+        };
+
+        let mut hit_info = HashMap::<String, String>::new();
+
+        if let Some(method) = string_field(&view, "method").filter(|m| *m != parts.method.as_str())
+        {
+            match Method::from_bytes(method.as_bytes()) {
+                Ok(method) => {
+                    hit_info.insert(String::from("method"), method.to_string());
+                    parts.method = method;
+                }
+                Err(_) => log::warn!("script - invalid method `{method}`"),
+            }
+        }
+
+        if let Some(uri) = string_field(&view, "uri").filter(|uri| *uri != parts.uri.to_string()) {
+            match uri.parse::<Uri>() {
+                Ok(uri) => {
+                    hit_info.insert(String::from("uri"), uri.to_string());
+                    parts.uri = uri;
+                }
+                Err(_) => log::warn!("script - invalid uri `{uri}`"),
+            }
+        }
+
+        let body = apply_message(&origin, &view, &mut parts.headers, body, &mut hit_info);
+        delay(&view, &mut hit_info).await;
+
+        let req = Request::from_parts(parts, body.into());
+
+        let res = if is_dropped(&view) {
+            hit_info.insert(String::from("drop"), String::from("true"));
+            Some(dropped_response())
+        } else {
+            match view.get("response").and_then(|res| res.read_lock::<Map>()) {
+                Some(res) => {
+                    let res = mock_response(&res);
+                    hit_info.insert(String::from("mock"), res.status().to_string());
+                    Some(res)
+                }
+                None => None,
+            }
+        };
+
+        let hit = !hit_info.is_empty();
+        let hit_info = if hit { Some(hit_info) } else { None };
+
+        match res {
+            Some(res) => ((req, res).into(), hit, hit_info),
+            None => (req.into(), hit, hit_info),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpResponseProcessor for ScriptProcessor {
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        res: Response<Body>,
+    ) -> ResponseProcessResult {
+        let Some(script) = self
+            .script
+            .as_ref()
+            .filter(|script| script.on_response && script.matches_snapshot(req))
+        else {
+            return (res, false, None);
+        };
+
+        let (mut parts, body) = res.into_parts();
+        let body = match script.read_body(body).await {
+            Ok(body) => body,
+            Err(e) => {
+                let (res, hit_info) = unreadable_body(ON_RESPONSE, e);
+                return (res, true, Some(hit_info));
+            }
+        };
+
+        let origin = message_view(&parts.headers, body.bytes());
+        let mut view = origin.clone();
+        view.insert("status".into(), (parts.status.as_u16() as i64).into());
+
+        let req_body = if script.with_body {
+            match req.body.load().await {
+                Ok(body) => Some(body),
+                Err(e) => {
+                    let (res, hit_info) = unreadable_body(ON_RESPONSE, e);
+                    return (res, true, Some(hit_info));
+                }
+            }
+        } else {
+            None
+        };
+        let mut req_view = message_view(&req.headers, req_body.as_ref());
+        req_view.insert("method".into(), req.method.to_string().into());
+        req_view.insert("uri".into(), req.uri.to_string().into());
+
+        let view = match script
+            .call(ON_RESPONSE, view, (Dynamic::from_map(req_view),))
+            .await
+        {
+            Ok(view) => view,
+            Err(e) => {
+                log::error!("script - {ON_RESPONSE} of {} failed: {e}", req.uri);
+                return (Response::from_parts(parts, body.into()), false, None);
+            }
+        };
+
+        if is_dropped(&view) {
+            let mut hit_info = HashMap::<String, String>::new();
+            hit_info.insert(String::from("drop"), String::from("true"));
+            return (dropped_response(), true, Some(hit_info));
+        }
+
+        let mut hit_info = HashMap::<String, String>::new();
+
+        if let Some(status) = view
+            .get("status")
+            .and_then(|status| status.as_int().ok())
+            .filter(|status| *status != parts.status.as_u16() as i64)
+        {
+            match u16::try_from(status)
+                .ok()
+                .and_then(|s| StatusCode::from_u16(s).ok())
+            {
+                Some(status) => {
+                    hit_info.insert(String::from("status"), status.to_string());
+                    parts.status = status;
+                }
+                None => log::warn!("script - invalid status `{status}`"),
+            }
+        }
+
+        let body = apply_message(&origin, &view, &mut parts.headers, body, &mut hit_info);
+        delay(&view, &mut hit_info).await;
+
+        let res = Response::from_parts(parts, body.into());
+
+        if hit_info.is_empty() {
+            (res, false, None)
+        } else {
+            (res, true, Some(hit_info))
+        }
+    }
+}
+
+/// The fields shared by requests and responses.
+fn message_view(headers: &HeaderMap, body: Option<&Bytes>) -> Map {
+    let headers: Map = headers
+        .keys()
+        .map(|name| {
+            let mut values: Vec<Dynamic> = headers
+                .get_all(name)
+                .iter()
+                .map(|value| {
+                    String::from_utf8_lossy(value.as_bytes())
+                        .into_owned()
+                        .into()
+                })
+                .collect();
+
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                values.into()
+            };
+            (name.as_str().into(), value)
+        })
+        .collect();
+
+    let mut view = Map::new();
+    view.insert("headers".into(), Dynamic::from_map(headers));
+    if let Some(body) = body {
+        view.insert(
+            "body".into(),
+            String::from_utf8_lossy(body).into_owned().into(),
+        );
+    }
+    view
+}
+
+/// Header values of the view, an array stands for a header with several values.
+fn view_header_pairs(view: &Map) -> BTreeMap<String, Vec<String>> {
+    view.get("headers")
+        .and_then(|headers| headers.read_lock::<Map>())
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|(name, value)| {
+                    let values = match value.read_lock::<rhai::Array>() {
+                        Some(values) => values.iter().map(Dynamic::to_string).collect(),
+                        None => vec![value.to_string()],
+                    };
+                    (name.to_lowercase(), values)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Replace all values of the header, returns whether they are all valid.
+fn set_header(headers: &mut HeaderMap, name: &str, values: &[String]) -> bool {
+    let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+        return false;
+    };
+    let Ok(values) = values
+        .iter()
+        .map(|value| HeaderValue::from_str(value))
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return false;
+    };
+
+    headers.remove(&name);
+    for value in values {
+        headers.append(&name, value);
+    }
+    true
+}
+
+fn string_field(view: &Map, key: &str) -> Option<String> {
+    view.get(key)
+        .filter(|value| !value.is_unit())
+        .map(|value| value.to_string())
+}
+
+/// Apply the headers and body changed by the script, untouched headers keep all of their values.
+fn apply_message(
+    origin: &Map,
+    view: &Map,
+    headers: &mut HeaderMap,
+    body: HookBody,
+    hit_info: &mut HashMap<String, String>,
+) -> HookBody {
+    let origin_headers = view_header_pairs(origin);
+    let view_headers = view_header_pairs(view);
+
+    for name in origin_headers.keys() {
+        if !view_headers.contains_key(name) {
+            headers.remove(name.as_str());
+            hit_info.insert(format!("remove {name}"), String::new());
+        }
+    }
+
+    for (name, values) in view_headers.iter() {
+        if origin_headers.get(name) == Some(values) {
+            continue;
+        }
+
+        if set_header(headers, name, values) {
+            hit_info.insert(format!("set {name}"), values.join(", "));
+        } else {
+            log::warn!("script - invalid header `{name}: {values:?}`");
+        }
+    }
+
+    match string_field(view, "body") {
+        Some(rewritten) if Some(&rewritten) != string_field(origin, "body").as_ref() => {
+            headers.remove(CONTENT_LENGTH);
+            hit_info.insert(String::from("body"), format!("{} bytes", rewritten.len()));
+            HookBody::Buffered(Bytes::from(rewritten))
+        }
+        _ => body,
+    }
+}
+
+async fn delay(view: &Map, hit_info: &mut HashMap<String, String>) {
+    let millis = view
+        .get("delay")
+        .and_then(|delay| delay.as_int().ok())
+        .filter(|millis| *millis > 0);
+
+    if let Some(millis) = millis {
+        hit_info.insert(String::from("delay"), millis.to_string());
+        sleep(Duration::from_millis(millis as u64)).await;
+    }
+}
+
+fn is_dropped(view: &Map) -> bool {
+    view.get("drop")
+        .and_then(|drop| drop.as_bool().ok())
+        .unwrap_or(false)
+}
+
+/// Answer with 502 rather than sending on a message whose body is lost.
+fn unreadable_body(hook: &str, e: io::Error) -> (Response<Body>, HashMap<String, String>) {
+    log::error!("read body for script - {hook} failed: {e}");
+    (
+        dropped_response(),
+        HashMap::from([(String::from("error"), e.to_string())]),
+    )
+}
+
+fn dropped_response() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .header(CONTENT_LENGTH, 0)
+        .body(Body::empty())
+        .expect("Failed to build response")
+}
+
+/// Build the response mocked by `this.response = #{ status: 200, headers: #{}, body: "" }`.
+fn mock_response(view: &Map) -> Response<Body> {
+    let status = view
+        .get("status")
+        .and_then(|status| status.as_int().ok())
+        .and_then(|status| u16::try_from(status).ok())
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+    let body = string_field(view, "body").unwrap_or_default();
+
+    let mut res = Response::builder()
+        .status(status)
+        .body(Body::from(body.clone()))
+        .expect("Failed to build response");
+    for (name, values) in view_header_pairs(view) {
+        if !set_header(res.headers_mut(), &name, &values) {
+            log::warn!("script - invalid header `{name}: {values:?}`");
+        }
+    }
+    res.headers_mut().insert(CONTENT_LENGTH, body.len().into());
+
+    res
+}
+
+impl From<String> for ScriptProcessor {
+    fn from(value: String) -> Self {
+        match Self::validate_script(value.as_str()) {
+            Ok(script) => ScriptProcessor { script },
+            Err(diagnostics) => {
+                log::error!("compile script failed: {:?}", diagnostics);
+                ScriptProcessor::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::body::to_bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_script_hooks() {
+        let processor = ScriptProcessor::from(String::from(
+            r#"
+            // match https://www.x.com/.*
+            fn onRequest() {
+                if this.uri.contains("/mock") {
+                    this.response = #{ status: 201, body: "mocked" };
+                }
+                this.headers["x-script"] = "1";
+                this.headers.remove("cookie");
+            }
+
+            fn onResponse(req) {
+                if req.headers["x-script"] == "1" {
+                    this.status = 500;
+                    this.body = "rewritten";
+                    this.headers["set-cookie"].push("c=3");
+                }
+            }
+            "#,
+        ));
+
+        let req = Request::builder()
+            .uri("https://www.x.com/mock")
+            .header("cookie", "a=1")
+            .body(Body::empty())
+            .unwrap();
+        let (mut req_or_res, hit, info) = processor.process_request(req).await;
+        let info = info.unwrap();
+
+        assert!(hit);
+        assert_eq!(info.get("mock").unwrap(), "201 Created");
+        assert!(info.contains_key("remove cookie"));
+        assert_eq!(req_or_res.req.headers().get("x-script").unwrap(), "1");
+        assert_eq!(req_or_res.res.unwrap().status(), StatusCode::CREATED);
+
+        let snapshot = RequestSnapshot::capture(&mut req_or_res.req).await;
+        let res = Response::builder()
+            .header(CONTENT_LENGTH, 2)
+            .header("set-cookie", "a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT")
+            .header("set-cookie", "b=2")
+            .body(Body::from("ok"))
+            .unwrap();
+        let (res, hit, _) = processor.process_response(&snapshot, res).await;
+
+        assert!(hit);
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(res.headers().get(CONTENT_LENGTH).is_none());
+        let cookies: Vec<_> = res.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(
+            cookies,
+            ["a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", "b=2", "c=3"]
+        );
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "rewritten");
+
+        // unmatched exchanges aren't touched
+        let req = Request::builder()
+            .uri("https://www.y.com/mock")
+            .body(Body::empty())
+            .unwrap();
+        let (_, hit, _) = processor.process_request(req).await;
+        assert!(!hit);
+    }
+
+    #[tokio::test]
+    async fn test_script_without_matchers_streams_body() {
+        let processor = ScriptProcessor::from(String::from(
+            r#"
+            fn onResponse(req) {
+                this.headers["x-has-body"] = `${"body" in this}`;
+            }
+            "#,
+        ));
+
+        let mut req = Request::builder()
+            .uri("https://www.x.com/")
+            .body(Body::empty())
+            .unwrap();
+        let snapshot = RequestSnapshot::capture(&mut req).await;
+        let (res, hit, _) = processor
+            .process_response(&snapshot, Response::new(Body::from("ok")))
+            .await;
+
+        assert!(hit);
+        assert_eq!(res.headers()["x-has-body"], "false");
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "ok");
+    }
+
+    #[test]
+    fn test_validate_script() {
+        let diagnostics =
+            ScriptProcessor::validate_script("fn onRequest() {\n  let a = ;\n}").unwrap_err();
+        assert_eq!(diagnostics[0].line, 2);
+
+        assert!(ScriptProcessor::validate_script("fn other() {}").is_err());
+
+        let diagnostics =
+            ScriptProcessor::validate_script("fn onRequest() {}\n// match GET\n").unwrap_err();
+        assert_eq!(diagnostics[0].line, 2);
+        assert!(ScriptProcessor::validate_script("  ").unwrap().is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use http::StatusCode;
+use hyper::{Body, Response};
+
+use crate::processors::{
+    matcher::{CompiledRules, RequestMatcher, RequestSnapshot},
+    parser::{expect_last_token, ProcessorRuleParser},
+    Processor,
+};
+
+use super::{HttpResponseProcessor, ProcessorID, ResponseProcessResult};
+
+impl ProcessorID {
+    pub const STATUS: ProcessorID = ProcessorID("Status");
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseStatusMapping {
+    pub matcher: RequestMatcher,
+    pub status: StatusCode,
+}
+
+impl AsRef<RequestMatcher> for ResponseStatusMapping {
+    fn as_ref(&self) -> &RequestMatcher {
+        &self.matcher
+    }
+}
+
+pub(crate) type ResponseStatusRule = Vec<ResponseStatusMapping>;
+
+/// Overrides the status code of the upstream response.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResponseStatusProcessor {
+    mappings: Option<CompiledRules<ResponseStatusMapping>>,
+}
+
+impl ResponseStatusProcessor {
+    pub fn set_status_mapping(&mut self, mappings: ResponseStatusRule) {
+        self.mappings = Some(CompiledRules::new(mappings));
+    }
+}
+
+impl Processor for ResponseStatusProcessor {
+    fn name(&self) -> ProcessorID {
+        ProcessorID::STATUS
+    }
+}
+
+#[async_trait]
+impl HttpResponseProcessor for ResponseStatusProcessor {
+    async fn process_response(
+        &self,
+        req: &RequestSnapshot,
+        mut res: Response<Body>,
+    ) -> ResponseProcessResult {
+        if let Some(ref mappings) = self.mappings {
+            if let Some(ResponseStatusMapping { status, .. }) =
+                mappings.matches_snapshot(req).next()
+            {
+                let mut hit_info = HashMap::<String, String>::new();
+                hit_info.insert(String::from("from"), res.status().as_u16().to_string());
+                hit_info.insert(String::from("to"), status.as_u16().to_string());
+
+                *res.status_mut() = *status;
+
+                return (res, true, Some(hit_info));
+            }
+        }
+
+        (res, false, None)
+    }
+}
+
+impl ProcessorRuleParser for ResponseStatusProcessor {
+    type Mapping = ResponseStatusMapping;
+
+    /// Parse configuration like this:
+    /// ```shell
+    /// ## This is a comment line
+    /// https://www.x.com/api/(.*) 500
+    /// https://www.y.com 404
+    /// DELETE https://www.y.com/api/(.*) 403
+    /// ```
+    fn parse_line(line: &str) -> Result<Self::Mapping, String> {
+        let (matcher, rest) = RequestMatcher::parse(line)?;
+        let status_str = expect_last_token(rest, "status code")?;
+
+        let status = StatusCode::from_bytes(status_str.as_bytes())
+            .map_err(|_| format!("invalid status code `{status_str}`"))?;
+
+        Ok(ResponseStatusMapping { matcher, status })
+    }
+}
+
+impl From<String> for ResponseStatusProcessor {
+    fn from(value: String) -> Self {
+        if value.is_empty() {
+            return ResponseStatusProcessor::default();
+        }
+
+        let mappings = Self::parse_rule(value.as_str());
+
+        ResponseStatusProcessor {
+            mappings: if mappings.is_empty() {
+                None
+            } else {
+                Some(CompiledRules::new(mappings))
+            },
+        }
+    }
+}
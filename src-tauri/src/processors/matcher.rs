@@ -0,0 +1,467 @@
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, Method, Uri};
+use hyper::{Body, Request};
+use regex::{Regex, RegexSet};
+use serde_json::Value;
+
+use crate::events::body::{peek_body, preview_limit, SpooledBody};
+
+use super::parser::{expect_token, next_token};
+
+/// The request info kept for the response phase, the request itself is consumed by sending it
+/// upstream.
+#[derive(Debug, Clone)]
+pub struct RequestSnapshot {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    /// Large bodies are in the spool rather than in memory.
+    pub body: SpooledBody,
+}
+
+impl RequestSnapshot {
+    pub async fn capture(req: &mut Request<Body>) -> Self {
+        let body = SpooledBody::read_temporary(req.body_mut())
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("capture the request body of {} failed: {e}", req.uri());
+                SpooledBody::default()
+            });
+        Self::with_body(req, body)
+    }
+
+    /// The request along with its body, which is already read.
+    pub fn with_body<T>(req: &Request<T>, body: SpooledBody) -> Self {
+        Self {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            headers: req.headers().clone(),
+            body,
+        }
+    }
+
+    /// Rebuild the request, e.g. to send it again.
+    pub fn to_request(&self) -> Request<Body> {
+        let mut req = Request::new(self.body.to_body());
+        *req.method_mut() = self.method.clone();
+        *req.uri_mut() = self.uri.clone();
+        *req.headers_mut() = self.headers.clone();
+        req
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    /// Header exists, and its value matches the pattern if there is one.
+    Header(HeaderName, Option<Regex>),
+    /// Query parameter exists, and its value matches the pattern if there is one.
+    Query(String, Option<Regex>),
+    Body(regex::bytes::Regex),
+    /// Value at the JSON pointer of the body exists, and equals to the value if there is one.
+    Json(String, Option<Value>),
+}
+
+/// Matches a request by its uri, and optionally method, headers, query parameters and body.
+///
+/// Used as the leading part of rule lines:
+/// ```shell
+/// ## [METHOD] <uri pattern> [[!]header:<name>[=<pattern>]] [[!]query:<key>[=<pattern>]]
+/// ##     [[!]body:<pattern>] [[!]json:<pointer>[=<value>]] <rest of rule>
+/// https://www.x.com https://www.y.com
+/// POST https://api.x.com/graphql json:/operationName=GetUser user.json
+/// GET https://api.x.com/(.*) header:authorization !query:debug=1 500
+/// ```
+///
+/// `body:` and `json:` only look at the head of the body up to the preview limit.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestMatcher {
+    method: Option<Method>,
+    uri: Regex,
+    conditions: Vec<(bool /* negated */, Condition)>,
+}
+
+impl RequestMatcher {
+    /// Parse the matcher off the head of a rule line, returns the rest of the line.
+    pub(crate) fn parse(line: &str) -> Result<(Self, &str), String> {
+        let (mut token, mut rest) = expect_token(line, "uri pattern")?;
+
+        let method = match parse_method(token) {
+            Some(method) => {
+                (token, rest) = expect_token(rest, "uri pattern")?;
+                Some(method)
+            }
+            None => None,
+        };
+
+        let uri = compile(token)?;
+
+        let mut conditions = Vec::new();
+
+        while let Some((token, remains)) = next_token(rest) {
+            let (negated, condition) = match token.strip_prefix('!') {
+                Some(token) => (true, token),
+                None => (false, token),
+            };
+
+            let condition = if let Some(header) = condition.strip_prefix("header:") {
+                let (name, pattern) = split_condition(header);
+                Condition::Header(
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| format!("invalid header name `{name}`"))?,
+                    pattern.map(compile).transpose()?,
+                )
+            } else if let Some(query) = condition.strip_prefix("query:") {
+                let (key, pattern) = split_condition(query);
+                Condition::Query(key.to_string(), pattern.map(compile).transpose()?)
+            } else if let Some(pattern) = condition.strip_prefix("body:") {
+                Condition::Body(
+                    regex::bytes::Regex::new(pattern)
+                        .map_err(|e| format!("invalid pattern `{pattern}`: {e}"))?,
+                )
+            } else if let Some(json) = condition.strip_prefix("json:") {
+                let (pointer, value) = split_condition(json);
+                if !pointer.starts_with('/') {
+                    return Err(format!("json pointer `{pointer}` should start with `/`"));
+                }
+                Condition::Json(
+                    pointer.to_string(),
+                    value.map(|value| {
+                        serde_json::from_str(value).unwrap_or(Value::String(value.to_string()))
+                    }),
+                )
+            } else {
+                break;
+            };
+
+            conditions.push((negated, condition));
+            rest = remains;
+        }
+
+        Ok((
+            Self {
+                method,
+                uri,
+                conditions,
+            },
+            rest,
+        ))
+    }
+
+    pub(crate) fn uri_regex(&self) -> &Regex {
+        &self.uri
+    }
+
+    fn needs_body(&self) -> bool {
+        self.conditions
+            .iter()
+            .any(|(_, condition)| matches!(condition, Condition::Body(_) | Condition::Json(..)))
+    }
+
+    /// Everything but the uri pattern, which `CompiledRules` checks for all rules at once. Body
+    /// conditions only look at the head of the body up to the preview limit, which is only read
+    /// if any of them exists.
+    async fn matches_conditions(&self, req: &mut Request<Body>) -> bool {
+        if !self.matches_head(req.method(), req.uri(), req.headers()) {
+            return false;
+        }
+
+        if !self.needs_body() {
+            return true;
+        }
+
+        match peek_body(req.body_mut(), preview_limit()).await {
+            Ok(body) => self.matches_body(&body),
+            Err(e) => {
+                log::warn!(
+                    "read the request body of {} for matching failed: {e}",
+                    req.uri()
+                );
+                false
+            }
+        }
+    }
+
+    fn matches_snapshot_conditions(&self, req: &RequestSnapshot) -> bool {
+        self.matches_head(&req.method, &req.uri, &req.headers)
+            && (!self.needs_body() || self.matches_body(req.body.preview()))
+    }
+
+    fn matches_head(&self, method: &Method, uri: &Uri, headers: &HeaderMap) -> bool {
+        if let Some(ref expected) = self.method {
+            if expected != method {
+                return false;
+            }
+        }
+
+        self.conditions.iter().all(|(negated, condition)| {
+            let matched = match condition {
+                Condition::Header(name, pattern) => headers.get_all(name).iter().any(|value| {
+                    pattern.as_ref().is_none_or(|pattern| {
+                        pattern.is_match(&String::from_utf8_lossy(value.as_bytes()))
+                    })
+                }),
+                Condition::Query(key, pattern) => form_urlencoded::parse(
+                    uri.query().unwrap_or_default().as_bytes(),
+                )
+                .any(|(k, v)| {
+                    k == key.as_str() && pattern.as_ref().is_none_or(|pattern| pattern.is_match(&v))
+                }),
+                // checked by `matches_body`
+                Condition::Body(_) | Condition::Json(..) => return true,
+            };
+
+            matched != *negated
+        })
+    }
+
+    fn matches_body(&self, body: &Bytes) -> bool {
+        let mut json: Option<Option<Value>> = None;
+
+        self.conditions.iter().all(|(negated, condition)| {
+            let matched = match condition {
+                Condition::Body(pattern) => pattern.is_match(body),
+                Condition::Json(pointer, expected) => {
+                    let json = json.get_or_insert_with(|| serde_json::from_slice(body).ok());
+
+                    match json.as_ref().and_then(|json| json.pointer(pointer)) {
+                        Some(value) => expected.as_ref().is_none_or(|expected| expected == value),
+                        None => false,
+                    }
+                }
+                // checked by `matches_head`
+                Condition::Header(..) | Condition::Query(..) => return true,
+            };
+
+            matched != *negated
+        })
+    }
+}
+
+impl AsRef<RequestMatcher> for RequestMatcher {
+    fn as_ref(&self) -> &RequestMatcher {
+        self
+    }
+}
+
+/// Mappings of a processor, compiled once when the rule is set. The uri patterns of all mappings
+/// are checked in a single pass with a `RegexSet`, then the remaining conditions of the
+/// candidates are checked in the rule order.
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledRules<M> {
+    mappings: Vec<M>,
+    // `None` if the patterns are too large to be compiled as a set.
+    uris: Option<RegexSet>,
+}
+
+impl<M> Default for CompiledRules<M> {
+    fn default() -> Self {
+        Self {
+            mappings: Vec::new(),
+            uris: None,
+        }
+    }
+}
+
+impl<M: AsRef<RequestMatcher>> CompiledRules<M> {
+    pub(crate) fn new(mappings: Vec<M>) -> Self {
+        let patterns = mappings.iter().map(|mapping| mapping.as_ref().uri.as_str());
+
+        let uris = match RegexSet::new(patterns) {
+            Ok(uris) => Some(uris),
+            Err(e) => {
+                log::warn!("compile uri patterns as a set failed, fallback to one by one: {e}");
+                None
+            }
+        };
+
+        Self { mappings, uris }
+    }
+
+    fn candidates(&self, uri: &Uri) -> Vec<&M> {
+        if self.mappings.is_empty() {
+            return Vec::new();
+        }
+
+        let uri = uri.to_string();
+
+        match self.uris {
+            Some(ref uris) => uris
+                .matches(&uri)
+                .into_iter()
+                .map(|idx| &self.mappings[idx])
+                .collect(),
+            None => self
+                .mappings
+                .iter()
+                .filter(|mapping| mapping.as_ref().uri.is_match(&uri))
+                .collect(),
+        }
+    }
+
+    /// All mappings matching the request being processed, in the rule order.
+    pub(crate) async fn matches(&self, req: &mut Request<Body>) -> Vec<&M> {
+        let mut matched = Vec::new();
+
+        for mapping in self.candidates(req.uri()) {
+            if mapping.as_ref().matches_conditions(req).await {
+                matched.push(mapping);
+            }
+        }
+
+        matched
+    }
+
+    pub(crate) async fn first_match(&self, req: &mut Request<Body>) -> Option<&M> {
+        for mapping in self.candidates(req.uri()) {
+            if mapping.as_ref().matches_conditions(req).await {
+                return Some(mapping);
+            }
+        }
+
+        None
+    }
+
+    /// All mappings matching the request sent upstream, in the rule order.
+    pub(crate) fn matches_snapshot<'a>(
+        &'a self,
+        req: &'a RequestSnapshot,
+    ) -> impl Iterator<Item = &'a M> + 'a {
+        self.candidates(&req.uri)
+            .into_iter()
+            .filter(move |mapping| mapping.as_ref().matches_snapshot_conditions(req))
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("invalid pattern `{pattern}`: {e}"))
+}
+
+/// Only the standard methods, so that an uppercase uri pattern isn't taken as a method.
+fn parse_method(token: &str) -> Option<Method> {
+    [
+        Method::GET,
+        Method::HEAD,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::CONNECT,
+        Method::OPTIONS,
+        Method::TRACE,
+        Method::PATCH,
+    ]
+    .into_iter()
+    .find(|method| method.as_str() == token)
+}
+
+// name=value -> (name, Some(value)), name -> (name, None)
+fn split_condition(condition: &str) -> (&str, Option<&str>) {
+    match condition.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (condition, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(
+        method: Method,
+        uri: &'static str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> RequestSnapshot {
+        let mut header_map = HeaderMap::new();
+        for (k, v) in headers {
+            header_map.append(
+                HeaderName::from_bytes(k.as_bytes()).unwrap(),
+                v.parse().unwrap(),
+            );
+        }
+
+        RequestSnapshot {
+            method,
+            uri: Uri::from_static(uri),
+            headers: header_map,
+            body: SpooledBody::Memory(Bytes::from(body.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_parse_matcher() {
+        let (matcher, rest) = RequestMatcher::parse(
+            "POST https://api.x.com/graphql json:/operationName=GetUser user.json",
+        )
+        .unwrap();
+
+        assert_eq!(matcher.method, Some(Method::POST));
+        assert_eq!(matcher.uri_regex().as_str(), "https://api.x.com/graphql");
+        assert_eq!(matcher.conditions.len(), 1);
+        assert_eq!(rest, "user.json");
+
+        let (matcher, rest) = RequestMatcher::parse("https://www.x.com https://www.y.com").unwrap();
+        assert_eq!(matcher.method, None);
+        assert!(matcher.conditions.is_empty());
+        assert_eq!(rest, "https://www.y.com");
+
+        let (matcher, rest) = RequestMatcher::parse("API 200").unwrap();
+        assert_eq!(matcher.method, None);
+        assert_eq!(matcher.uri_regex().as_str(), "API");
+        assert_eq!(rest, "200");
+
+        assert!(RequestMatcher::parse("https://www.x.com/(.* 200").is_err());
+        assert!(RequestMatcher::parse("GET").is_err());
+    }
+
+    #[test]
+    fn test_match_request() {
+        let (other, _) = RequestMatcher::parse("https://api.x.com/(.*) x").unwrap();
+        let (matcher, _) = RequestMatcher::parse(
+            "POST https://api.x.com/graphql header:content-type=json !query:debug=1 json:/operationName=GetUser json:/variables/id=1 x",
+        )
+        .unwrap();
+
+        let rules = CompiledRules::new(vec![other, matcher]);
+        let matches = |req: RequestSnapshot| rules.matches_snapshot(&req).count() == 2;
+
+        let headers = [("content-type", "application/json")];
+        let body = r#"{"operationName":"GetUser","variables":{"id":1}}"#;
+
+        assert!(matches(snapshot(
+            Method::POST,
+            "https://api.x.com/graphql?a=1",
+            &headers,
+            body
+        )));
+        assert!(!matches(snapshot(
+            Method::GET,
+            "https://api.x.com/graphql",
+            &headers,
+            body
+        )));
+        assert!(!matches(snapshot(
+            Method::POST,
+            "https://api.x.com/graphql?debug=1",
+            &headers,
+            body
+        )));
+        assert!(!matches(snapshot(
+            Method::POST,
+            "https://api.x.com/graphql",
+            &[],
+            body
+        )));
+        assert!(!matches(snapshot(
+            Method::POST,
+            "https://api.x.com/graphql",
+            &headers,
+            r#"{"operationName":"GetOrder","variables":{"id":1}}"#
+        )));
+        assert!(!matches(snapshot(
+            Method::POST,
+            "https://api.x.com/graphql",
+            &headers,
+            "not json"
+        )));
+    }
+}
@@ -1,7 +1,7 @@
 use self::processor_id::ProcessorID;
-use crate::processors::http_processor::HttpRequestProcessor;
 
 pub mod http_processor;
+pub mod matcher;
 pub mod parser;
 pub mod processor_id;
 pub mod processor_pack;
@@ -12,6 +12,6 @@ pub mod persist;
 pub mod processor;
 pub mod processor_effect;
 
-pub trait Processor: HttpRequestProcessor {
+pub trait Processor: Send + Sync + std::fmt::Debug {
     fn name(&self) -> ProcessorID;
 }
@@ -1,4 +1,133 @@
+use serde::Serialize;
+
+/// A rule line which failed to be parsed, `line` starts from 1.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleDiagnostic {
+    pub line: usize,
+    pub content: String,
+    pub message: String,
+}
+
 pub trait ProcessorRuleParser {
-    type Rule;
-    fn parse_rule(content: &str) -> Self::Rule;
+    type Mapping;
+
+    /// Parse a single rule line, blank and comment lines never reach here.
+    fn parse_line(line: &str) -> Result<Self::Mapping, String>;
+
+    /// Checks which depend on the environment rather than the syntax, e.g. referenced files.
+    /// Only run when saving rules, so rules loaded from disk aren't dropped by them.
+    fn check_mapping(_mapping: &Self::Mapping) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Parse the rule content leniently, invalid lines are logged and skipped.
+    fn parse_rule(content: &str) -> Vec<Self::Mapping> {
+        let mut mappings = Vec::new();
+
+        for (_, line) in rule_lines(content) {
+            match Self::parse_line(line) {
+                Ok(mapping) => mappings.push(mapping),
+                Err(e) => log::error!("parse rule - {} failed: {}", line, e),
+            }
+        }
+
+        mappings
+    }
+
+    /// Parse the rule content strictly, every invalid line is reported.
+    fn validate_rule(content: &str) -> Result<Vec<Self::Mapping>, Vec<RuleDiagnostic>> {
+        let mut mappings = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        for (line_no, line) in rule_lines(content) {
+            match Self::parse_line(line).and_then(|mapping| {
+                Self::check_mapping(&mapping)?;
+                Ok(mapping)
+            }) {
+                Ok(mapping) => mappings.push(mapping),
+                Err(message) => diagnostics.push(RuleDiagnostic {
+                    line: line_no,
+                    content: line.to_string(),
+                    message,
+                }),
+            }
+        }
+
+        if diagnostics.is_empty() {
+            Ok(mappings)
+        } else {
+            Err(diagnostics)
+        }
+    }
+}
+
+/// Trimmed non-empty and non-comment lines with their line numbers.
+fn rule_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .split('\n')
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Split the first whitespace separated token off, the rest is returned trimmed but otherwise
+/// untouched, so that the last field of a rule line could contain spaces.
+pub(crate) fn next_token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+
+    if s.is_empty() {
+        return None;
+    }
+
+    match s.split_once(char::is_whitespace) {
+        Some((token, rest)) => Some((token, rest.trim())),
+        None => Some((s, "")),
+    }
+}
+
+/// Like `next_token`, but a missing token is reported as an error about `field`.
+pub(crate) fn expect_token<'a>(s: &'a str, field: &str) -> Result<(&'a str, &'a str), String> {
+    next_token(s).ok_or_else(|| format!("missing {field}"))
+}
+
+/// Like `expect_token`, but the token has to end the line, anything after it is reported rather
+/// than ignored.
+pub(crate) fn expect_last_token<'a>(s: &'a str, field: &str) -> Result<&'a str, String> {
+    match expect_token(s, field)? {
+        (token, "") => Ok(token),
+        (_, rest) => Err(format!("unexpected `{rest}` after {field}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::http_processor::delay::RequestDelayProcessor;
+
+    #[test]
+    fn test_validate_rule() {
+        let content = "# comment\n\
+                       https://www.x.com 200\n\
+                       \n\
+                       https://www.y.com/(.* 300\n\
+                       https://www.z.com abc\n\
+                       https://www.z.com\n\
+                       https://www.z.com 200 300";
+
+        let diagnostics = RequestDelayProcessor::validate_rule(content).unwrap_err();
+
+        assert_eq!(
+            diagnostics.iter().map(|d| d.line).collect::<Vec<_>>(),
+            vec![4, 5, 6, 7]
+        );
+        assert_eq!(diagnostics[1].message, "invalid delay milliseconds `abc`");
+        assert_eq!(diagnostics[2].message, "missing delay milliseconds");
+        assert_eq!(
+            diagnostics[3].message,
+            "unexpected `300` after delay milliseconds"
+        );
+
+        assert_eq!(RequestDelayProcessor::parse_rule(content).len(), 1);
+        assert!(RequestDelayProcessor::validate_rule("https://www.x.com 200").is_ok());
+    }
 }
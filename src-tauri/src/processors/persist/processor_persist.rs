@@ -11,11 +11,18 @@ use crate::error::{
     processor_error::{ProcessorErrorKind, ReadError},
     Error, ProcessorError,
 };
+use crate::processors::http_processor::body::BodyProcessor;
+use crate::processors::http_processor::breakpoint::BreakpointProcessor;
 use crate::processors::http_processor::delay::RequestDelayProcessor;
+use crate::processors::http_processor::header::HeaderProcessor;
+use crate::processors::http_processor::map_local::MapLocalProcessor;
+use crate::processors::http_processor::playback::PlaybackProcessor;
 use crate::processors::http_processor::redirect::RequestRedirectProcessor;
 use crate::processors::http_processor::response::ResponseProcessor;
+use crate::processors::http_processor::script::ScriptProcessor;
+use crate::processors::http_processor::status::ResponseStatusProcessor;
 use crate::processors::processor_id::ProcessorID;
-use crate::processors::processor_pack::ProcessorPack;
+use crate::processors::processor_pack::{sort_packs, ProcessorPack};
 
 pub type ProcessorPackStatus = HashMap<String, bool>;
 
@@ -24,7 +31,7 @@ pub fn read_processor_packs_status() -> Result<ProcessorPackStatus, Error> {
         .context(ReadStatusError {})
         .context(ProcessorStatusError {})?;
 
-    serde_json::from_str(str.as_str()).map_err(|err| Error::ProcessorStatus {
+    serde_json::from_str(str.as_str()).map_err(|_| Error::ProcessorStatus {
         source: ProcessorErrorKind::Fmt {},
     })
 }
@@ -34,7 +41,7 @@ pub fn write_processor_pack_status(pack_name: &str, enable: bool) -> Result<(),
 
     status.insert(pack_name.to_string(), enable);
 
-    let str = serde_json::to_string::<ProcessorPackStatus>(&status).map_err(|err| {
+    let str = serde_json::to_string::<ProcessorPackStatus>(&status).map_err(|_| {
         Error::ProcessorPack {
             source: ProcessorErrorKind::Fmt {},
         }
@@ -45,8 +52,76 @@ pub fn write_processor_pack_status(pack_name: &str, enable: bool) -> Result<(),
     })
 }
 
+/// Pack names from the highest priority to the lowest.
+pub type ProcessorPackOrder = Vec<String>;
+
+pub fn read_processor_pack_order() -> Result<ProcessorPackOrder, Error> {
+    let str = fs::read_to_string(app_conf::app_processor_pack_order_file())
+        .context(ReadStatusError {})
+        .context(ProcessorStatusError {})?;
+
+    serde_json::from_str(str.as_str()).map_err(|_| Error::ProcessorStatus {
+        source: ProcessorErrorKind::Fmt {},
+    })
+}
+
+pub fn write_processor_pack_order(order: &[String]) -> Result<(), Error> {
+    let str = serde_json::to_string(order).map_err(|_| Error::ProcessorPack {
+        source: ProcessorErrorKind::Fmt {},
+    })?;
+
+    fs::write(app_conf::app_processor_pack_order_file(), str).map_err(|err| Error::ProcessorPack {
+        source: ProcessorErrorKind::Write { source: err },
+    })
+}
+
+/// Put a new pack at the top, which is where it's shown.
+pub fn prepend_processor_pack_order(pack_name: &str) -> Result<(), Error> {
+    let mut order = read_processor_pack_order().unwrap_or_default();
+
+    order.retain(|x| x != pack_name);
+    order.insert(0, pack_name.to_string());
+
+    write_processor_pack_order(&order)
+}
+
+/// Whether the matching goes on to the next packs after a pack is hit, packs stop by default.
+pub type ProcessorPackContinue = HashMap<String, bool>;
+
+pub fn read_processor_packs_continue() -> Result<ProcessorPackContinue, Error> {
+    let str = fs::read_to_string(app_conf::app_processor_pack_continue_file())
+        .context(ReadStatusError {})
+        .context(ProcessorStatusError {})?;
+
+    serde_json::from_str(str.as_str()).map_err(|_| Error::ProcessorStatus {
+        source: ProcessorErrorKind::Fmt {},
+    })
+}
+
+pub fn write_processor_pack_continue(
+    pack_name: &str,
+    continue_matching: bool,
+) -> Result<(), Error> {
+    let mut flags = read_processor_packs_continue().unwrap_or_default();
+
+    flags.insert(pack_name.to_string(), continue_matching);
+
+    let str = serde_json::to_string::<ProcessorPackContinue>(&flags).map_err(|_| {
+        Error::ProcessorPack {
+            source: ProcessorErrorKind::Fmt {},
+        }
+    })?;
+
+    fs::write(app_conf::app_processor_pack_continue_file(), str).map_err(|err| {
+        Error::ProcessorPack {
+            source: ProcessorErrorKind::Write { source: err },
+        }
+    })
+}
+
 pub fn read_processors_from_appdir() -> Vec<ProcessorPack> {
     let pack_status = read_processor_packs_status();
+    let pack_continue = read_processor_packs_continue().unwrap_or_default();
 
     // TODO: refactor with Result<Vec<Interceptor>, std::io::Error> inner fn.
     let mut packs = Vec::<ProcessorPack>::new();
@@ -68,13 +143,17 @@ pub fn read_processors_from_appdir() -> Vec<ProcessorPack> {
                             }
                         }
 
+                        if pack_continue.get(&pack.pack_name) == Some(&true) {
+                            pack.set_continue(true);
+                        }
+
                         let files = fs::read_dir(dir.path());
                         if let Ok(files) = files {
                             for file in files.flatten() {
                                 if let Ok(file_name) = file.file_name().into_string() {
                                     let processor_id = ProcessorID::try_from(file_name);
                                     if let Ok(processor_id) = processor_id {
-                                        let content = fs::read_to_string(&file.path());
+                                        let content = fs::read_to_string(file.path());
 
                                         if let Ok(content) = content {
                                             match processor_id {
@@ -86,6 +165,26 @@ pub fn read_processors_from_appdir() -> Vec<ProcessorPack> {
                                                 ),
                                                 ProcessorID::RESPONSE => pack
                                                     .set_response(ResponseProcessor::from(content)),
+                                                ProcessorID::STATUS => pack.set_status(
+                                                    ResponseStatusProcessor::from(content),
+                                                ),
+                                                ProcessorID::HEADER => {
+                                                    pack.set_header(HeaderProcessor::from(content))
+                                                }
+                                                ProcessorID::BODY => {
+                                                    pack.set_body(BodyProcessor::from(content))
+                                                }
+                                                ProcessorID::MAP_LOCAL => pack.set_map_local(
+                                                    MapLocalProcessor::from(content),
+                                                ),
+                                                ProcessorID::PLAYBACK => pack
+                                                    .set_playback(PlaybackProcessor::from(content)),
+                                                ProcessorID::SCRIPT => {
+                                                    pack.set_script(ScriptProcessor::from(content))
+                                                }
+                                                ProcessorID::BREAKPOINT => pack.set_breakpoint(
+                                                    BreakpointProcessor::from(content),
+                                                ),
                                                 _ => {
                                                     log::debug!(
                                                         "processor file: {processor_id} is ignored"
@@ -105,6 +204,8 @@ pub fn read_processors_from_appdir() -> Vec<ProcessorPack> {
         }
     }
 
+    sort_packs(&mut packs, &read_processor_pack_order().unwrap_or_default());
+
     packs
 }
 
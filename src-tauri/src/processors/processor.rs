@@ -4,6 +4,7 @@ use hyper::{Body, Request, Response};
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::error::Error;
+use crate::processors::matcher::RequestSnapshot;
 use crate::processors::processor_effect::ProcessorEffects;
 
 #[derive(Debug)]
@@ -33,14 +34,44 @@ impl From<(Request<Body>, Response<Body>)> for RequestOrResponse {
     }
 }
 
+#[derive(Debug)]
+pub struct ProcessedResponse {
+    pub res: Response<Body>,
+    pub processor_effects: Option<ProcessorEffects>,
+}
+
+impl From<Response<Body>> for ProcessedResponse {
+    fn from(value: Response<Body>) -> Self {
+        Self {
+            res: value,
+            processor_effects: None,
+        }
+    }
+}
+
 #[async_trait]
 pub trait HttpProcessor: Clone + Send + Sync + 'static {
     async fn process_request(&self, req: Request<Body>) -> RequestOrResponse {
         req.into()
     }
 
-    async fn process_response(&self, res: Response<Body>) -> Response<Body> {
-        res
+    /// `req` is the snapshot of the request actually sent to upstream.
+    async fn process_response(
+        &self,
+        _req: &RequestSnapshot,
+        res: Response<Body>,
+    ) -> ProcessedResponse {
+        res.into()
+    }
+
+    /// Whether to pause the exchange before the request is sent to upstream.
+    async fn request_breakpoint(&self, _req: &mut Request<Body>) -> bool {
+        false
+    }
+
+    /// Whether to pause the exchange before the response is sent to the client.
+    async fn response_breakpoint(&self, _req: &RequestSnapshot) -> bool {
+        false
     }
 
     async fn process_error(&self, err: Error) -> Response<Body> {
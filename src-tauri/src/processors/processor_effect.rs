@@ -1,14 +1,31 @@
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::processors::processor_id::ProcessorID;
 
 pub type ProcessorEffects =
     HashMap<String /* pack name */, Vec<ProcessorEffect> /* pack hit rules */>;
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProcessorEffect {
     pub name: ProcessorID,
     pub info: Option<HashMap<String, String>>,
 }
+
+/// Merge response phase effects into the request phase ones of the same exchange.
+pub fn merge_effects(
+    effects: Option<ProcessorEffects>,
+    other: Option<ProcessorEffects>,
+) -> Option<ProcessorEffects> {
+    match (effects, other) {
+        (Some(mut effects), Some(other)) => {
+            for (pack_name, pack_effect) in other {
+                effects.entry(pack_name).or_default().extend(pack_effect);
+            }
+            Some(effects)
+        }
+        (effects, None) => effects,
+        (None, other) => other,
+    }
+}
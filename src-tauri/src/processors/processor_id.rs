@@ -1,6 +1,6 @@
 use core::fmt;
 
-use serde::{Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct ProcessorID(pub(super) &'static str);
@@ -33,6 +33,13 @@ impl TryFrom<String> for ProcessorID {
             "Delay" => Ok(Self::DELAY),
             "Redirect" => Ok(Self::REDIRECT),
             "Response" => Ok(Self::RESPONSE),
+            "Status" => Ok(Self::STATUS),
+            "Header" => Ok(Self::HEADER),
+            "Body" => Ok(Self::BODY),
+            "MapLocal" => Ok(Self::MAP_LOCAL),
+            "Playback" => Ok(Self::PLAYBACK),
+            "Script" => Ok(Self::SCRIPT),
+            "Breakpoint" => Ok(Self::BREAKPOINT),
             _ => Err("Unsupport processor"),
         }
     }
@@ -46,3 +53,12 @@ impl Serialize for ProcessorID {
         serializer.serialize_str(self.0)
     }
 }
+
+impl<'de> Deserialize<'de> for ProcessorID {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ProcessorID::try_from(String::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
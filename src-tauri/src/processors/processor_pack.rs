@@ -1,4 +1,7 @@
-use super::http_processor::{delay::*, redirect::*, response::*};
+use super::http_processor::{
+    body::*, breakpoint::*, delay::*, header::*, map_local::*, playback::*, redirect::*,
+    response::*, script::*, status::*,
+};
 
 #[derive(Debug, Clone)]
 pub struct ProcessorPack {
@@ -7,6 +10,15 @@ pub struct ProcessorPack {
     redirect: RequestRedirectProcessor,
     delay: RequestDelayProcessor,
     response: ResponseProcessor,
+    status: ResponseStatusProcessor,
+    header: HeaderProcessor,
+    body: BodyProcessor,
+    map_local: MapLocalProcessor,
+    playback: PlaybackProcessor,
+    script: ScriptProcessor,
+    breakpoint: BreakpointProcessor,
+    // go on matching the next packs after this pack is hit
+    continue_matching: bool,
 }
 
 impl ProcessorPack {
@@ -17,6 +29,14 @@ impl ProcessorPack {
             redirect: RequestRedirectProcessor::default(),
             delay: RequestDelayProcessor::default(),
             response: ResponseProcessor::default(),
+            status: ResponseStatusProcessor::default(),
+            header: HeaderProcessor::default(),
+            body: BodyProcessor::default(),
+            map_local: MapLocalProcessor::default(),
+            playback: PlaybackProcessor::default(),
+            script: ScriptProcessor::default(),
+            breakpoint: BreakpointProcessor::default(),
+            continue_matching: false,
         }
     }
 
@@ -32,6 +52,14 @@ impl ProcessorPack {
         self.enable = false;
     }
 
+    pub(crate) fn is_continue(&self) -> bool {
+        self.continue_matching
+    }
+
+    pub(crate) fn set_continue(&mut self, continue_matching: bool) {
+        self.continue_matching = continue_matching;
+    }
+
     pub(crate) fn get_redirect(&self) -> &RequestRedirectProcessor {
         &self.redirect
     }
@@ -56,6 +84,30 @@ impl ProcessorPack {
         &mut self.response
     }
 
+    pub(crate) fn get_status(&self) -> &ResponseStatusProcessor {
+        &self.status
+    }
+
+    pub(crate) fn get_status_mut(&mut self) -> &mut ResponseStatusProcessor {
+        &mut self.status
+    }
+
+    pub(crate) fn get_header(&self) -> &HeaderProcessor {
+        &self.header
+    }
+
+    pub(crate) fn get_header_mut(&mut self) -> &mut HeaderProcessor {
+        &mut self.header
+    }
+
+    pub(crate) fn get_body(&self) -> &BodyProcessor {
+        &self.body
+    }
+
+    pub(crate) fn get_body_mut(&mut self) -> &mut BodyProcessor {
+        &mut self.body
+    }
+
     pub(crate) fn set_redirect(&mut self, redirect: RequestRedirectProcessor) {
         self.redirect = redirect;
     }
@@ -67,4 +119,80 @@ impl ProcessorPack {
     pub(crate) fn set_response(&mut self, response: ResponseProcessor) {
         self.response = response;
     }
+
+    pub(crate) fn set_status(&mut self, status: ResponseStatusProcessor) {
+        self.status = status;
+    }
+
+    pub(crate) fn set_header(&mut self, header: HeaderProcessor) {
+        self.header = header;
+    }
+
+    pub(crate) fn set_body(&mut self, body: BodyProcessor) {
+        self.body = body;
+    }
+
+    pub(crate) fn get_map_local(&self) -> &MapLocalProcessor {
+        &self.map_local
+    }
+
+    pub(crate) fn get_map_local_mut(&mut self) -> &mut MapLocalProcessor {
+        &mut self.map_local
+    }
+
+    pub(crate) fn set_map_local(&mut self, map_local: MapLocalProcessor) {
+        self.map_local = map_local;
+    }
+
+    pub(crate) fn get_playback(&self) -> &PlaybackProcessor {
+        &self.playback
+    }
+
+    pub(crate) fn get_playback_mut(&mut self) -> &mut PlaybackProcessor {
+        &mut self.playback
+    }
+
+    pub(crate) fn set_playback(&mut self, playback: PlaybackProcessor) {
+        self.playback = playback;
+    }
+
+    pub(crate) fn get_script(&self) -> &ScriptProcessor {
+        &self.script
+    }
+
+    pub(crate) fn get_script_mut(&mut self) -> &mut ScriptProcessor {
+        &mut self.script
+    }
+
+    pub(crate) fn set_script(&mut self, script: ScriptProcessor) {
+        self.script = script;
+    }
+
+    pub(crate) fn get_breakpoint(&self) -> &BreakpointProcessor {
+        &self.breakpoint
+    }
+
+    pub(crate) fn get_breakpoint_mut(&mut self) -> &mut BreakpointProcessor {
+        &mut self.breakpoint
+    }
+
+    pub(crate) fn set_breakpoint(&mut self, breakpoint: BreakpointProcessor) {
+        self.breakpoint = breakpoint;
+    }
+}
+
+/// Sort packs by the persisted order, packs missing from it go last in name order.
+pub(crate) fn sort_packs(packs: &mut [ProcessorPack], order: &[String]) {
+    packs.sort_by(|a, b| {
+        let position = |pack: &ProcessorPack| {
+            order
+                .iter()
+                .position(|name| *name == pack.pack_name)
+                .unwrap_or(usize::MAX)
+        };
+
+        position(a)
+            .cmp(&position(b))
+            .then_with(|| a.pack_name.cmp(&b.pack_name))
+    });
 }
@@ -0,0 +1,83 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use serde::Deserialize;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use super::edit::MessageEdit;
+
+/// How long a paused exchange waits for the UI, it goes on untouched afterwards so that
+/// forgotten breakpoints don't hang clients forever.
+pub(crate) const BREAKPOINT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What to do with a paused exchange.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub(crate) enum BreakpointAction {
+    Continue(Box<MessageEdit>),
+    Abort,
+}
+
+/// Exchanges paused by breakpoints, keyed by the connection id.
+#[derive(Debug, Default)]
+pub(crate) struct Breakpoints {
+    paused: Mutex<HashMap<Uuid, oneshot::Sender<BreakpointAction>>>,
+}
+
+impl Breakpoints {
+    /// Register the exchange before the UI is told about it, so that it can't be resumed
+    /// before it's paused.
+    pub(crate) fn pause(&self, id: Uuid) -> oneshot::Receiver<BreakpointAction> {
+        let (tx, rx) = oneshot::channel();
+        self.paused.lock().unwrap().insert(id, tx);
+        rx
+    }
+
+    pub(crate) async fn wait(
+        &self,
+        id: Uuid,
+        paused: oneshot::Receiver<BreakpointAction>,
+    ) -> BreakpointAction {
+        let action = tokio::time::timeout(BREAKPOINT_TIMEOUT, paused).await;
+
+        self.paused.lock().unwrap().remove(&id);
+
+        match action {
+            Ok(Ok(action)) => action,
+            _ => {
+                log::debug!("breakpoint of {id} timed out, continue as is");
+                BreakpointAction::Continue(Box::default())
+            }
+        }
+    }
+
+    /// Returns false if the exchange isn't paused, e.g. it has timed out.
+    pub(crate) fn resume(&self, id: Uuid, action: BreakpointAction) -> bool {
+        match self.paused.lock().unwrap().remove(&id) {
+            Some(tx) => tx.send(action).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume_breakpoint() {
+        let breakpoints = Breakpoints::default();
+        let id = Uuid::new_v4();
+
+        let paused = breakpoints.pause(id);
+        let action: BreakpointAction =
+            serde_json::from_str(r#"{"action":"continue","body":"edited"}"#).unwrap();
+        assert!(breakpoints.resume(id, action));
+        assert!(!breakpoints.resume(id, BreakpointAction::Abort));
+
+        assert!(matches!(
+            breakpoints.wait(id, paused).await,
+            BreakpointAction::Continue(_)
+        ));
+    }
+}
@@ -0,0 +1,53 @@
+use moka::future::Cache;
+use uuid::Uuid;
+
+use crate::processors::matcher::RequestSnapshot;
+
+/// How many bytes of requests are retained for replaying, the least recently used go first.
+const CAPTURE_CAPACITY: u64 = 64 * 1024 * 1024;
+
+/// Roughly the memory a captured request takes, large bodies are spooled to disk though.
+fn weigh(_: &Uuid, req: &RequestSnapshot) -> u32 {
+    let headers: usize = req
+        .headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    let size = req.uri.to_string().len() + headers + req.body.memory_len();
+    u32::try_from(size).unwrap_or(u32::MAX)
+}
+
+/// Requests received from clients, keyed by the connection id.
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedRequests {
+    cache: Cache<Uuid, RequestSnapshot>,
+}
+
+impl Default for CapturedRequests {
+    fn default() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(CAPTURE_CAPACITY)
+                .weigher(weigh)
+                .build(),
+        }
+    }
+}
+
+impl CapturedRequests {
+    pub(crate) async fn insert(&self, id: Uuid, req: RequestSnapshot) {
+        self.cache.insert(id, req).await;
+    }
+
+    pub(crate) fn get(&self, id: &Uuid) -> Option<RequestSnapshot> {
+        self.cache.get(id)
+    }
+}
+
+/// A request to be sent again, `replay_of` is the captured request it's made from.
+#[derive(Debug)]
+pub(crate) struct Replay {
+    pub id: Uuid,
+    pub replay_of: Option<Uuid>,
+    pub req: hyper::Request<hyper::Body>,
+}
@@ -1,22 +1,33 @@
 use crate::error::endpoint_error::EndpointError;
-use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder, ZstdDecoder};
+use async_compression::tokio::bufread::{
+    BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder, ZstdDecoder,
+    ZstdEncoder,
+};
 use bstr::ByteSlice;
-use bytes::Bytes;
-use futures::Stream;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
 use http::{
     header::{CONTENT_ENCODING, CONTENT_LENGTH},
     HeaderMap, HeaderValue,
 };
-use hyper::{Body, Error as HyperError, Request, Response};
+use hyper::{
+    body::{to_bytes, HttpBody},
+    Body, Error as HyperError, Request, Response,
+};
 use std::{
-    io,
+    collections::hash_map::DefaultHasher,
+    hash::Hasher,
     io::Error as IoError,
     pin::Pin,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 use tokio::io::{AsyncBufRead, AsyncRead, BufReader};
 use tokio_util::io::{ReaderStream, StreamReader};
 
+/// Raw bytes of a request held to pass it through if decoding fails.
+const HELD_REQUEST_LIMIT: usize = 1024 * 1024;
+
 pub struct IoStream<T: Stream<Item = Result<Bytes, HyperError>> + Unpin>(pub T);
 
 impl<T: Stream<Item = Result<Bytes, HyperError>> + Unpin> Stream for IoStream<T> {
@@ -25,7 +36,7 @@ impl<T: Stream<Item = Result<Bytes, HyperError>> + Unpin> Stream for IoStream<T>
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match futures::ready!(Pin::new(&mut self.0).poll_next(cx)) {
             Some(Ok(chunk)) => Poll::Ready(Some(Ok(chunk))),
-            Some(Err(err)) => Poll::Ready(Some(Err(IoError::new(io::ErrorKind::Other, err)))),
+            Some(Err(err)) => Poll::Ready(Some(Err(IoError::other(err)))),
             None => Poll::Ready(None),
         }
     }
@@ -54,7 +65,8 @@ impl Decoder {
             b"zstd" => Box::new(ZstdDecoder::new(reader)),
             _ => {
                 return Err(EndpointError::Decoder {
-                    scenario: "unknown decoding",
+                    scenario: "unknown encoding",
+                    encoding: String::from_utf8_lossy(encoding).into_owned(),
                 })
             }
         };
@@ -94,48 +106,474 @@ fn decode_body<'a>(
     Ok(decoder.into())
 }
 
-pub fn decode_request(mut req: Request<Body>) -> Result<Request<Body>, EndpointError> {
-    if !req.headers().contains_key(CONTENT_ENCODING) {
-        return Ok(req);
+fn is_supported(encoding: &[u8]) -> bool {
+    matches!(
+        encoding,
+        b"identity" | b"gzip" | b"x-gzip" | b"deflate" | b"br" | b"zstd"
+    )
+}
+
+fn check_encodings(headers: &HeaderMap) -> Result<(), EndpointError> {
+    match parse_encodings(headers).find(|encoding| !is_supported(encoding)) {
+        Some(encoding) => Err(EndpointError::Decoder {
+            scenario: "unknown encoding",
+            encoding: String::from_utf8_lossy(encoding).into_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Decode the body in place. If it's in an encoding which can't be decoded, the body and the
+/// headers are left as they are, so that it can be passed through untouched.
+pub(super) fn decode_message(
+    headers: &mut HeaderMap,
+    body: &mut Body,
+) -> Result<(), EndpointError> {
+    if !headers.contains_key(CONTENT_ENCODING) {
+        return Ok(());
     }
 
-    if let Some(val) = req.headers_mut().remove(CONTENT_LENGTH) {
+    check_encodings(headers)?;
+
+    if let Some(val) = headers.remove(CONTENT_LENGTH) {
         if val == "0" {
-            return Ok(req);
+            return Ok(());
         }
     }
 
-    let (mut parts, body) = req.into_parts();
+    *body = decode_body(parse_encodings(headers), std::mem::take(body))?;
+
+    headers.remove(CONTENT_ENCODING);
+
+    Ok(())
+}
+
+fn corrupt_body(headers: &HeaderMap) -> EndpointError {
+    let encodings: Vec<_> = headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .map(|encoding| String::from_utf8_lossy(encoding.as_bytes()).into_owned())
+        .collect();
+
+    EndpointError::Decoder {
+        scenario: "corrupt body in",
+        encoding: encodings.join(", "),
+    }
+}
+
+/// Decode the body as it's read. Up to `HELD_REQUEST_LIMIT` bytes of it are decoded up front, so
+/// that a corrupt one, e.g. a truncated gzip body, is passed through as it is along with its
+/// headers, rather than failing whoever reads it. Failures beyond it can't be undone.
+pub async fn decode_request(req: &mut Request<Body>) -> Result<(), EndpointError> {
+    if !req.headers().contains_key(CONTENT_ENCODING) || req.body().is_end_stream() {
+        return Ok(());
+    }
+
+    check_encodings(req.headers())?;
 
-    let body = {
-        let encodings = parse_encodings(&parts.headers);
-        decode_body(encodings, body)?
+    let raw = Arc::new(Mutex::new(RawBody {
+        body: std::mem::take(req.body_mut()),
+        held: Some(vec![]),
+    }));
+
+    let encodings: Vec<Vec<u8>> = parse_encodings(req.headers()).map(<[u8]>::to_vec).collect();
+    // the encodings are checked
+    let mut decoded = decode_body(
+        encodings.iter().map(Vec::as_slice),
+        Body::wrap_stream(RawStream(Arc::clone(&raw))),
+    )?;
+
+    let mut probed = BytesMut::new();
+    let complete = loop {
+        // both sides are bounded, a small body may be decoded into a large one
+        if probed.len() > HELD_REQUEST_LIMIT || raw.lock().unwrap().held_len() > HELD_REQUEST_LIMIT
+        {
+            break false;
+        }
+
+        match decoded.data().await {
+            Some(Ok(chunk)) => probed.extend_from_slice(&chunk),
+            Some(Err(_)) => {
+                drop(decoded);
+                let raw = std::mem::take(&mut *raw.lock().unwrap());
+                *req.body_mut() = raw.into_body();
+                return Err(corrupt_body(req.headers()));
+            }
+            None => break true,
+        }
     };
 
-    parts.headers.remove(CONTENT_ENCODING);
+    raw.lock().unwrap().held = None;
+    let probed = probed.freeze();
+    *req.body_mut() = if complete {
+        Body::from(probed)
+    } else {
+        let probed = futures::stream::once(futures::future::ready(Ok(probed)));
+        Body::wrap_stream(probed.chain(decoded))
+    };
+    req.headers_mut().remove(CONTENT_ENCODING);
+    req.headers_mut().remove(CONTENT_LENGTH);
 
-    Ok(Request::from_parts(parts, body))
+    Ok(())
 }
 
-pub fn decode_response(mut res: Response<Body>) -> Result<Response<Body>, EndpointError> {
-    if !res.headers().contains_key(CONTENT_ENCODING) {
-        return Ok(res);
+/// The encoded body, along with the chunks read from it until the decoded body is known to be
+/// valid.
+#[derive(Default)]
+struct RawBody {
+    body: Body,
+    held: Option<Vec<Bytes>>,
+}
+
+impl RawBody {
+    fn held_len(&self) -> usize {
+        self.held.iter().flatten().map(|chunk| chunk.len()).sum()
     }
 
-    if let Some(val) = res.headers_mut().remove(CONTENT_LENGTH) {
-        if val == "0" {
-            return Ok(res);
+    /// The encoded body as it's sent, the held chunks go first.
+    fn into_body(self) -> Body {
+        let held = self
+            .held
+            .unwrap_or_default()
+            .into_iter()
+            .map(Ok::<_, HyperError>);
+        Body::wrap_stream(futures::stream::iter(held).chain(self.body))
+    }
+}
+
+/// Feeds the decoder from a `RawBody`, which could be taken back if decoding fails early.
+struct RawStream(Arc<Mutex<RawBody>>);
+
+impl Stream for RawStream {
+    type Item = Result<Bytes, HyperError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut raw = self.0.lock().unwrap();
+        let polled = Pin::new(&mut raw.body).poll_data(cx);
+
+        if let Poll::Ready(Some(Ok(chunk))) = &polled {
+            if let Some(held) = raw.held.as_mut() {
+                held.push(chunk.clone());
+            }
         }
+
+        polled
     }
+}
 
-    let (mut parts, body) = res.into_parts();
+/// Decode the body as it's read, the headers are only changed once the first decoded chunk is
+/// out. A body which fails before, e.g. one which isn't in the encoding it claims, is passed
+/// through as it is. Failures later on can't be undone, as the decoded chunks may be sent on.
+pub async fn decode_response(res: &mut Response<Body>) -> Result<(), EndpointError> {
+    // e.g. responses to `HEAD`
+    if !res.headers().contains_key(CONTENT_ENCODING) || res.body().is_end_stream() {
+        return Ok(());
+    }
+
+    check_encodings(res.headers())?;
+
+    let raw = Arc::new(Mutex::new(RawBody {
+        body: std::mem::take(res.body_mut()),
+        held: Some(vec![]),
+    }));
+
+    let encodings: Vec<Vec<u8>> = parse_encodings(res.headers()).map(<[u8]>::to_vec).collect();
+    let mut decoded = decode_body(
+        encodings.iter().map(Vec::as_slice),
+        Body::wrap_stream(RawStream(Arc::clone(&raw))),
+    )?;
 
-    let body = {
-        let encodings = parse_encodings(&parts.headers);
-        decode_body(encodings, body)?
+    match decoded.data().await {
+        Some(Err(_)) => {
+            drop(decoded);
+            let raw = std::mem::take(&mut *raw.lock().unwrap());
+            *res.body_mut() = raw.into_body();
+            Err(corrupt_body(res.headers()))
+        }
+        first => {
+            raw.lock().unwrap().held = None;
+            let first = futures::stream::iter(first);
+            *res.body_mut() = Body::wrap_stream(first.chain(decoded));
+            res.headers_mut().remove(CONTENT_ENCODING);
+            res.headers_mut().remove(CONTENT_LENGTH);
+            Ok(())
+        }
+    }
+}
+
+/// What's been read from a decoded body, to tell if it's changed by the time it's restored.
+#[derive(Default)]
+struct Fingerprint {
+    started: bool,
+    /// The body is read to the end without errors.
+    complete: bool,
+    failed: bool,
+    len: usize,
+    hasher: DefaultHasher,
+}
+
+impl Fingerprint {
+    fn update(&mut self, chunk: &Result<Bytes, HyperError>) {
+        match chunk {
+            Ok(chunk) => {
+                self.len += chunk.len();
+                self.hasher.write(chunk);
+            }
+            Err(_) => self.failed = true,
+        }
+    }
+
+    fn matches(&self, body: &[u8]) -> bool {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(body);
+        self.complete
+            && !self.failed
+            && self.len == body.len()
+            && self.hasher.finish() == hasher.finish()
+    }
+}
+
+/// The original encoding of a response which is decoded for the rules, so that the client still
+/// gets the body as upstream sent it.
+pub struct PreservedEncoding {
+    content_encoding: Vec<HeaderValue>,
+    content_length: Option<HeaderValue>,
+    /// The encoded body, along with the chunks the decoder has read from it.
+    raw: Arc<Mutex<RawBody>>,
+    decoded: Arc<Mutex<Fingerprint>>,
+}
+
+impl PreservedEncoding {
+    /// Put the original encoding back. The original bytes are sent unless the body is changed,
+    /// e.g. by the rules, which is encoded again then.
+    pub async fn restore(self, mut res: Response<Body>) -> Response<Body> {
+        let headers = res.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+        for value in self.content_encoding.iter() {
+            headers.append(CONTENT_ENCODING, value.clone());
+        }
+
+        let decoded = std::mem::take(&mut *self.decoded.lock().unwrap());
+        let unchanged = if !decoded.started {
+            true
+        } else if decoded.complete {
+            // it's been read as a whole, buffering it again doesn't cost much more
+            match to_bytes(std::mem::take(res.body_mut())).await {
+                Ok(body) if decoded.matches(&body) => true,
+                Ok(body) => {
+                    *res.body_mut() = Body::from(body);
+                    false
+                }
+                Err(e) => {
+                    *res.body_mut() =
+                        Body::wrap_stream(futures::stream::iter([Err::<Bytes, _>(e)]));
+                    false
+                }
+            }
+        } else {
+            false
+        };
+
+        if unchanged {
+            let raw = std::mem::take(&mut *self.raw.lock().unwrap());
+            *res.body_mut() = raw.into_body();
+            if let Some(content_length) = self.content_length {
+                res.headers_mut().insert(CONTENT_LENGTH, content_length);
+            }
+        } else {
+            let encodings: Vec<Vec<u8>> =
+                parse_encodings(res.headers()).map(<[u8]>::to_vec).collect();
+            let body = std::mem::take(res.body_mut());
+            // the encodings are parsed in the order of decoding
+            *res.body_mut() = encode_body(encodings.iter().rev(), body);
+        }
+
+        res
+    }
+}
+
+/// Like `decode_response`, but the encoding can be restored with `PreservedEncoding`. The body
+/// is only decoded once it's read.
+pub fn decode_response_preserving(
+    res: &mut Response<Body>,
+) -> Result<Option<PreservedEncoding>, EndpointError> {
+    if !res.headers().contains_key(CONTENT_ENCODING) {
+        return Ok(None);
+    }
+
+    check_encodings(res.headers())?;
+
+    let preserved = PreservedEncoding {
+        content_encoding: res
+            .headers()
+            .get_all(CONTENT_ENCODING)
+            .iter()
+            .cloned()
+            .collect(),
+        content_length: res.headers().get(CONTENT_LENGTH).cloned(),
+        raw: Arc::new(Mutex::new(RawBody {
+            body: std::mem::take(res.body_mut()),
+            // the original bytes are sent if the decoded body turns out unchanged
+            held: Some(vec![]),
+        })),
+        decoded: Arc::default(),
     };
 
-    parts.headers.remove(CONTENT_ENCODING);
+    let encodings: Vec<Vec<u8>> = parse_encodings(res.headers()).map(<[u8]>::to_vec).collect();
+    let raw = Arc::clone(&preserved.raw);
+    let decoded = Arc::clone(&preserved.decoded);
+    let body = futures::stream::once(async move {
+        decoded.lock().unwrap().started = true;
+        let raw = Body::wrap_stream(RawStream(raw));
+        // the encodings are checked
+        let body = decode_body(encodings.iter().map(Vec::as_slice), raw).unwrap_or_default();
+
+        let read = Arc::clone(&decoded);
+        body.inspect(move |chunk| read.lock().unwrap().update(chunk))
+            .chain(futures::stream::poll_fn(move |_| {
+                decoded.lock().unwrap().complete = true;
+                Poll::Ready(None)
+            }))
+    })
+    .flatten();
+
+    *res.body_mut() = Body::wrap_stream(body);
+    res.headers_mut().remove(CONTENT_ENCODING);
+    res.headers_mut().remove(CONTENT_LENGTH);
+
+    Ok(Some(preserved))
+}
+
+fn encode_body<'a>(encodings: impl IntoIterator<Item = &'a Vec<u8>>, body: Body) -> Body {
+    let mut reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(StreamReader::new(IoStream(body)));
 
-    Ok(Response::from_parts(parts, body))
+    for encoding in encodings {
+        let buffered = BufReader::new(reader);
+        reader = match encoding.as_slice() {
+            b"gzip" | b"x-gzip" => Box::new(GzipEncoder::new(buffered)),
+            b"deflate" => Box::new(ZlibEncoder::new(buffered)),
+            b"br" => Box::new(BrotliEncoder::new(buffered)),
+            b"zstd" => Box::new(ZstdEncoder::new(buffered)),
+            _ => Box::new(buffered),
+        };
+    }
+
+    Body::wrap_stream(ReaderStream::new(reader))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_TYPE;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_decode_unknown_encoding() {
+        let mut res = Response::builder()
+            .header(CONTENT_ENCODING, "gzip, x-custom")
+            .header(CONTENT_LENGTH, "5")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("hello"))
+            .unwrap();
+
+        let err = decode_response(&mut res).await.unwrap_err();
+
+        assert!(
+            matches!(err, EndpointError::Decoder { ref encoding, .. } if encoding == "x-custom")
+        );
+        assert_eq!(res.headers()[CONTENT_ENCODING], "gzip, x-custom");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "5");
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_preserve_encoding() {
+        let gzipped = || {
+            let body = Body::from("hello");
+            let encoded = encode_body([&b"gzip".to_vec()], body);
+            Response::builder()
+                .header(CONTENT_ENCODING, "gzip")
+                .body(encoded)
+                .unwrap()
+        };
+
+        // untouched, the original bytes are sent
+        let mut res = gzipped();
+        let preserved = decode_response_preserving(&mut res).unwrap().unwrap();
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        let res = preserved.restore(res).await;
+        assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+        let raw = to_bytes(res.into_body()).await.unwrap();
+        assert!(raw.starts_with(b"\x1f\x8b"));
+
+        // read but unchanged, the original bytes are still sent
+        let mut res = Response::builder()
+            .header(CONTENT_ENCODING, "gzip")
+            .header(CONTENT_LENGTH, raw.len())
+            .body(Body::from(raw.clone()))
+            .unwrap();
+        let preserved = decode_response_preserving(&mut res).unwrap().unwrap();
+        let body = to_bytes(std::mem::take(res.body_mut())).await.unwrap();
+        *res.body_mut() = Body::from(body);
+        let res = preserved.restore(res).await;
+        assert_eq!(
+            res.headers()[CONTENT_LENGTH],
+            raw.len().to_string().as_str()
+        );
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), raw);
+
+        // modified, the body is encoded again
+        let mut res = gzipped();
+        let preserved = decode_response_preserving(&mut res).unwrap().unwrap();
+        let body = to_bytes(std::mem::take(res.body_mut())).await.unwrap();
+        assert_eq!(body, "hello");
+        *res.body_mut() = Body::from("world");
+        let mut res = preserved.restore(res).await;
+        decode_response(&mut res).await.unwrap();
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn test_pass_corrupt_body_through() {
+        let mut res = Response::builder()
+            .header(CONTENT_ENCODING, "gzip")
+            .header(CONTENT_LENGTH, "5")
+            .body(Body::from("hello"))
+            .unwrap();
+
+        let err = decode_response(&mut res).await.unwrap_err();
+
+        assert!(matches!(err, EndpointError::Decoder { ref encoding, .. } if encoding == "gzip"));
+        assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "5");
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "hello");
+
+        let mut req = Request::builder()
+            .header(CONTENT_ENCODING, "gzip")
+            .body(Body::from("hello"))
+            .unwrap();
+
+        assert!(decode_request(&mut req).await.is_err());
+        assert_eq!(req.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(to_bytes(req.into_body()).await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_decode_large_request() {
+        let body = Bytes::from(vec![b'a'; HELD_REQUEST_LIMIT * 3]);
+        let gzip = [b"gzip".to_vec()];
+        let encoded = to_bytes(encode_body(gzip.iter(), Body::from(body.clone())))
+            .await
+            .unwrap();
+
+        let mut req = Request::builder()
+            .header(CONTENT_ENCODING, "gzip")
+            .body(Body::from(encoded))
+            .unwrap();
+
+        decode_request(&mut req).await.unwrap();
+        assert!(req.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(to_bytes(req.into_body()).await.unwrap(), body);
+    }
 }
@@ -0,0 +1,193 @@
+use std::{fmt::Display, str::FromStr};
+
+use bytes::Bytes;
+use http::{
+    header::{CONTENT_LENGTH, TRANSFER_ENCODING},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+};
+use hyper::{Body, Request, Response};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::events::body::BodyEncoding;
+
+/// Edited parts of a request or response, missing parts are kept as is.
+#[derive(Debug, Default, Deserialize)]
+#[serde(try_from = "EncodedEdit")]
+pub(crate) struct MessageEdit {
+    method: Option<Method>,
+    uri: Option<Uri>,
+    status: Option<StatusCode>,
+    headers: Option<HeaderMap>,
+    body: Option<Bytes>,
+}
+
+/// [`MessageEdit`] as it's sent by the UI, the body is encoded like the one of events.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncodedEdit {
+    #[serde(default, deserialize_with = "parse_optional")]
+    method: Option<Method>,
+    #[serde(default, deserialize_with = "parse_optional")]
+    uri: Option<Uri>,
+    #[serde(default, deserialize_with = "parse_status")]
+    status: Option<StatusCode>,
+    /// `[name, value]` pairs which replace all headers.
+    #[serde(default, deserialize_with = "parse_headers")]
+    headers: Option<HeaderMap>,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    body_encoding: BodyEncoding,
+}
+
+impl TryFrom<EncodedEdit> for MessageEdit {
+    type Error = String;
+
+    fn try_from(edit: EncodedEdit) -> Result<Self, Self::Error> {
+        let body = edit
+            .body
+            .map(|body| edit.body_encoding.decode(&body))
+            .transpose()
+            .map_err(|e| format!("invalid base64 body: {e}"))?;
+
+        Ok(MessageEdit {
+            method: edit.method,
+            uri: edit.uri,
+            status: edit.status,
+            headers: edit.headers,
+            body,
+        })
+    }
+}
+
+impl MessageEdit {
+    pub(crate) fn apply_to_request(self, req: Request<Body>) -> Request<Body> {
+        let (mut parts, body) = req.into_parts();
+
+        if let Some(method) = self.method {
+            parts.method = method;
+        }
+        if let Some(uri) = self.uri {
+            parts.uri = uri;
+        }
+
+        let body = replace_message(&mut parts.headers, body, self.headers, self.body);
+        Request::from_parts(parts, body)
+    }
+
+    pub(crate) fn apply_to_response(self, res: Response<Body>) -> Response<Body> {
+        let (mut parts, body) = res.into_parts();
+
+        if let Some(status) = self.status {
+            parts.status = status;
+        }
+
+        let body = replace_message(&mut parts.headers, body, self.headers, self.body);
+        Response::from_parts(parts, body)
+    }
+}
+
+fn replace_message(
+    headers: &mut HeaderMap,
+    body: Body,
+    edited_headers: Option<HeaderMap>,
+    edited_body: Option<Bytes>,
+) -> Body {
+    if let Some(edited_headers) = edited_headers {
+        *headers = edited_headers;
+    }
+
+    match edited_body {
+        Some(edited_body) => {
+            headers.remove(TRANSFER_ENCODING);
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(edited_body.len()));
+            Body::from(edited_body)
+        }
+        None => body,
+    }
+}
+
+fn parse_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+fn parse_status<'de, D>(deserializer: D) -> Result<Option<StatusCode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<u16>::deserialize(deserializer)?
+        .map(|status| StatusCode::from_u16(status).map_err(D::Error::custom))
+        .transpose()
+}
+
+fn parse_headers<'de, D>(deserializer: D) -> Result<Option<HeaderMap>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(pairs) = Option::<Vec<(String, String)>>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes()).map_err(D::Error::custom)?,
+            HeaderValue::from_str(&value).map_err(D::Error::custom)?,
+        );
+    }
+
+    Ok(Some(headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_LENGTH;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_edit() {
+        let edit: MessageEdit = serde_json::from_str(
+            r#"{"method":"PUT","headers":[["x-edited","1"]],"body":"edited"}"#,
+        )
+        .unwrap();
+
+        let req = Request::builder()
+            .uri("https://www.x.com/")
+            .header("x-origin", "1")
+            .body(Body::from("origin"))
+            .unwrap();
+        let req = edit.apply_to_request(req);
+
+        assert_eq!(req.method(), Method::PUT);
+        assert_eq!(req.uri(), "https://www.x.com/");
+        assert!(req.headers().get("x-origin").is_none());
+        assert_eq!(req.headers().get(CONTENT_LENGTH).unwrap(), "6");
+        assert_eq!(
+            hyper::body::to_bytes(req.into_body()).await.unwrap(),
+            "edited"
+        );
+
+        let edit: MessageEdit =
+            serde_json::from_str(r#"{"status":404,"body":"/wA=","bodyEncoding":"base64"}"#)
+                .unwrap();
+        let res = edit.apply_to_response(Response::new(Body::from("origin")));
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            hyper::body::to_bytes(res.into_body()).await.unwrap(),
+            &[0xff, 0x00][..]
+        );
+
+        assert!(
+            serde_json::from_str::<MessageEdit>(r#"{"body":"!","bodyEncoding":"base64"}"#).is_err()
+        );
+    }
+}
@@ -9,17 +9,36 @@ use tauri::{
 };
 use tokio::{
     net::TcpListener,
-    sync::{mpsc, oneshot},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
 };
 
-use crate::commands;
+use crate::{app_conf, commands, events::Events, session::SessionStore};
 
-use self::service::ProxyService;
+use self::{
+    breakpoint::Breakpoints,
+    capture::{CapturedRequests, Replay},
+    service::ProxyService,
+    upstream::Upstreams,
+};
 
+pub(crate) mod breakpoint;
+pub(crate) mod capture;
 mod decoder;
+pub(crate) mod edit;
 mod rewind;
 mod service;
+mod socks;
+mod tee;
+#[cfg(target_os = "linux")]
+mod transparent;
 mod tunnel;
+mod upstream;
+
+/// Events waiting to be recorded into the session.
+const SESSION_QUEUE: usize = 1024;
 
 pub(crate) type ProxyState = Mutex<
     Option<(
@@ -28,6 +47,7 @@ pub(crate) type ProxyState = Mutex<
         tauri::async_runtime::JoinHandle<()>,
         tauri::async_runtime::JoinHandle<()>,
         tauri::async_runtime::JoinHandle<()>,
+        mpsc::Sender<Replay>,
     )>,
 >;
 
@@ -45,6 +65,9 @@ async fn check_port_available(port: u16) -> bool {
 
 pub fn set_proxy_state(app: &tauri::App) {
     app.manage(Mutex::new(None) as ProxyState);
+    app.manage(Arc::new(Breakpoints::default()));
+    // Outlives the proxy, so that requests captured before a restart can still be replayed.
+    app.manage(CapturedRequests::default());
 }
 
 #[tauri::command]
@@ -61,6 +84,47 @@ pub(crate) async fn start_proxy<R: Runtime>(
 
     let addr: SocketAddr = ([127, 0, 0, 1], port).into();
 
+    let setting = app_conf::get_app_setting();
+    let upstreams = Upstreams::new(setting.upstream_proxies())?;
+
+    let socks5_addr = match setting.socks5_port() {
+        Some(socks5_port) if socks5_port == port || !check_port_available(socks5_port).await => {
+            return Err(format!("SOCKS5 port {} was occupied", socks5_port));
+        }
+        Some(socks5_port) => Some(SocketAddr::from(([127, 0, 0, 1], socks5_port))),
+        None => None,
+    };
+
+    #[cfg(not(target_os = "linux"))]
+    if setting.transparent_port().is_some() {
+        return Err(String::from("transparent proxy is supported on Linux only"));
+    }
+    #[cfg(target_os = "linux")]
+    let transparent_addr = match setting.transparent_port() {
+        Some(transparent_port)
+            if transparent_port == port
+                || Some(transparent_port) == setting.socks5_port()
+                || !check_port_available(transparent_port).await =>
+        {
+            return Err(format!(
+                "transparent port {} was occupied",
+                transparent_port
+            ));
+        }
+        Some(transparent_port) => Some(SocketAddr::from((
+            setting.transparent_address(),
+            transparent_port,
+        ))),
+        None => None,
+    };
+
+    let record_sessions = setting.record_sessions();
+    let (max_sessions, max_session_bytes) = (setting.max_sessions(), setting.max_session_bytes());
+
+    let breakpoints = Arc::clone(&app.state::<Arc<Breakpoints>>());
+    let captured = CapturedRequests::clone(&app.state::<CapturedRequests>());
+    let (replay_tx, replay_rx) = mpsc::channel(20);
+
     let (transporter_tx, mut transporter_recv) = tokio::sync::mpsc::channel(200);
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
 
@@ -71,19 +135,46 @@ pub(crate) async fn start_proxy<R: Runtime>(
     // ------------------------------- Interceptors update channel -------------------------------
 
     let proxy_thread = async_runtime::spawn(async move {
-        if let Err(e) =
-            ProxyService::new(addr, Some(transporter_tx.clone()), Arc::clone(&processor))
-                .start(async move {
-                    let _ = shutdown_rx.await;
-                })
-                .await
+        let service = ProxyService::new(
+            addr,
+            Some(transporter_tx.clone()),
+            Arc::clone(&processor),
+            breakpoints,
+            captured,
+            replay_rx,
+            setting.preserve_content_encoding(),
+            Arc::new(upstreams),
+        )
+        .socks5(socks5_addr);
+        #[cfg(target_os = "linux")]
+        let service = service.transparent(transparent_addr);
+
+        if let Err(e) = service
+            .start(async move {
+                let _ = shutdown_rx.await;
+            })
+            .await
         {
             log::error!("Running proxy on {:?}, error: {}", addr, e);
         }
     });
 
     let transporter_thread = async_runtime::spawn(async move {
+        // sessions are recorded on their own, so that the events aren't held back by the disk
+        let session_tx = record_sessions.then(|| {
+            let (session_tx, session_rx) = mpsc::channel(SESSION_QUEUE);
+            async_runtime::spawn(record_session(max_sessions, max_session_bytes, session_rx));
+            session_tx
+        });
+
         while let Some(exchange) = transporter_recv.recv().await {
+            if let Some(session_tx) = session_tx.as_ref() {
+                // left out rather than piled up in memory if the disk can't keep up
+                if let Err(TrySendError::Full(_)) = session_tx.try_send(exchange.clone()) {
+                    log::warn!("Session recording falls behind, an event is left out");
+                }
+            }
+
             app.emit("proxy_event", exchange).unwrap();
         }
     });
@@ -95,11 +186,41 @@ pub(crate) async fn start_proxy<R: Runtime>(
         proxy_thread,
         processor_thread,
         transporter_thread,
+        replay_tx,
     ));
 
     Ok(())
 }
 
+/// Record the proxy run as a session, after making room for it within the limits.
+async fn record_session(max_sessions: usize, max_bytes: u64, mut events: mpsc::Receiver<Events>) {
+    let store = SessionStore::default();
+
+    if let Err(e) = store.prune(max_sessions, max_bytes) {
+        log::error!("Prune sessions error: {}", e);
+    }
+
+    let mut recorder = match store.record().await {
+        Ok(recorder) => recorder.limit(max_bytes),
+        Err(e) => {
+            log::error!("Create session error: {}", e);
+            return;
+        }
+    };
+
+    while let Some(event) = events.recv().await {
+        if let Err(e) = recorder.record(&event).await {
+            // e.g. the session has been deleted in the meantime
+            log::error!(
+                "Record session {} error, stop recording: {}",
+                recorder.id(),
+                e
+            );
+            return;
+        }
+    }
+}
+
 #[tauri::command]
 pub(crate) async fn stop_proxy(proxy: State<'_, ProxyState>) -> Result<(), String> {
     let mut proxy = proxy.lock().await;
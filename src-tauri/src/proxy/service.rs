@@ -1,22 +1,34 @@
+use arc_swap::ArcSwap;
 use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
-use tauri::async_runtime::Mutex;
-use tokio::sync::mpsc::Sender;
+use tokio::{
+    net::TcpListener,
+    sync::mpsc::{Receiver, Sender},
+};
 
 use hyper::{
     server::conn::AddrStream,
     service::{make_service_fn, service_fn},
     Client, Server,
 };
-use hyper_rustls::HttpsConnectorBuilder;
+use hyper_rustls::{ConfigBuilderExt, HttpsConnectorBuilder};
 use snafu::ResultExt;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_tungstenite::Connector;
 
-use super::tunnel::Tunnel;
+#[cfg(target_os = "linux")]
+use super::transparent;
+use super::{
+    breakpoint::Breakpoints,
+    capture::{CapturedRequests, Replay},
+    tunnel::Tunnel,
+    upstream::{UpstreamConnector, Upstreams},
+};
 
 use crate::{
     ca::Ssl,
     error::{
         self,
-        endpoint_error::{ConnectError, HttpError},
+        endpoint_error::{ConnectError, HttpError, IoError},
         ServerError,
     },
     events,
@@ -26,22 +38,57 @@ use crate::{
 pub struct ProxyService {
     addr: SocketAddr,
     transporter: Option<Sender<events::Events>>,
-    processor: Arc<Mutex<HttpProcessor>>,
+    processor: Arc<ArcSwap<HttpProcessor>>,
+    breakpoints: Arc<Breakpoints>,
+    captured: CapturedRequests,
+    replays: Receiver<Replay>,
+    preserve_encoding: bool,
+    upstreams: Arc<Upstreams>,
+    socks5_addr: Option<SocketAddr>,
+    #[cfg(target_os = "linux")]
+    transparent_addr: Option<SocketAddr>,
 }
 
 impl ProxyService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         addr: SocketAddr,
         transporter: Option<Sender<events::Events>>,
-        processor: Arc<Mutex<HttpProcessor>>,
+        processor: Arc<ArcSwap<HttpProcessor>>,
+        breakpoints: Arc<Breakpoints>,
+        captured: CapturedRequests,
+        replays: Receiver<Replay>,
+        preserve_encoding: bool,
+        upstreams: Arc<Upstreams>,
     ) -> Self {
         Self {
             addr,
             transporter,
             processor,
+            breakpoints,
+            captured,
+            replays,
+            preserve_encoding,
+            upstreams,
+            socks5_addr: None,
+            #[cfg(target_os = "linux")]
+            transparent_addr: None,
         }
     }
 
+    /// Also listen on `addr` for SOCKS5 clients.
+    pub fn socks5(mut self, addr: Option<SocketAddr>) -> Self {
+        self.socks5_addr = addr;
+        self
+    }
+
+    /// Also listen on `addr` for connections redirected by iptables or nftables.
+    #[cfg(target_os = "linux")]
+    pub fn transparent(mut self, addr: Option<SocketAddr>) -> Self {
+        self.transparent_addr = addr;
+        self
+    }
+
     pub async fn start<F: Future<Output = ()>>(
         self,
         should_shutdown_signal: F,
@@ -56,13 +103,21 @@ impl ProxyService {
         #[cfg(feature = "http2")]
         let connector = connector.enable_http2();
 
-        let connector = connector.build();
+        let connector =
+            connector.wrap_connector(UpstreamConnector::new(Arc::clone(&self.upstreams)));
 
         let client = Client::builder()
             .http1_preserve_header_case(true)
             .http1_title_case_headers(true)
             .build(connector);
 
+        let websocket_connector = Connector::Rustls(Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_webpki_roots()
+                .with_no_client_auth(),
+        ));
+
         let server_builder = Server::try_bind(&addr)
             .context(ConnectError {})
             .context(ServerError {
@@ -73,12 +128,83 @@ impl ProxyService {
 
         let ssl = Arc::new(Ssl::default());
 
+        // Replayed requests go through the same client and rules as the captured ones.
+        let replayer = Tunnel {
+            ca: Arc::clone(&ssl),
+            client: client.clone(),
+            websocket_connector: Some(websocket_connector.clone()),
+            transporter: self.transporter.clone().unwrap(),
+            processor: Arc::clone(&self.processor),
+            breakpoints: Arc::clone(&self.breakpoints),
+            captured: self.captured.clone(),
+            preserve_encoding: self.preserve_encoding,
+            upstreams: Arc::clone(&self.upstreams),
+        };
+        let socks5_listener = match self.socks5_addr {
+            Some(addr) => Some(TcpListener::bind(addr).await.context(IoError {}).context(
+                ServerError {
+                    scenario: "binding SOCKS5 listener",
+                },
+            )?),
+            None => None,
+        };
+        let socks5_thread = socks5_listener.map(|listener| {
+            let tunnel = replayer.clone();
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(tunnel.clone().accept_socks5(stream));
+                        }
+                        Err(e) => log::error!("Accept SOCKS5 connection error: {e}"),
+                    }
+                }
+            })
+        });
+
+        #[cfg(target_os = "linux")]
+        let transparent_thread = match self.transparent_addr {
+            Some(addr) => {
+                let listener =
+                    transparent::bind(addr)
+                        .context(IoError {})
+                        .context(ServerError {
+                            scenario: "binding transparent listener",
+                        })?;
+                let tunnel = replayer.clone();
+                Some(tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => {
+                                tokio::spawn(
+                                    tunnel.clone().accept_transparent(stream, addr.port()),
+                                );
+                            }
+                            Err(e) => log::error!("Accept redirected connection error: {e}"),
+                        }
+                    }
+                }))
+            }
+            None => None,
+        };
+
+        let mut replays = self.replays;
+        tokio::spawn(async move {
+            while let Some(replay) = replays.recv().await {
+                tokio::spawn(replayer.clone().replay(replay));
+            }
+        });
+
         let make_service = make_service_fn(move |_conn: &AddrStream| {
             let client = client.clone();
             let ca = Arc::clone(&ssl);
             let transporter = self.transporter.clone();
             let processor = Arc::clone(&self.processor);
-            let websocket_connector = None;
+            let breakpoints = Arc::clone(&self.breakpoints);
+            let captured = self.captured.clone();
+            let preserve_encoding = self.preserve_encoding;
+            let upstreams = Arc::clone(&self.upstreams);
+            let websocket_connector = Some(websocket_connector.clone());
 
             // accept every request with async tasks
             async move {
@@ -89,19 +215,33 @@ impl ProxyService {
                         websocket_connector: websocket_connector.clone(),
                         transporter: transporter.clone().unwrap(),
                         processor: Arc::clone(&processor),
+                        breakpoints: Arc::clone(&breakpoints),
+                        captured: captured.clone(),
+                        preserve_encoding,
+                        upstreams: Arc::clone(&upstreams),
                     }
                     .accept(req)
                 }))
             }
         });
 
-        server_builder
+        let served = server_builder
             .serve(make_service)
             .with_graceful_shutdown(should_shutdown_signal)
             .await
             .context(HttpError {})
             .context(ServerError {
                 scenario: "tunnel start",
-            })
+            });
+
+        if let Some(socks5_thread) = socks5_thread {
+            socks5_thread.abort();
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(transparent_thread) = transparent_thread {
+            transparent_thread.abort();
+        }
+
+        served
     }
 }
@@ -0,0 +1,134 @@
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use http::uri::Authority;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 0x01;
+
+const SUCCEEDED: u8 = 0x00;
+const GENERAL_FAILURE: u8 = 0x01;
+const COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const ADDRESS_TYPE_NOT_SUPPORTED: u8 = 0x08;
+
+/// The server side of a SOCKS5 (RFC 1928) handshake without authentication, returns where
+/// the client asks to `CONNECT`.
+///
+/// The client is told the connection succeeds right away, as what's connected depends on the
+/// protocol it speaks afterwards.
+pub(crate) async fn handshake<S>(stream: &mut S) -> io::Result<Authority>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut greeting = [0_u8; 2];
+    stream.read_exact(&mut greeting).await?;
+    if greeting[0] != VERSION {
+        return Err(io::Error::other(format!(
+            "unsupported SOCKS version {}",
+            greeting[0]
+        )));
+    }
+
+    let mut methods = vec![0_u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&NO_AUTHENTICATION) {
+        stream.write_all(&[VERSION, NO_ACCEPTABLE_METHODS]).await?;
+        return Err(io::Error::other("client requires authentication"));
+    }
+    stream.write_all(&[VERSION, NO_AUTHENTICATION]).await?;
+
+    let mut head = [0_u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != CONNECT {
+        reply(stream, COMMAND_NOT_SUPPORTED).await?;
+        return Err(io::Error::other(format!(
+            "unsupported SOCKS command {}",
+            head[1]
+        )));
+    }
+
+    let host = match head[3] {
+        0x01 => {
+            let mut ip = [0_u8; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        0x03 => {
+            let mut domain = vec![0_u8; stream.read_u8().await? as usize];
+            stream.read_exact(&mut domain).await?;
+            String::from_utf8_lossy(&domain).into_owned()
+        }
+        0x04 => {
+            let mut ip = [0_u8; 16];
+            stream.read_exact(&mut ip).await?;
+            format!("[{}]", Ipv6Addr::from(ip))
+        }
+        atyp => {
+            reply(stream, ADDRESS_TYPE_NOT_SUPPORTED).await?;
+            return Err(io::Error::other(format!(
+                "unknown SOCKS address type {atyp}"
+            )));
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    match Authority::try_from(format!("{host}:{port}").as_str()) {
+        Ok(authority) => {
+            reply(stream, SUCCEEDED).await?;
+            Ok(authority)
+        }
+        Err(e) => {
+            reply(stream, GENERAL_FAILURE).await?;
+            Err(io::Error::other(format!(
+                "invalid address {host}:{port}: {e}"
+            )))
+        }
+    }
+}
+
+/// The bound address is left empty, clients don't need it for `CONNECT`.
+async fn reply<S>(stream: &mut S, rep: u8) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&[VERSION, rep, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let mut req = vec![
+            VERSION,
+            1,
+            NO_AUTHENTICATION,
+            VERSION,
+            CONNECT,
+            0x00,
+            0x03,
+            9,
+        ];
+        req.extend_from_slice(b"www.x.com");
+        req.extend_from_slice(&443_u16.to_be_bytes());
+        client.write_all(&req).await.unwrap();
+
+        let authority = handshake(&mut server).await.unwrap();
+        assert_eq!(authority.as_str(), "www.x.com:443");
+
+        let mut replies = [0_u8; 12];
+        client.read_exact(&mut replies).await.unwrap();
+        assert_eq!(replies[..2], [VERSION, NO_AUTHENTICATION]);
+        assert_eq!(replies[2..4], [VERSION, SUCCEEDED]);
+    }
+}
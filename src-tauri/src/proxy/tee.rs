@@ -0,0 +1,142 @@
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use hyper::{body::HttpBody, Body, Response};
+use tokio::sync::mpsc::{self, error::TrySendError, Sender};
+use uuid::Uuid;
+
+use crate::{
+    events::{body::BodyRecorder, Events, ResponseCompleteEvent},
+    processors::http_processor::HttpPhase,
+};
+
+use super::decoder::decode_message;
+
+/// Chunks of the copy waiting to be recorded, the recording is cut off once it falls this far
+/// behind.
+const COPY_QUEUE: usize = 256;
+
+/// Hand the body to the client as it arrives rather than after it ends, so that event streams
+/// and large downloads aren't held back, not even by the recording. A copy is recorded on the
+/// way, decoded if the client gets it encoded, and reported with `ResponseCompleteEvent` once
+/// the body ends.
+pub(crate) fn tee_response(
+    id: Uuid,
+    res: Response<Body>,
+    transporter: Sender<Events>,
+) -> Response<Body> {
+    let (parts, mut body) = res.into_parts();
+    let mut headers = parts.headers.clone();
+    let (mut sender, tee) = Body::channel();
+    let (copy_sender, copy) = mpsc::channel::<Bytes>(COPY_QUEUE);
+    let mut copy = Body::wrap_stream(futures::stream::unfold(copy, |mut copy| async move {
+        let chunk = copy.recv().await?;
+        Some((Ok::<_, Infallible>(chunk), copy))
+    }));
+
+    let pump = tokio::spawn(async move {
+        let mut copy_sender = Some(copy_sender);
+        let mut lagged = false;
+
+        let error = loop {
+            match body.data().await {
+                Some(Ok(chunk)) => {
+                    if let Err(e) = sender.send_data(chunk.clone()).await {
+                        break Some(format!("client is gone: {e}"));
+                    }
+                    // never wait for the recording
+                    if let Some(copy) = copy_sender.as_ref() {
+                        if let Err(e) = copy.try_send(chunk) {
+                            lagged = matches!(e, TrySendError::Full(_));
+                            copy_sender = None;
+                        }
+                    }
+                }
+                Some(Err(e)) => break Some(e.to_string()),
+                None => {
+                    match body.trailers().await {
+                        Ok(Some(trailers)) => {
+                            let _ = sender.send_trailers(trailers).await;
+                        }
+                        Ok(None) => {}
+                        Err(e) => break Some(e.to_string()),
+                    }
+                    break None;
+                }
+            }
+        };
+
+        if error.is_some() {
+            // let the client know the body is incomplete instead of ending it normally
+            sender.abort();
+        }
+
+        error.or_else(|| {
+            lagged.then(|| String::from("recording fell behind, the capture is cut off"))
+        })
+    });
+
+    tokio::spawn(async move {
+        if let Err(e) = decode_message(&mut headers, &mut copy) {
+            log::warn!("record the response body of {id} as it's encoded: {e}");
+        }
+
+        let mut recorder = BodyRecorder::new(id, HttpPhase::Response);
+        let mut decode_error = None;
+        while let Some(chunk) = copy.data().await {
+            match chunk {
+                Ok(chunk) => recorder.write(&chunk).await,
+                Err(e) => {
+                    decode_error = Some(format!("decoding the body failed: {e}"));
+                    break;
+                }
+            }
+        }
+        // stop feeding the copy, so that the client isn't held back by it
+        drop(copy);
+
+        let error = pump
+            .await
+            .unwrap_or_else(|e| Some(e.to_string()))
+            .or(decode_error);
+
+        let complete = ResponseCompleteEvent::new(id, recorder.finish(&headers).await, error);
+        if let Err(e) = transporter.send(complete.into()).await {
+            log::error!("send events to client failed: {e}");
+        }
+    });
+
+    Response::from_parts(parts, tee)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::body::to_bytes;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tee_response() {
+        let (transporter, mut events) = mpsc::channel(1);
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("data: 1\n\n"), Ok("data: 2\n\n")];
+        let res = Response::new(Body::wrap_stream(futures::stream::iter(chunks)));
+
+        let id = Uuid::new_v4();
+        let res = tee_response(id, res, transporter);
+
+        assert_eq!(
+            to_bytes(res.into_body()).await.unwrap(),
+            "data: 1\n\ndata: 2\n\n"
+        );
+
+        let Some(Events::ResponseComplete(complete)) = events.recv().await else {
+            panic!("expect a complete event");
+        };
+        let complete = serde_json::to_value(complete).unwrap();
+        assert_eq!(complete["id"], id.to_string());
+        assert_eq!(complete["body"], "data: 1\n\ndata: 2\n\n");
+        assert_eq!(complete["bodyLength"], 18);
+        assert!(complete["error"].is_null());
+    }
+}
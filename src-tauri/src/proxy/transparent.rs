@@ -0,0 +1,139 @@
+use std::{
+    io, mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::fd::AsRawFd,
+};
+
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+/// `SO_ORIGINAL_DST` of IPv6 connections, which `libc` doesn't define.
+const IP6T_SO_ORIGINAL_DST: libc::c_int = 80;
+
+/// Bind the listener of connections redirected by iptables or nftables. `IP_TRANSPARENT`,
+/// which `TPROXY` requires, is only set with `CAP_NET_ADMIN`, `REDIRECT` works without it.
+pub(crate) fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let (socket, level, transparent) = match addr {
+        SocketAddr::V4(_) => (TcpSocket::new_v4()?, libc::SOL_IP, libc::IP_TRANSPARENT),
+        SocketAddr::V6(_) => (TcpSocket::new_v6()?, libc::SOL_IPV6, libc::IPV6_TRANSPARENT),
+    };
+    socket.set_reuseaddr(true)?;
+
+    let enable: libc::c_int = 1;
+    // SAFETY: the socket is open, and the option value is a `c_int` as `IP_TRANSPARENT` expects.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            transparent,
+            &enable as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        log::warn!(
+            "IP_TRANSPARENT isn't set, only REDIRECT works: {}",
+            io::Error::last_os_error()
+        );
+    }
+
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+/// Where a connection accepted on `listener_port` was going before it was redirected. `REDIRECT`
+/// keeps it in conntrack, which `SO_ORIGINAL_DST` reads, while `TPROXY` keeps it as the local
+/// address of the connection.
+pub(crate) fn original_dst(stream: &TcpStream, listener_port: u16) -> io::Result<SocketAddr> {
+    let local = stream.local_addr()?;
+
+    let dst = match local {
+        SocketAddr::V4(_) => original_dst_v4(stream),
+        SocketAddr::V6(_) => original_dst_v6(stream),
+    }
+    .unwrap_or(local);
+
+    // Connected to the listener itself rather than redirected, serving it would loop forever.
+    if dst == local && dst.port() == listener_port {
+        return Err(io::Error::other(format!(
+            "connection to {dst} isn't redirected"
+        )));
+    }
+
+    Ok(dst)
+}
+
+fn original_dst_v4(stream: &TcpStream) -> Option<SocketAddr> {
+    // SAFETY: all zeros is a valid `sockaddr_in`.
+    let mut addr: libc::sockaddr_in = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    // SAFETY: the socket is open, and `addr` and `len` describe a `sockaddr_in` buffer.
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_IP,
+            libc::SO_ORIGINAL_DST,
+            &mut addr as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    (ret == 0).then(|| {
+        SocketAddr::from((
+            Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+            u16::from_be(addr.sin_port),
+        ))
+    })
+}
+
+fn original_dst_v6(stream: &TcpStream) -> Option<SocketAddr> {
+    // SAFETY: all zeros is a valid `sockaddr_in6`.
+    let mut addr: libc::sockaddr_in6 = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+    // SAFETY: the socket is open, and `addr` and `len` describe a `sockaddr_in6` buffer.
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_IPV6,
+            IP6T_SO_ORIGINAL_DST,
+            &mut addr as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    (ret == 0).then(|| {
+        SocketAddr::from((
+            Ipv6Addr::from(addr.sin6_addr.s6_addr),
+            u16::from_be(addr.sin6_port),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_original_dst_of_direct_connections() {
+        let listener = bind(([127, 0, 0, 1], 0).into()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        assert!(original_dst(&stream, addr.port()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_original_dst_of_direct_ipv6_connections() {
+        // IPv6 may be disabled in the sandbox
+        let Ok(listener) = bind((Ipv6Addr::LOCALHOST, 0).into()) else {
+            return;
+        };
+        let addr = listener.local_addr().unwrap();
+
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        assert!(original_dst(&stream, addr.port()).is_err());
+    }
+}
@@ -1,7 +1,19 @@
-use std::{convert::Infallible, sync::Arc};
+#[cfg(target_os = "linux")]
+use std::net::SocketAddr;
+use std::{
+    convert::Infallible,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
+use arc_swap::ArcSwap;
 use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
 use http::{
+    header::{HOST, SEC_WEBSOCKET_EXTENSIONS},
     uri::{Authority, Scheme},
     Method, StatusCode, Uri,
 };
@@ -10,14 +22,22 @@ use hyper::{
     upgrade::Upgraded, Body, Client, Request, Response,
 };
 use snafu::ResultExt;
-use tauri::async_runtime::Mutex;
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite},
     net::TcpStream,
     sync::mpsc::Sender,
 };
 use tokio_rustls::TlsAcceptor;
-use tokio_tungstenite::{tungstenite, Connector, WebSocketStream};
+#[cfg(target_os = "linux")]
+use tokio_rustls::{rustls::server::Acceptor, LazyConfigAcceptor};
+use tokio_tungstenite::{
+    tungstenite::{
+        self,
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    Connector, WebSocketStream,
+};
 use uuid::Uuid;
 
 use crate::{
@@ -26,19 +46,36 @@ use crate::{
         endpoint_error::{EndpointError, HttpError, WebsocketProtocolError},
         ClientError, ServerError,
     },
-    events::{Events, RequestEvent, ResponseEvent},
+    events::{
+        body::SpooledBody, BreakpointEvent, Events, RequestEvent, ResponseEvent,
+        WebsocketCloseEvent, WebsocketDirection, WebsocketMessageEvent, WebsocketOpenEvent,
+    },
 };
 
-use super::decoder::{decode_request, decode_response};
+use super::breakpoint::{BreakpointAction, Breakpoints, BREAKPOINT_TIMEOUT};
+use super::capture::{CapturedRequests, Replay};
+use super::decoder::{decode_request, decode_response, decode_response_preserving};
 use super::rewind::Rewind;
-use crate::processors::processor;
+use super::socks;
+use super::tee::tee_response;
+#[cfg(target_os = "linux")]
+use super::transparent;
+use super::upstream::Upstreams;
+use crate::processors::{
+    http_processor::HttpPhase, matcher::RequestSnapshot, processor, processor_effect::merge_effects,
+};
 
 pub struct Tunnel<CA, C, P> {
     pub ca: Arc<CA>,
     pub client: Client<C>,
     pub websocket_connector: Option<Connector>,
     pub transporter: Sender<Events>,
-    pub processor: Arc<Mutex<P>>,
+    pub processor: Arc<ArcSwap<P>>,
+    pub breakpoints: Arc<Breakpoints>,
+    pub captured: CapturedRequests,
+    /// Responses keep their `Content-Encoding` toward the client.
+    pub preserve_encoding: bool,
+    pub upstreams: Arc<Upstreams>,
 }
 
 impl<CA, C, P> Clone for Tunnel<CA, C, P>
@@ -53,6 +90,10 @@ where
             websocket_connector: self.websocket_connector.clone(),
             transporter: self.transporter.clone(),
             processor: Arc::clone(&self.processor),
+            breakpoints: Arc::clone(&self.breakpoints),
+            captured: self.captured.clone(),
+            preserve_encoding: self.preserve_encoding,
+            upstreams: Arc::clone(&self.upstreams),
         }
     }
 }
@@ -61,7 +102,7 @@ impl<CA, C, P> Tunnel<CA, C, P>
 where
     CA: CertificateAuthority,
     C: Connect + Clone + Send + Sync + 'static,
-    P: processor::HttpProcessor + std::fmt::Debug,
+    P: processor::HttpProcessor + processor::WebsocketProcessor + std::fmt::Debug,
 {
     async fn send_event(&self, event: Events) {
         if let Err(e) = self.transporter.send(event).await {
@@ -69,77 +110,222 @@ where
         }
     }
 
-    pub(crate) async fn accept(self, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    /// Pause the exchange until the UI resumes it, or the breakpoint times out.
+    async fn pause(&self, id: Uuid, event: BreakpointEvent) -> BreakpointAction {
+        let paused = self.breakpoints.pause(id);
+        self.send_event(event.into()).await;
+        self.breakpoints.wait(id, paused).await
+    }
+
+    pub(crate) async fn accept(self, mut req: Request<Body>) -> Result<Response<Body>, Infallible> {
         // 模拟 Server, 对 Client 连接
         if req.method() == Method::CONNECT {
             Ok(self.handle_connect(req))
         } else if hyper_tungstenite::is_upgrade_request(&req) {
-            Ok(self.upgrade_websocket(req))
+            let conn_id = Uuid::new_v4();
+            log::trace!(
+                "accept websocket upgrade from client: {}, {:?}",
+                conn_id,
+                req
+            );
+
+            self.send_event(RequestEvent::new(conn_id, &mut req).await.into())
+                .await;
+
+            Ok(self.upgrade_websocket(conn_id, req).await)
         } else {
             let conn_id = Uuid::new_v4();
             log::trace!("accept request from client: {}, {:?}", conn_id, req);
 
-            let mut req = decode_request(req)
+            let decode_error = decode_request(&mut req)
+                .await
                 .context(ClientError {
-                    scenario: "decoding request body failed",
+                    scenario: "decoding request body",
                 })
-                .unwrap();
+                .err()
+                .map(|e| {
+                    log::warn!("pass the request body of {conn_id} through: {e}");
+                    e.to_string()
+                });
 
-            self.send_event(RequestEvent::new(conn_id, &mut req).await.into())
+            Ok(self.handle_request(conn_id, req, None, decode_error).await)
+        }
+    }
+
+    /// Send the request to upstream with rules and breakpoints applied, `replay_of` is set if
+    /// it's replayed from a captured request, and `decode_error` if its body can't be decoded.
+    async fn handle_request(
+        &self,
+        conn_id: Uuid,
+        mut req: Request<Body>,
+        replay_of: Option<Uuid>,
+        decode_error: Option<String>,
+    ) -> Response<Body> {
+        // read once, so that nobody later on fails on it, large bodies are spooled to disk
+        let body = match SpooledBody::read(req.body_mut(), conn_id, HttpPhase::Request).await {
+            Ok(body) => body,
+            Err(e) => {
+                log::error!("read the request body of {conn_id} failed: {e}");
+                return bad_request();
+            }
+        };
+        let (spooled, original_body) = body.to_tracked_body();
+        *req.body_mut() = spooled;
+
+        self.captured
+            .insert(conn_id, RequestSnapshot::with_body(&req, body.clone()))
+            .await;
+
+        self.send_event(
+            RequestEvent::spooled(conn_id, &req, &body)
+                .replay_of(replay_of)
+                .decode_error(decode_error)
+                .into(),
+        )
+        .await;
+
+        // Both phases see the same rules even if they are updated in between.
+        let processor = self.processor.load_full();
+        let req_or_res = processor.process_request(req).await;
+
+        let mut req = match req_or_res.res {
+            Some(mut res) => {
+                self.send_event(
+                    ResponseEvent::new(
+                        conn_id,
+                        req_or_res.req.uri().to_owned(),
+                        &mut res,
+                        req_or_res.processor_effects,
+                    )
+                    .await
+                    .replay_of(replay_of)
+                    .into(),
+                )
                 .await;
+                return res;
+            }
+            None => req_or_res.req,
+        };
 
-            let processor = self.processor.lock().await;
-            let req_or_res = processor.process_request(req).await;
-            drop(processor); // release mutex lock
+        if processor.request_breakpoint(&mut req).await {
+            let event = BreakpointEvent::request(conn_id, &mut req, BREAKPOINT_TIMEOUT).await;
 
-            let req = match req_or_res.res {
-                Some(mut res) => {
+            match self.pause(conn_id, event).await {
+                BreakpointAction::Continue(edit) => req = edit.apply_to_request(req),
+                BreakpointAction::Abort => {
+                    let mut res = aborted();
                     self.send_event(
                         ResponseEvent::new(
                             conn_id,
-                            req_or_res.req.uri().to_owned(),
+                            req.uri().to_owned(),
                             &mut res,
                             req_or_res.processor_effects,
                         )
                         .await
+                        .replay_of(replay_of)
                         .into(),
                     )
                     .await;
-                    return Ok(res);
+                    return res;
                 }
-                None => req_or_res.req,
-            };
+            }
+        }
+
+        // the body is read again only if it's replaced, e.g. by the rules or a breakpoint
+        let snapshot = if original_body.strong_count() > 0 {
+            RequestSnapshot::with_body(&req, body)
+        } else {
+            RequestSnapshot::capture(&mut req).await
+        };
+        let req_uri = snapshot.uri.clone();
 
-            let req_uri = req.uri().clone();
+        let mut req = normalize_request(req);
+        // kept out of the captures
+        if let Some(authorization) = self.upstreams.proxy_authorization(req.uri()) {
+            req.headers_mut()
+                .insert(hyper::header::PROXY_AUTHORIZATION, authorization);
+        }
 
-            log::trace!("send network request: {}, {:?}", conn_id, req);
-            let res = self
-                .client
-                .request(normalize_request(req))
-                .await
-                .context(HttpError {})
-                .context(ServerError {
-                    scenario: "sending request",
-                });
-            log::trace!("send network request done: {}, {:?}", conn_id, res);
+        log::trace!("send network request: {}, {:?}", conn_id, req);
+        let res = self
+            .client
+            .request(req)
+            .await
+            .context(HttpError {})
+            .context(ServerError {
+                scenario: "sending request",
+            });
+        log::trace!("send network request done: {}, {:?}", conn_id, res);
+
+        let mut decode_error = None;
+        let mut preserved = None;
+        let (mut res, processor_effects) = match res {
+            Ok(mut res) => {
+                let decoded = if self.preserve_encoding {
+                    decode_response_preserving(&mut res).map(|encoding| preserved = encoding)
+                } else {
+                    decode_response(&mut res).await
+                };
+
+                if let Err(e) = decoded.context(ServerError {
+                    scenario: "decoding response body",
+                }) {
+                    log::warn!("pass the response body of {conn_id} through: {e}");
+                    decode_error = Some(e.to_string());
+                }
+
+                let processed = processor.process_response(&snapshot, res).await;
+                (
+                    processed.res,
+                    merge_effects(req_or_res.processor_effects, processed.processor_effects),
+                )
+            }
+            Err(e) => (
+                processor.process_error(e).await,
+                req_or_res.processor_effects,
+            ),
+        };
 
-            let processor = self.processor.lock().await;
-            let mut res = match res {
-                Ok(res) => res,
-                Err(e) => processor.process_error(e).await,
+        if processor.response_breakpoint(&snapshot).await {
+            let event =
+                BreakpointEvent::response(conn_id, req_uri.clone(), &mut res, BREAKPOINT_TIMEOUT)
+                    .await;
+
+            res = match self.pause(conn_id, event).await {
+                BreakpointAction::Continue(edit) => edit.apply_to_response(res),
+                BreakpointAction::Abort => {
+                    preserved = None;
+                    aborted()
+                }
             };
-            drop(processor);
+        }
 
-            res = decode_response(res).unwrap();
+        if let Some(preserved) = preserved {
+            res = preserved.restore(res).await;
+        }
 
-            self.send_event(
-                ResponseEvent::new(conn_id, req_uri, &mut res, req_or_res.processor_effects)
-                    .await
-                    .into(),
-            )
+        // the head goes first, the body is reported as it completes
+        self.send_event(
+            ResponseEvent::streaming(conn_id, req_uri, &res, processor_effects)
+                .replay_of(replay_of)
+                .decode_error(decode_error)
+                .into(),
+        )
+        .await;
+
+        tee_response(conn_id, res, self.transporter.clone())
+    }
+
+    pub(crate) async fn replay(self, replay: Replay) {
+        log::trace!("replay request: {}, {:?}", replay.id, replay.req);
+
+        let res = self
+            .handle_request(replay.id, replay.req, replay.replay_of, None)
             .await;
 
-            Ok(res)
+        // nobody reads replayed responses, drain it for the capture
+        if let Err(e) = hyper::body::to_bytes(res.into_body()).await {
+            log::error!("read replayed response failed: {e}");
         }
     }
 
@@ -148,83 +334,7 @@ where
             Some(authority) => {
                 let fut = async move {
                     match hyper::upgrade::on(&mut req).await {
-                        Ok(mut upgraded) => {
-                            let mut buffer = [0_u8; 4];
-                            let bytes_read = match upgraded.read(&mut buffer).await {
-                                Ok(bytes) => bytes,
-                                Err(e) => {
-                                    log::error!("Failed to read from upgraded connections: {e}");
-                                    return;
-                                }
-                            };
-
-                            let mut upgraded = Rewind::new_buffered(
-                                upgraded,
-                                Bytes::copy_from_slice(buffer[..bytes_read].as_ref()),
-                            );
-
-                            if &buffer == b"GET " {
-                                if let Err(e) =
-                                    self.serve_stream(upgraded, Scheme::HTTP, authority).await
-                                {
-                                    log::error!("HTTP connect error, {e}");
-                                }
-                            }
-                            // Tls
-
-                            // Content type: Handshake (22)
-                            // TLS version: 1.x (3, _)
-                            else if buffer[..2] == [22, 3] {
-                                let server_config = self.ca.gen_server_config(&authority).await;
-
-                                log::debug!(
-                                    "TLS connections established with client and tunnel server"
-                                );
-
-                                // stream for proxy to server
-                                let stream =
-                                    match TlsAcceptor::from(server_config).accept(upgraded).await {
-                                        Ok(stream) => stream,
-                                        Err(e) => {
-                                            log::error!("Failed to establish TLS connection: {e}");
-                                            return;
-                                        }
-                                    };
-
-                                if let Err(e) =
-                                    self.serve_stream(stream, Scheme::HTTPS, authority).await
-                                {
-                                    if !e.to_string().starts_with("error shutting down connection")
-                                    {
-                                        log::error!("HTTPS connect error: {e}");
-                                    }
-                                }
-                            } else {
-                                log::debug!(
-                                    "Unknown protocol, read '{:02X?}' from upgraded connection",
-                                    &buffer[..bytes_read]
-                                );
-
-                                let mut server = match TcpStream::connect(authority.as_ref()).await
-                                {
-                                    Ok(server) => server,
-                                    Err(e) => {
-                                        log::error!("Failed to connect to {authority}: {e}");
-                                        return;
-                                    }
-                                };
-
-                                if let Err(e) =
-                                    tokio::io::copy_bidirectional(&mut upgraded, &mut server).await
-                                {
-                                    log::error!(
-                                        "Failed to tunnel unknown protocol to {}: {}",
-                                        authority,
-                                        e
-                                    );
-                                }
-                            }
-                        }
+                        Ok(upgraded) => self.serve_tunnel(upgraded, authority).await,
                         Err(e) => {
                             log::error!("Upgrade error: {e}");
                         }
@@ -238,7 +348,156 @@ where
         }
     }
 
-    fn upgrade_websocket(self, req: Request<Body>) -> Response<Body> {
+    /// Serve a SOCKS5 client, whose `CONNECT` goes on the same way as an HTTP `CONNECT`.
+    pub(crate) async fn accept_socks5(self, mut stream: TcpStream) {
+        match socks::handshake(&mut stream).await {
+            Ok(authority) => self.serve_tunnel(stream, authority).await,
+            Err(e) => log::error!("SOCKS5 handshake error: {e}"),
+        }
+    }
+
+    /// Serve a connection redirected to the transparent listener on `listener_port`.
+    #[cfg(target_os = "linux")]
+    pub(crate) async fn accept_transparent(self, stream: TcpStream, listener_port: u16) {
+        match transparent::original_dst(&stream, listener_port) {
+            Ok(dst) => self.serve_redirected(stream, dst).await,
+            Err(e) => log::error!("Failed to recover the original destination: {e}"),
+        }
+    }
+
+    /// Serve a connection which was going to `dst`. Plain HTTP requests go to their `Host`, the
+    /// host of TLS connections is taken from the SNI, and others are tunneled to `dst`.
+    #[cfg(target_os = "linux")]
+    async fn serve_redirected<S>(self, stream: S, dst: SocketAddr)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let dst_authority = match Authority::try_from(dst.to_string()) {
+            Ok(authority) => authority,
+            Err(e) => {
+                log::error!("Failed to parse the original destination {dst}: {e}");
+                return;
+            }
+        };
+
+        let (stream, protocol) = match sniff(stream).await {
+            Ok(sniffed) => sniffed,
+            Err(e) => {
+                log::error!("Failed to read from redirected connections: {e}");
+                return;
+            }
+        };
+
+        match protocol {
+            Protocol::Http => {
+                let destination = Destination::Host(dst_authority);
+                if let Err(e) = self.serve_stream(stream, Scheme::HTTP, destination).await {
+                    log::error!("HTTP connect error, {e}");
+                }
+            }
+            Protocol::Tls => {
+                let handshake = match LazyConfigAcceptor::new(Acceptor::default(), stream).await {
+                    Ok(handshake) => handshake,
+                    Err(e) => {
+                        log::error!("Failed to read TLS client hello: {e}");
+                        return;
+                    }
+                };
+
+                let authority = handshake
+                    .client_hello()
+                    .server_name()
+                    .and_then(|name| Authority::try_from(format!("{name}:{}", dst.port())).ok())
+                    .unwrap_or(dst_authority);
+
+                let server_config = self.ca.gen_server_config(&authority).await;
+                let stream = match handshake.into_stream(server_config).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::error!("Failed to establish TLS connection: {e}");
+                        return;
+                    }
+                };
+
+                let destination = Destination::Tunnel(authority);
+                if let Err(e) = self.serve_stream(stream, Scheme::HTTPS, destination).await {
+                    if !e.to_string().starts_with("error shutting down connection") {
+                        log::error!("HTTPS connect error: {e}");
+                    }
+                }
+            }
+            Protocol::Unknown => self.pass_through(stream, dst_authority).await,
+        }
+    }
+
+    /// Serve a tunnel to `authority` by the protocol the client speaks, HTTP and HTTPS are
+    /// served as if the proxy is the server, others are passed through.
+    async fn serve_tunnel<S>(self, stream: S, authority: Authority)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (stream, protocol) = match sniff(stream).await {
+            Ok(sniffed) => sniffed,
+            Err(e) => {
+                log::error!("Failed to read from upgraded connections: {e}");
+                return;
+            }
+        };
+
+        match protocol {
+            Protocol::Http => {
+                let destination = Destination::Tunnel(authority);
+                if let Err(e) = self.serve_stream(stream, Scheme::HTTP, destination).await {
+                    log::error!("HTTP connect error, {e}");
+                }
+            }
+            Protocol::Tls => {
+                let server_config = self.ca.gen_server_config(&authority).await;
+
+                log::debug!("TLS connections established with client and tunnel server");
+
+                // stream for proxy to server
+                let stream = match TlsAcceptor::from(server_config).accept(stream).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::error!("Failed to establish TLS connection: {e}");
+                        return;
+                    }
+                };
+
+                let destination = Destination::Tunnel(authority);
+                if let Err(e) = self.serve_stream(stream, Scheme::HTTPS, destination).await {
+                    if !e.to_string().starts_with("error shutting down connection") {
+                        log::error!("HTTPS connect error: {e}");
+                    }
+                }
+            }
+            Protocol::Unknown => self.pass_through(stream, authority).await,
+        }
+    }
+
+    /// Tunnel a protocol which isn't understood to `authority` as it is.
+    async fn pass_through<S>(self, mut stream: S, authority: Authority)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        log::debug!("Unknown protocol, pass the connection to {authority} through");
+
+        let port = authority.port_u16().unwrap_or(443);
+        let mut server = match self.upstreams.connect(authority.host(), port).await {
+            Ok(server) => server,
+            Err(e) => {
+                log::error!("Failed to connect to {authority}: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = tokio::io::copy_bidirectional(&mut stream, &mut server).await {
+            log::error!("Failed to tunnel unknown protocol to {}: {}", authority, e);
+        }
+    }
+
+    async fn upgrade_websocket(self, conn_id: Uuid, req: Request<Body>) -> Response<Body> {
         let mut req = {
             let (mut parts, _) = req.into_parts();
 
@@ -265,11 +524,18 @@ where
             .context(ClientError {
                 scenario: "upgrade tungstenite",
             }) {
-            Ok((res, websocket)) => {
+            Ok((mut res, websocket)) => {
+                self.send_event(
+                    ResponseEvent::new(conn_id, req.uri().to_owned(), &mut res, None)
+                        .await
+                        .into(),
+                )
+                .await;
+
                 let fut = async move {
                     match websocket.await {
                         Ok(ws) => {
-                            if let Err(e) = self.handle_websocket(ws, req).await {
+                            if let Err(e) = self.handle_websocket(conn_id, ws, req).await {
                                 log::error!("Failed to handle websocket: {e}");
                             }
                         }
@@ -288,9 +554,87 @@ where
 
     async fn handle_websocket(
         self,
-        _server_socket: WebSocketStream<Upgraded>,
-        _req: Request<()>,
+        conn_id: Uuid,
+        mut server_socket: WebSocketStream<Upgraded>,
+        mut req: Request<()>,
     ) -> Result<(), tungstenite::Error> {
+        // tungstenite can't decode compressed frames, so keep upstream from negotiating
+        // `permessage-deflate` with a handshake the client never agreed to.
+        req.headers_mut().remove(SEC_WEBSOCKET_EXTENSIONS);
+
+        let uri = req.uri().clone();
+
+        let connected = async {
+            let host = uri.host().unwrap_or_default();
+            let port = uri
+                .port_u16()
+                .unwrap_or(if uri.scheme_str() == Some("wss") {
+                    443
+                } else {
+                    80
+                });
+            let stream = self.upstreams.connect(host, port).await?;
+
+            tokio_tungstenite::client_async_tls_with_config(
+                req,
+                stream,
+                None,
+                self.websocket_connector.clone(),
+            )
+            .await
+        };
+
+        let client_socket = match connected.await {
+            Ok((client_socket, res)) => {
+                self.send_event(WebsocketOpenEvent::new(conn_id, uri, &res).into())
+                    .await;
+                client_socket
+            }
+            Err(e) => {
+                let code = CloseCode::Error;
+                let reason = format!("Failed to connect to upstream: {e}");
+
+                self.send_event(
+                    WebsocketCloseEvent::new(conn_id, None, Some(code.into()), Some(reason)).into(),
+                )
+                .await;
+
+                let _ = server_socket
+                    .close(Some(CloseFrame {
+                        code,
+                        reason: "Failed to connect to upstream".into(),
+                    }))
+                    .await;
+                return Err(e);
+            }
+        };
+
+        let (server_sink, server_stream) = server_socket.split();
+        let (client_sink, client_stream) = client_socket.split();
+
+        let closed = Arc::new(AtomicBool::new(false));
+
+        tokio::spawn(
+            WebsocketRelay {
+                id: conn_id,
+                direction: WebsocketDirection::ServerToClient,
+                processor: Arc::clone(&self.processor),
+                transporter: self.transporter.clone(),
+                closed: Arc::clone(&closed),
+            }
+            .forward(client_stream, server_sink),
+        );
+        tokio::spawn(
+            WebsocketRelay {
+                id: conn_id,
+                direction: WebsocketDirection::ClientToServer,
+                processor: Arc::clone(&self.processor),
+                transporter: self.transporter.clone(),
+                closed,
+            }
+            .forward(server_stream, client_sink),
+        );
+
         Ok(())
     }
 
@@ -298,7 +642,7 @@ where
         self,
         stream: S,
         scheme: Scheme,
-        authority: Authority,
+        destination: Destination,
     ) -> Result<(), EndpointError>
     where
         S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
@@ -308,14 +652,22 @@ where
             {
                 let (mut parts, body) = req.into_parts();
 
-                // Use host part only.(Remove port 443)
-                let host = authority.host();
+                let authority = match &destination {
+                    // Use host part only.(Remove port 443)
+                    Destination::Tunnel(authority) => {
+                        Authority::try_from(authority.host()).expect("Failed to parse authority")
+                    }
+                    Destination::Host(dst) => parts
+                        .headers
+                        .get(HOST)
+                        .and_then(|host| Authority::try_from(host.as_bytes()).ok())
+                        .unwrap_or_else(|| dst.clone()),
+                };
 
                 parts.uri = {
                     let mut parts = parts.uri.into_parts();
                     parts.scheme = Some(scheme.clone());
-                    parts.authority =
-                        Some(Authority::try_from(host).expect("Failed to parse authority"));
+                    parts.authority = Some(authority);
                     Uri::from_parts(parts).expect("Failed to build URI")
                 };
 
@@ -333,6 +685,160 @@ where
     }
 }
 
+/// Where the requests of a served stream go.
+#[derive(Debug, Clone)]
+enum Destination {
+    /// The host the stream is tunneled to.
+    Tunnel(Authority),
+    /// The `Host` of each request, or the original destination of a redirected connection if
+    /// there's none.
+    Host(Authority),
+}
+
+/// What a client speaks, sniffed from the first bytes it sends.
+#[derive(Debug, PartialEq)]
+enum Protocol {
+    Http,
+    /// Content type: Handshake (22), TLS version: 1.x (3, _)
+    Tls,
+    Unknown,
+}
+
+const TLS_HANDSHAKE: &[u8] = &[22, 3];
+
+/// Plain HTTP requests start with one of them, `CONNECT` isn't expected in a tunnel.
+const HTTP_METHODS: &[&[u8]] = &[
+    b"GET ",
+    b"HEAD ",
+    b"POST ",
+    b"PUT ",
+    b"DELETE ",
+    b"OPTIONS ",
+    b"TRACE ",
+    b"PATCH ",
+];
+
+/// Read as many bytes as it takes to tell the protocol, they are put back into the stream.
+async fn sniff<S>(mut stream: S) -> io::Result<(Rewind<S>, Protocol)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = [0_u8; 8];
+    let mut len = 0;
+
+    let protocol = loop {
+        let read = &buffer[..len];
+        if read.starts_with(TLS_HANDSHAKE) {
+            break Protocol::Tls;
+        }
+        if HTTP_METHODS.iter().any(|method| read.starts_with(method)) {
+            break Protocol::Http;
+        }
+
+        let undecided = TLS_HANDSHAKE.starts_with(read)
+            || HTTP_METHODS.iter().any(|method| method.starts_with(read));
+        if !undecided || len == buffer.len() {
+            break Protocol::Unknown;
+        }
+
+        // a read may return fewer bytes than the client has sent
+        match stream.read(&mut buffer[len..]).await? {
+            0 => break Protocol::Unknown,
+            read => len += read,
+        }
+    };
+
+    let stream = Rewind::new_buffered(stream, Bytes::copy_from_slice(&buffer[..len]));
+    Ok((stream, protocol))
+}
+
+/// Pumps websocket messages of one direction, both directions of a connection share `closed`
+/// so that only one close event is reported.
+struct WebsocketRelay<P> {
+    id: Uuid,
+    direction: WebsocketDirection,
+    processor: Arc<ArcSwap<P>>,
+    transporter: Sender<Events>,
+    closed: Arc<AtomicBool>,
+}
+
+impl<P> WebsocketRelay<P>
+where
+    P: processor::WebsocketProcessor,
+{
+    async fn send_event(&self, event: Events) {
+        if let Err(e) = self.transporter.send(event).await {
+            log::error!("send events to client failed: {e}");
+        }
+    }
+
+    async fn report_close(&self, code: Option<u16>, reason: Option<String>) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        self.send_event(
+            WebsocketCloseEvent::new(self.id, Some(self.direction), code, reason).into(),
+        )
+        .await;
+    }
+
+    async fn forward<S, K>(self, mut stream: S, mut sink: K)
+    where
+        S: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+        K: Sink<Message, Error = tungstenite::Error> + Unpin,
+    {
+        while let Some(message) = stream.next().await {
+            match message {
+                Ok(message) => {
+                    let message = self.processor.load_full().process_message(message).await;
+
+                    let Some(message) = message else {
+                        continue;
+                    };
+
+                    self.send_event(
+                        WebsocketMessageEvent::new(self.id, self.direction, &message).into(),
+                    )
+                    .await;
+
+                    if let Message::Close(ref frame) = message {
+                        self.report_close(
+                            frame.as_ref().map(|frame| frame.code.into()),
+                            frame.as_ref().map(|frame| frame.reason.to_string()),
+                        )
+                        .await;
+                    }
+
+                    match sink.send(message).await {
+                        Ok(_) => {}
+                        Err(tungstenite::Error::ConnectionClosed) => break,
+                        Err(e) => {
+                            log::error!("Websocket send error: {e}");
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("Websocket message error: {e}");
+
+                    self.report_close(None, Some(e.to_string())).await;
+
+                    if let Err(e) = sink.send(Message::Close(None)).await {
+                        if !matches!(e, tungstenite::Error::ConnectionClosed) {
+                            log::error!("Websocket close error: {e}");
+                        }
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        self.report_close(None, None).await;
+    }
+}
+
 fn normalize_request<T>(mut req: Request<T>) -> Request<T> {
     // Hyper will automatically add a Host header if needed.
     req.headers_mut().remove(hyper::header::HOST);
@@ -348,9 +854,115 @@ fn normalize_request<T>(mut req: Request<T>) -> Request<T> {
     req
 }
 
+/// Answers exchanges aborted at breakpoints.
+fn aborted() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::empty())
+        .expect("Failed to build response")
+}
+
 fn bad_request() -> Response<Body> {
     Response::builder()
         .status(StatusCode::BAD_REQUEST)
         .body(Body::empty())
         .expect("Failed to build response")
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use async_trait::async_trait;
+    use hyper::client::HttpConnector;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::TcpListener,
+        sync::mpsc::{self, Receiver},
+    };
+
+    use super::*;
+    use crate::ca::Ssl;
+
+    #[derive(Debug, Clone)]
+    struct NoRules;
+
+    #[async_trait]
+    impl processor::HttpProcessor for NoRules {}
+
+    #[async_trait]
+    impl processor::WebsocketProcessor for NoRules {}
+
+    fn tunnel() -> (Tunnel<Ssl, HttpConnector, NoRules>, Receiver<Events>) {
+        let (transporter, events) = mpsc::channel(16);
+        let tunnel = Tunnel {
+            ca: Arc::new(Ssl::default()),
+            client: Client::builder().build(HttpConnector::new()),
+            websocket_connector: None,
+            transporter,
+            processor: Arc::new(ArcSwap::from_pointee(NoRules)),
+            breakpoints: Arc::new(Breakpoints::default()),
+            captured: CapturedRequests::default(),
+            preserve_encoding: false,
+            upstreams: Arc::new(Upstreams::default()),
+        };
+        (tunnel, events)
+    }
+
+    #[tokio::test]
+    async fn test_capture_redirected_http() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut buffer = [0_u8; 1024];
+            let _ = stream.read(&mut buffer).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+
+        // the original destination is unreachable, the request goes to its `Host`
+        let (tunnel, mut events) = tunnel();
+        let (mut client, server) = tokio::io::duplex(4096);
+        let dst = SocketAddr::from(([127, 0, 0, 1], 1));
+        tokio::spawn(tunnel.serve_redirected(server, dst));
+
+        let request =
+            format!("GET /a HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nConnection: close\r\n\r\n");
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("ok"), "{response}");
+
+        let request = loop {
+            match events.recv().await.unwrap() {
+                Events::NewRequest(request) => break serde_json::to_value(request).unwrap(),
+                _ => continue,
+            }
+        };
+        assert_eq!(request["uri"], format!("http://127.0.0.1:{port}/a"));
+    }
+
+    #[tokio::test]
+    async fn test_sniff_protocol() {
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"PO").await.unwrap();
+        let sniffed = tokio::spawn(sniff(server));
+        client.write_all(b"ST / HTTP/1.1\r\n").await.unwrap();
+        let (mut stream, protocol) = sniffed.await.unwrap().unwrap();
+        assert_eq!(protocol, Protocol::Http);
+        let mut buffer = [0_u8; 4];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"POST");
+
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(&[22, 3, 1]).await.unwrap();
+        assert_eq!(sniff(server).await.unwrap().1, Protocol::Tls);
+
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"SSH-2.0").await.unwrap();
+        assert_eq!(sniff(server).await.unwrap().1, Protocol::Unknown);
+    }
+}
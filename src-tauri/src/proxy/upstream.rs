@@ -0,0 +1,617 @@
+use std::{
+    future::Future,
+    io,
+    net::IpAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{uri::Scheme, HeaderValue, Uri};
+use hyper::{
+    client::connect::{Connected, Connection},
+    service::Service,
+};
+use hyper_rustls::ConfigBuilderExt;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{ClientConfig, ServerName},
+    TlsConnector,
+};
+
+use crate::app_conf::UpstreamRule;
+
+#[derive(Debug, Clone, PartialEq)]
+enum UpstreamProxy {
+    Direct,
+    /// Tunnels with `CONNECT`, over TLS if `tls` is set. Plain HTTP requests are sent to it in
+    /// the absolute form instead.
+    Http {
+        tls: bool,
+        host: String,
+        port: u16,
+        authorization: Option<String>,
+    },
+    Socks5 {
+        host: String,
+        port: u16,
+        credentials: Option<(String, String)>,
+    },
+}
+
+impl UpstreamProxy {
+    /// `direct`, `http://[user:pass@]host[:port]`, `https://...` or `socks5://...`
+    fn parse(proxy: &str) -> Result<Self, String> {
+        if proxy == "direct" {
+            return Ok(Self::Direct);
+        }
+
+        let uri: Uri = proxy
+            .parse()
+            .map_err(|e| format!("invalid upstream proxy `{proxy}`: {e}"))?;
+        let authority = uri
+            .authority()
+            .ok_or_else(|| format!("upstream proxy `{proxy}` has no host"))?;
+
+        let userinfo = authority.as_str().rsplit_once('@').map(|(userinfo, _)| {
+            let (user, pass) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+            (
+                percent_decode_str(user).decode_utf8_lossy().into_owned(),
+                percent_decode_str(pass).decode_utf8_lossy().into_owned(),
+            )
+        });
+        let host = authority.host().to_string();
+
+        match uri.scheme_str() {
+            Some(scheme @ ("http" | "https")) => Ok(Self::Http {
+                tls: scheme == "https",
+                host,
+                port: authority
+                    .port_u16()
+                    .unwrap_or(if scheme == "https" { 443 } else { 80 }),
+                authorization: userinfo.map(|(user, pass)| BASE64.encode(format!("{user}:{pass}"))),
+            }),
+            Some("socks5") => Ok(Self::Socks5 {
+                host,
+                port: authority.port_u16().unwrap_or(1080),
+                credentials: userinfo,
+            }),
+            _ => Err(format!(
+                "upstream proxy `{proxy}` should be direct, or of http, https or socks5"
+            )),
+        }
+    }
+}
+
+/// Picks the way to connect to upstream by host. Rules are tried in order, hosts matching
+/// none of them are connected directly.
+#[derive(Debug)]
+pub(crate) struct Upstreams {
+    rules: Vec<(Regex, UpstreamProxy)>,
+    tls: Arc<ClientConfig>,
+}
+
+impl Default for Upstreams {
+    fn default() -> Self {
+        Self::new(&[]).expect("no rules to parse")
+    }
+}
+
+impl Upstreams {
+    pub fn new(rules: &[UpstreamRule]) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                // the pattern matches the whole host, `corp.com` shouldn't match `notcorp.com`
+                let host = Regex::new(&format!("^(?:{})$", rule.host))
+                    .map_err(|e| format!("invalid host pattern `{}`: {e}", rule.host))?;
+                Ok((host, UpstreamProxy::parse(&rule.proxy)?))
+            })
+            .collect::<Result<_, String>>()?;
+
+        let tls = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_webpki_roots()
+            .with_no_client_auth();
+
+        Ok(Self {
+            rules,
+            tls: Arc::new(tls),
+        })
+    }
+
+    fn select(&self, host: &str) -> &UpstreamProxy {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(host))
+            .map(|(_, proxy)| proxy)
+            .unwrap_or(&UpstreamProxy::Direct)
+    }
+
+    /// Open a stream to `host:port`, through the upstream proxy if there is one for the host.
+    pub async fn connect(&self, host: &str, port: u16) -> io::Result<UpstreamStream> {
+        // hosts of ipv6 uris are bracketed
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        match self.select(host) {
+            UpstreamProxy::Direct => {
+                let stream = TcpStream::connect((host, port)).await?;
+                stream.set_nodelay(true)?;
+                Ok(UpstreamStream::new(stream))
+            }
+            UpstreamProxy::Http {
+                tls,
+                host: proxy_host,
+                port: proxy_port,
+                authorization,
+            } => {
+                let mut stream = self
+                    .connect_http_proxy(*tls, proxy_host, *proxy_port)
+                    .await?;
+                http_connect(&mut stream, host, port, authorization.as_deref()).await?;
+                Ok(stream)
+            }
+            UpstreamProxy::Socks5 {
+                host: proxy_host,
+                port: proxy_port,
+                credentials,
+            } => {
+                let mut stream = TcpStream::connect((proxy_host.as_str(), *proxy_port)).await?;
+                stream.set_nodelay(true)?;
+                socks5_connect(&mut stream, host, port, credentials.as_ref()).await?;
+                Ok(UpstreamStream::new(stream))
+            }
+        }
+    }
+
+    /// Open a stream for the requests to `uri`. Plain HTTP requests go to HTTP proxies as they
+    /// are, rather than through a tunnel.
+    async fn connect_uri(&self, uri: &Uri) -> io::Result<UpstreamStream> {
+        let host = uri
+            .host()
+            .ok_or_else(|| io::Error::other(format!("{uri} has no host")))?;
+        let https = uri.scheme() == Some(&Scheme::HTTPS);
+        let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+        match self.select(host.trim_start_matches('[').trim_end_matches(']')) {
+            UpstreamProxy::Http {
+                tls,
+                host: proxy_host,
+                port: proxy_port,
+                ..
+            } if !https => {
+                let mut stream = self
+                    .connect_http_proxy(*tls, proxy_host, *proxy_port)
+                    .await?;
+                stream.proxied = true;
+                Ok(stream)
+            }
+            _ => self.connect(host, port).await,
+        }
+    }
+
+    async fn connect_http_proxy(
+        &self,
+        tls: bool,
+        proxy_host: &str,
+        proxy_port: u16,
+    ) -> io::Result<UpstreamStream> {
+        let stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+        stream.set_nodelay(true)?;
+
+        if !tls {
+            return Ok(UpstreamStream::new(stream));
+        }
+
+        let server_name = ServerName::try_from(proxy_host)
+            .map_err(|_| io::Error::other(format!("invalid host `{proxy_host}`")))?;
+        let stream = TlsConnector::from(Arc::clone(&self.tls))
+            .connect(server_name, stream)
+            .await?;
+        Ok(UpstreamStream::new(stream))
+    }
+
+    /// `Proxy-Authorization` of the plain HTTP requests to `uri`, which go to an HTTP proxy as
+    /// they are.
+    pub fn proxy_authorization(&self, uri: &Uri) -> Option<HeaderValue> {
+        if uri.scheme() != Some(&Scheme::HTTP) {
+            return None;
+        }
+
+        let host = uri.host()?.trim_start_matches('[').trim_end_matches(']');
+        match self.select(host) {
+            UpstreamProxy::Http {
+                authorization: Some(authorization),
+                ..
+            } => HeaderValue::from_str(&format!("Basic {authorization}")).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Ask an HTTP proxy for a tunnel to `host:port`.
+async fn http_connect<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    authorization: Option<&str>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let target = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => format!("[{host}]:{port}"),
+        _ => format!("{host}:{port}"),
+    };
+
+    let mut req = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(authorization) = authorization {
+        req.push_str(&format!("Proxy-Authorization: Basic {authorization}\r\n"));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await?;
+
+    // read byte by byte, so that nothing after the head is taken from the tunnel
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8 * 1024 {
+            return Err(io::Error::other(
+                "response head of the upstream proxy is too large",
+            ));
+        }
+        head.push(stream.read_u8().await?);
+    }
+
+    let status_line = String::from_utf8_lossy(&head);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "upstream proxy refused to connect {target}: {status_line}"
+        ))),
+    }
+}
+
+/// Ask a SOCKS5 proxy (RFC 1928) to connect `host:port`, with the username/password
+/// authentication (RFC 1929) if there are credentials.
+async fn socks5_connect<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    credentials: Option<&(String, String)>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let methods: &[u8] = match credentials {
+        Some(_) => &[0x00, 0x02],
+        None => &[0x00],
+    };
+    stream.write_all(&[0x05, methods.len() as u8]).await?;
+    stream.write_all(methods).await?;
+
+    let mut reply = [0_u8; 2];
+    stream.read_exact(&mut reply).await?;
+    match (reply, credentials) {
+        ([0x05, 0x00], _) => {}
+        ([0x05, 0x02], Some((user, pass))) => {
+            if user.len() > 255 || pass.len() > 255 {
+                return Err(io::Error::other("SOCKS5 credentials are too long"));
+            }
+
+            let mut auth = vec![0x01, user.len() as u8];
+            auth.extend_from_slice(user.as_bytes());
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass.as_bytes());
+            stream.write_all(&auth).await?;
+
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0x00 {
+                return Err(io::Error::other("SOCKS5 authentication failed"));
+            }
+        }
+        _ => {
+            return Err(io::Error::other(
+                "no acceptable SOCKS5 authentication method",
+            ))
+        }
+    }
+
+    let mut req = vec![0x05, 0x01, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            req.push(0x01);
+            req.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            req.push(0x04);
+            req.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(io::Error::other(format!("host `{host}` is too long")));
+            }
+            req.push(0x03);
+            req.push(host.len() as u8);
+            req.extend_from_slice(host.as_bytes());
+        }
+    }
+    req.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&req).await?;
+
+    let mut reply = [0_u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0x00 {
+        return Err(io::Error::other(format!(
+            "SOCKS5 proxy refused to connect {host}:{port}, reply {}",
+            reply[1]
+        )));
+    }
+
+    // the bound address, which isn't needed
+    let addr_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await? as usize,
+        atyp => {
+            return Err(io::Error::other(format!(
+                "unknown SOCKS5 address type {atyp}"
+            )))
+        }
+    };
+    let mut bound = vec![0_u8; addr_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// A stream to upstream, either direct or tunneled through a proxy.
+pub(crate) struct UpstreamStream {
+    io: Box<dyn Io>,
+    /// Connected to an HTTP proxy, which takes requests in the absolute form.
+    proxied: bool,
+}
+
+impl UpstreamStream {
+    fn new<T: AsyncRead + AsyncWrite + Send + Unpin + 'static>(stream: T) -> Self {
+        Self {
+            io: Box::new(stream),
+            proxied: false,
+        }
+    }
+}
+
+impl AsyncRead for UpstreamStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UpstreamStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.io).poll_shutdown(cx)
+    }
+}
+
+impl Connection for UpstreamStream {
+    fn connected(&self) -> Connected {
+        // requests are sent as if the connection is direct, unless it's to an HTTP proxy
+        Connected::new().proxy(self.proxied)
+    }
+}
+
+/// Connector of the proxy client, which goes through `Upstreams`.
+#[derive(Debug, Clone)]
+pub(crate) struct UpstreamConnector {
+    upstreams: Arc<Upstreams>,
+}
+
+impl UpstreamConnector {
+    pub fn new(upstreams: Arc<Upstreams>) -> Self {
+        Self { upstreams }
+    }
+}
+
+impl Service<Uri> for UpstreamConnector {
+    type Response = UpstreamStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<UpstreamStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let upstreams = Arc::clone(&self.upstreams);
+
+        Box::pin(async move { upstreams.connect_uri(&uri).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_select_upstream() {
+        let upstreams = Upstreams::new(&[
+            UpstreamRule {
+                host: r"(.*\.)?corp\.com".into(),
+                proxy: "direct".into(),
+            },
+            UpstreamRule {
+                host: ".*".into(),
+                proxy: "http://u%40x:p@10.0.0.1:3128".into(),
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(upstreams.select("api.corp.com"), &UpstreamProxy::Direct);
+        assert_eq!(
+            upstreams.select("notcorp.com"),
+            &UpstreamProxy::Http {
+                tls: false,
+                host: "10.0.0.1".into(),
+                port: 3128,
+                authorization: Some(BASE64.encode("u@x:p")),
+            }
+        );
+
+        assert_eq!(
+            UpstreamProxy::parse("socks5://127.0.0.1").unwrap(),
+            UpstreamProxy::Socks5 {
+                host: "127.0.0.1".into(),
+                port: 1080,
+                credentials: None,
+            }
+        );
+        assert!(UpstreamProxy::parse("ftp://x.com").is_err());
+    }
+
+    fn upstreams(proxy: String) -> Upstreams {
+        Upstreams::new(&[UpstreamRule {
+            host: ".*".into(),
+            proxy,
+        }])
+        .unwrap()
+    }
+
+    /// Echo what's sent through the tunnel after `handshake`.
+    async fn stub_proxy<F, Fut>(handshake: F) -> u16
+    where
+        F: FnOnce(TcpStream) -> Fut + Send + 'static,
+        Fut: Future<Output = TcpStream> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = handshake(stream).await;
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        port
+    }
+
+    async fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    async fn assert_echo(mut stream: UpstreamStream) {
+        stream.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_http_proxy() {
+        let port = stub_proxy(|mut stream| async move {
+            let head = read_head(&mut stream).await;
+            assert!(head.starts_with("CONNECT www.x.com:443 HTTP/1.1\r\n"));
+            assert!(head.contains(&format!(
+                "Proxy-Authorization: Basic {}",
+                BASE64.encode("u:p")
+            )));
+            stream
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await
+                .unwrap();
+            stream
+        })
+        .await;
+
+        let upstreams = upstreams(format!("http://u:p@127.0.0.1:{port}"));
+        assert_echo(upstreams.connect("www.x.com", 443).await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_socks5_proxy() {
+        let port = stub_proxy(|mut stream| async move {
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [0x05, 0x01, 0x00]);
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+
+            let mut req = [0; 5];
+            stream.read_exact(&mut req).await.unwrap();
+            assert_eq!(req, [0x05, 0x01, 0x00, 0x03, 9]);
+            let mut target = [0; 11];
+            stream.read_exact(&mut target).await.unwrap();
+            assert_eq!(&target, b"www.x.com\x01\xbb");
+            stream
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            stream
+        })
+        .await;
+
+        let upstreams = upstreams(format!("socks5://127.0.0.1:{port}"));
+        assert_echo(upstreams.connect("www.x.com", 443).await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_send_plain_http_to_http_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let proxy = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let head = read_head(&mut stream).await;
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .await
+                .unwrap();
+            head
+        });
+
+        let upstreams = Arc::new(upstreams(format!("http://127.0.0.1:{port}")));
+        let uri: Uri = "http://www.x.com/a?b=1".parse().unwrap();
+        assert!(upstreams.proxy_authorization(&uri).is_none());
+
+        let client =
+            hyper::Client::builder().build::<_, hyper::Body>(UpstreamConnector::new(upstreams));
+        let res = client.get(uri).await.unwrap();
+
+        assert_eq!(res.status(), http::StatusCode::NO_CONTENT);
+        assert!(proxy
+            .await
+            .unwrap()
+            .starts_with("GET http://www.x.com/a?b=1 HTTP/1.1\r\n"));
+    }
+}
@@ -0,0 +1,455 @@
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    cmp::Reverse,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+    app_conf::app_session_dir,
+    events::{
+        body::{preview_limit, EventBody},
+        har::Har,
+        Events, RequestEvent, ResponseCompleteEvent, ResponseEvent,
+    },
+    processors::http_processor::HttpPhase,
+};
+
+const SESSION_FILE: &str = "session.json";
+const EVENTS_FILE: &str = "events.jsonl";
+const BODY_DIR: &str = "body";
+/// Marks events whose body files are the raw bytes, rather than the body encoded for the UI.
+const RAW_BODY: &str = "rawBody";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionMeta {
+    created: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SessionInfo {
+    pub id: Uuid,
+    pub created: i64,
+    pub requests: usize,
+}
+
+/// Captured traffic of every proxy run, laid out as:
+/// ```shell
+/// <root>/<session id>/session.json
+/// <root>/<session id>/events.jsonl        # one event per line, bodies left empty
+/// <root>/<session id>/body/<conn id>.req   # raw bytes
+/// <root>/<session id>/body/<conn id>.res
+/// ```
+#[derive(Debug, Clone)]
+pub(crate) struct SessionStore {
+    root: PathBuf,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(app_session_dir())
+    }
+}
+
+impl SessionStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn session_dir(&self, id: Uuid) -> PathBuf {
+        self.root.join(id.to_string())
+    }
+
+    /// Start a new session, which the events are appended to.
+    pub async fn record(&self) -> io::Result<SessionRecorder> {
+        let id = Uuid::new_v4();
+        let dir = self.session_dir(id);
+
+        tokio::fs::create_dir_all(dir.join(BODY_DIR)).await?;
+
+        let meta = SessionMeta {
+            created: chrono::Local::now().timestamp_millis(),
+        };
+        tokio::fs::write(dir.join(SESSION_FILE), serde_json::to_vec(&meta)?).await?;
+
+        let events = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(EVENTS_FILE))
+            .await?;
+
+        Ok(SessionRecorder {
+            id,
+            dir,
+            events,
+            written: 0,
+            max_bytes: None,
+        })
+    }
+
+    /// Make room for a new session, the oldest sessions are removed until fewer than
+    /// `max_sessions` are left and they take at most `max_bytes` together.
+    pub fn prune(&self, max_sessions: usize, max_bytes: u64) -> io::Result<()> {
+        let mut total = 0;
+
+        for (idx, session) in self.list()?.into_iter().enumerate() {
+            total += dir_size(&self.session_dir(session.id))?;
+
+            if idx + 1 >= max_sessions || total > max_bytes {
+                log::info!("remove session {} as it's over the limits", session.id);
+                self.remove(session.id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Import the archived exchanges as a new session.
+    pub async fn import(&self, har: &Har) -> io::Result<Uuid> {
+        let mut recorder = self.record().await?;
+
+        for entry in har.log.entries.iter() {
+            match entry.to_events() {
+                Ok((req, res)) => {
+                    recorder.record(&req.into()).await?;
+                    recorder.record(&res.into()).await?;
+                }
+                Err(e) => log::warn!("skip HAR entry {}: {e}", entry.request.url),
+            }
+        }
+
+        Ok(recorder.id())
+    }
+
+    /// Sessions from the newest to the oldest.
+    pub fn list(&self) -> io::Result<Vec<SessionInfo>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut sessions: Vec<_> = fs::read_dir(&self.root)?
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<Uuid>().ok())
+            .filter_map(|id| match self.info(id) {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    log::warn!("skip broken session {id}: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        sessions.sort_by_key(|session| Reverse(session.created));
+
+        Ok(sessions)
+    }
+
+    fn info(&self, id: Uuid) -> io::Result<SessionInfo> {
+        let dir = self.session_dir(id);
+
+        let meta: SessionMeta = serde_json::from_slice(&fs::read(dir.join(SESSION_FILE))?)?;
+        let requests = fs::read_to_string(dir.join(EVENTS_FILE))?
+            .lines()
+            .filter(|line| line.starts_with(r#"{"NewRequest""#))
+            .count();
+
+        Ok(SessionInfo {
+            id,
+            created: meta.created,
+            requests,
+        })
+    }
+
+    /// The recorded events in the same shape as they were emitted, bodies over the preview
+    /// limit are cut as well, see [`SessionStore::body`].
+    pub fn read(&self, id: Uuid) -> io::Result<Vec<Value>> {
+        self.events(id, Some(preview_limit()))
+    }
+
+    /// The full body of an exchange of the session.
+    pub async fn body(&self, id: Uuid, conn_id: Uuid, phase: HttpPhase) -> io::Result<EventBody> {
+        let ext = match phase {
+            HttpPhase::Request => "req",
+            HttpPhase::Response => "res",
+        };
+        let file = self
+            .session_dir(id)
+            .join(BODY_DIR)
+            .join(format!("{conn_id}.{ext}"));
+
+        Ok(EventBody::new(
+            &tokio::fs::read(file).await?,
+            &HeaderMap::new(),
+        ))
+    }
+
+    /// The recorded events with their bodies put back, bodies are cut to `preview_limit`.
+    fn events(&self, id: Uuid, preview_limit: Option<usize>) -> io::Result<Vec<Value>> {
+        let dir = self.session_dir(id);
+
+        let events = fs::read_to_string(dir.join(EVENTS_FILE))?
+            .lines()
+            // the last line may be cut off if the app was killed while writing
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .map(|mut event| {
+                if let Some((file, exchange)) = exchange_mut(&mut event) {
+                    let file = dir.join(BODY_DIR).join(file);
+                    if let Err(e) = load_body(&file, exchange, id, preview_limit) {
+                        log::warn!("load body of session {id} failed: {e}");
+                    }
+                }
+                event
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    /// Requests of the session in order, along with their responses if any.
+    pub fn exchanges(&self, id: Uuid) -> io::Result<Vec<(RequestEvent, Option<ResponseEvent>)>> {
+        let mut exchanges: Vec<(RequestEvent, Option<ResponseEvent>)> = vec![];
+
+        for mut event in self.events(id, None)? {
+            if let Some(req) = event.get_mut("NewRequest") {
+                exchanges.push((serde_json::from_value(req.take())?, None));
+            } else if let Some(res) = event.get_mut("NewResponse") {
+                let res: ResponseEvent = serde_json::from_value(res.take())?;
+                if let Some((_, slot)) = exchanges.iter_mut().find(|(req, _)| req.id() == res.id())
+                {
+                    *slot = Some(res);
+                }
+            } else if let Some(complete) = event.get_mut("ResponseComplete") {
+                let complete: ResponseCompleteEvent = serde_json::from_value(complete.take())?;
+                if let Some((_, Some(res))) = exchanges
+                    .iter_mut()
+                    .find(|(req, _)| req.id() == complete.id())
+                {
+                    res.complete(complete);
+                }
+            }
+        }
+
+        Ok(exchanges)
+    }
+
+    pub fn remove(&self, id: Uuid) -> io::Result<()> {
+        let dir = self.session_dir(id);
+
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        fs::remove_dir_all(dir)
+    }
+}
+
+pub(crate) struct SessionRecorder {
+    id: Uuid,
+    dir: PathBuf,
+    events: tokio::fs::File,
+    /// Bytes of the events and bodies recorded so far.
+    written: u64,
+    max_bytes: Option<u64>,
+}
+
+impl SessionRecorder {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Refuse to record events once the session would be over `max_bytes`.
+    pub fn limit(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub async fn record(&mut self, event: &Events) -> io::Result<()> {
+        // paused exchanges show up again once they go on
+        if matches!(event, Events::Breakpoint(_)) {
+            return Ok(());
+        }
+
+        let mut value = serde_json::to_value(event)?;
+
+        // sessions outlive the spool, keep the full bodies as they are
+        let mut body = None;
+        if let (Some((id, phase, event_body)), Some((file, exchange))) =
+            (event.body(), exchange_mut(&mut value))
+        {
+            if !event_body.is_empty() {
+                exchange.insert(String::from("body"), Value::String(String::new()));
+                exchange.insert(String::from(RAW_BODY), Value::Bool(true));
+                body = Some((id, phase, event_body, file));
+            }
+        }
+
+        let mut line = serde_json::to_vec(&value)?;
+        line.push(b'\n');
+
+        self.written +=
+            line.len() as u64 + body.as_ref().map_or(0, |(_, _, body, _)| body.len() as u64);
+        if let Some(max_bytes) = self.max_bytes.filter(|max| self.written > *max) {
+            return Err(io::Error::other(format!(
+                "session is over {max_bytes} bytes"
+            )));
+        }
+
+        if let Some((id, phase, body, file)) = body {
+            body.persist(id, phase, &self.dir.join(BODY_DIR).join(file))
+                .await?;
+        }
+
+        self.events.write_all(&line).await?;
+        // tokio writes in the background, make sure the session can be read as soon as it's
+        // recorded
+        self.events.flush().await
+    }
+}
+
+/// Put the stored body of session `id` back into the serialized event, only the head of it if
+/// it's over `preview_limit`.
+fn load_body(
+    file: &Path,
+    exchange: &mut Map<String, Value>,
+    id: Uuid,
+    preview_limit: Option<usize>,
+) -> io::Result<()> {
+    if !file.exists() {
+        return Ok(());
+    }
+
+    if exchange.remove(RAW_BODY).is_none() {
+        // sessions recorded before raw bodies store them as they're encoded in the events
+        exchange.insert(String::from("body"), fs::read_to_string(file)?.into());
+        return Ok(());
+    }
+
+    let len = fs::metadata(file)?.len() as usize;
+    let body = match preview_limit.filter(|limit| len > *limit) {
+        Some(limit) => {
+            let mut head = Vec::with_capacity(limit);
+            fs::File::open(file)?
+                .take(limit as u64)
+                .read_to_end(&mut head)?;
+            EventBody::preview(&head, len, &HeaderMap::new()).in_session(id)
+        }
+        None => EventBody::new(&fs::read(file)?, &HeaderMap::new()),
+    };
+    if let Value::Object(body) = serde_json::to_value(body)? {
+        // the mime is detected with the headers when the event is made
+        exchange.extend(body.into_iter().filter(|(key, _)| key != "mime"));
+    }
+
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+
+    Ok(size)
+}
+
+/// A serialized request/response event, along with the name of the file its body is stored in.
+fn exchange_mut(event: &mut Value) -> Option<(String, &mut Map<String, Value>)> {
+    let (kind, exchange) = event.as_object_mut()?.iter_mut().next()?;
+    let ext = match kind.as_str() {
+        "NewRequest" => "req",
+        // streamed responses come with their bodies at the end
+        "NewResponse" | "ResponseComplete" => "res",
+        _ => return None,
+    };
+
+    let exchange = exchange.as_object_mut()?;
+    let file = format!("{}.{ext}", exchange.get("id")?.as_str()?);
+
+    Some((file, exchange))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+
+    use crate::events::RequestEvent;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_session() {
+        let store = SessionStore::new(std::env::temp_dir().join(Uuid::new_v4().to_string()));
+        let mut recorder = store.record().await.unwrap();
+
+        let mut req = Request::builder()
+            .uri("https://www.x.com/")
+            .body(Body::from("hello"))
+            .unwrap();
+        let event_id = Uuid::new_v4();
+        let event = RequestEvent::new(event_id, &mut req).await;
+        recorder.record(&event.into()).await.unwrap();
+
+        let raw = fs::read_to_string(store.session_dir(recorder.id()).join(EVENTS_FILE)).unwrap();
+        assert!(raw.contains(r#""body":"""#));
+
+        let sessions = store.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].requests, 1);
+
+        let events = store.read(recorder.id()).unwrap();
+        assert_eq!(events[0]["NewRequest"]["body"], "hello");
+        assert!(events[0]["NewRequest"].get(RAW_BODY).is_none());
+
+        // large bodies are previewed, the full ones are loaded on demand
+        let events = store.events(recorder.id(), Some(2)).unwrap();
+        assert_eq!(events[0]["NewRequest"]["body"], "he");
+        assert_eq!(events[0]["NewRequest"]["truncated"], true);
+        assert_eq!(
+            events[0]["NewRequest"]["session"],
+            recorder.id().to_string()
+        );
+        let body = store
+            .body(recorder.id(), event_id, HttpPhase::Request)
+            .await
+            .unwrap();
+        assert_eq!(body.encoded(), "hello");
+
+        let exchanges = store.exchanges(recorder.id()).unwrap();
+        assert_eq!(exchanges[0].0.id(), event_id);
+
+        store.remove(recorder.id()).unwrap();
+        assert!(store.list().unwrap().is_empty());
+
+        let mut limited = store.record().await.unwrap().limit(10);
+        assert!(limited
+            .record(&exchanges[0].0.clone().into())
+            .await
+            .is_err());
+        store.record().await.unwrap();
+
+        // the oldest sessions are removed to make room for a new one
+        store.prune(3, u64::MAX).unwrap();
+        assert_eq!(store.list().unwrap().len(), 2);
+        store.prune(2, u64::MAX).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        store.prune(10, 0).unwrap();
+        assert!(store.list().unwrap().is_empty());
+
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
 import type { ThemeType } from "@/Components/TopBar/useTheme";
+import type {
+  ConnectionEvent,
+  EncodedBody,
+  RequestConnection,
+} from "@/Events/ConnectionEvents";
 import { invoke } from "@tauri-apps/api/core";
 
 export const checkTlsCertInstalled = async () => {
@@ -57,6 +62,12 @@ export const getProcessorContent = async (
   });
 };
 
+export interface RuleDiagnostic {
+  line: number;
+  content: string;
+  message: string;
+}
+
 export const setProcessor = async (
   packName: string,
   mode: string,
@@ -72,6 +83,8 @@ export const setProcessor = async (
 export interface ProcessorPackTransfer {
   packName: string;
   enable: boolean;
+  // keep matching the next packs after this pack is hit
+  continueMatching: boolean;
 }
 
 export const getProcessorPacks = async () => {
@@ -99,9 +112,141 @@ export const updateProcessPackStatus = async (
   });
 };
 
+export const updateProcessPackContinue = async (
+  packName: string,
+  continueMatching: boolean,
+) => {
+  return invokeWithLogging("update_processor_pack_continue", {
+    packName,
+    continueMatching,
+  });
+};
+
+// packNames are ordered from the highest priority to the lowest
+export const reorderProcessorPacks = async (packNames: string[]) => {
+  return invokeWithLogging("reorder_processor_packs", { packNames });
+};
+
+export interface MessageEdit {
+  method?: string;
+  uri?: string;
+  status?: number;
+  // replace all headers
+  headers?: Array<[string, string]>;
+  body?: string;
+  /**
+   * body 的编码, 同事件里的 bodyEncoding, 缺省为 utf8
+   */
+  bodyEncoding?: "utf8" | "base64";
+}
+
+export type BreakpointAction =
+  | ({ action: "continue" } & MessageEdit)
+  | { action: "abort" };
+
+export const resumeBreakpoint = async (
+  id: string,
+  action: BreakpointAction,
+) => {
+  return invokeWithLogging("resume_breakpoint", { id, action });
+};
+
+/**
+ * Replay a captured request, or a request made from scratch if id is missing.
+ * Resolves the id of the replayed connection.
+ */
+export const replayRequest = async (id?: string, edit?: MessageEdit) => {
+  return invokeWithLogging<string>("replay_request", { id, edit });
+};
+
+export const getBody = async (
+  id: string,
+  phase: "request" | "response",
+  session?: string,
+) => {
+  return invokeWithLogging<EncodedBody>("get_body", { id, phase, session });
+};
+
+export interface SessionInfo {
+  id: string;
+  created: number;
+  requests: number;
+}
+
+export const getSessions = async () => {
+  return invokeWithLogging<SessionInfo[]>("get_sessions");
+};
+
+export const openSession = async (id: string) => {
+  return invokeWithLogging<ConnectionEvent[]>("open_session", { id });
+};
+
+export const removeSession = async (id: string) => {
+  return invokeWithLogging("remove_session", { id });
+};
+
+/**
+ * 导入 HAR 文件内容为新的会话
+ */
+export const importHar = async (content: string) => {
+  return invokeWithLogging<string>("import_har", { content });
+};
+
+/**
+ * 导出为 HAR 文件, 返回文件路径
+ */
+export const exportHar = async (id: string) => {
+  return invokeWithLogging<string>("export_har", { id });
+};
+
+export type SnippetFormat = "curl" | "httpie" | "fetch";
+
+export const requestSnippet = async (
+  request: RequestConnection,
+  format: SnippetFormat,
+) => {
+  return invokeWithLogging<string>("request_snippet", { request, format });
+};
+
 export interface AppSetting {
   theme: ThemeType;
   layout: "bottom" | "right";
+  /**
+   * 超出该字节数的 body 只发送预览
+   */
+  bodyPreviewLimit: number;
+  /**
+   * 客户端收到原始 Content-Encoding 的响应, 修改过的 body 会重新编码
+   */
+  preserveContentEncoding: boolean;
+  /**
+   * 按 host 选择上游代理, 值为 direct 或 http/https/socks5 uri
+   */
+  upstreamProxies: Array<{ host: string; proxy: string }>;
+  /**
+   * 本机 SOCKS5 监听端口, 为 null 时不监听
+   */
+  socks5Port: number | null;
+  /**
+   * 透明代理监听端口, 仅支持 Linux, 为 null 时不监听
+   */
+  transparentPort: number | null;
+  /**
+   * 透明代理监听地址, 默认 127.0.0.1, 重定向其他网卡的连接时设为该网卡的地址
+   */
+  transparentAddress: string;
+  /**
+   * 是否将每次代理运行记录为 session
+   */
+  recordSessions: boolean;
+  /**
+   * 最多保留的 session 数量, 超出时删除最旧的
+   */
+  maxSessions: number;
+  /**
+   * 所有 session 在磁盘上的总字节数上限
+   */
+  maxSessionBytes: number;
 }
 
 export const setAppSetting = async (setting: AppSetting) => {
@@ -0,0 +1,161 @@
+import {
+  type SessionInfo,
+  exportHar,
+  getSessions,
+  importHar,
+  openSession,
+  removeSession,
+} from "@/Commands/Commands";
+import { openConnections } from "@/Store/ConnectionStore";
+import {
+  Button,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalHeader,
+} from "@nextui-org/react";
+import dayjs from "dayjs";
+import { type ChangeEvent, type FC, useEffect, useRef, useState } from "react";
+import { toast } from "react-hot-toast";
+
+/**
+ * 每次开启代理都会记录一个会话, 可重新打开或删除
+ */
+export const Sessions: FC<{
+  isOpen: boolean;
+  onOpenChange: (isOpen: boolean) => void;
+}> = ({ isOpen, onOpenChange }) => {
+  const [sessions, setSessions] = useState<SessionInfo[]>([]);
+  const fileRef = useRef<HTMLInputElement>(null);
+
+  const refresh = async () => {
+    try {
+      setSessions(await getSessions());
+    } catch (error) {
+      toast.error(error as string);
+    }
+  };
+
+  useEffect(() => {
+    if (isOpen) void refresh();
+  }, [isOpen]);
+
+  const open = async (id: string, onClose: () => void) => {
+    try {
+      openConnections(await openSession(id));
+      onClose();
+    } catch (error) {
+      toast.error(error as string);
+    }
+  };
+
+  const exportAsHar = async (id: string) => {
+    try {
+      const file = await exportHar(id);
+      toast.success(`已导出至 ${file}`, { duration: 5000 });
+    } catch (error) {
+      toast.error(error as string);
+    }
+  };
+
+  const importFile = async (event: ChangeEvent<HTMLInputElement>) => {
+    const file = event.target.files?.[0];
+    // allow importing the same file again
+    event.target.value = "";
+    if (!file) return;
+
+    try {
+      await importHar(await file.text());
+      toast.success("导入成功");
+      await refresh();
+    } catch (error) {
+      toast.error(error as string);
+    }
+  };
+
+  const remove = async (id: string) => {
+    try {
+      await removeSession(id);
+      await refresh();
+    } catch (error) {
+      toast.error(error as string);
+    }
+  };
+
+  return (
+    <Modal
+      isOpen={isOpen}
+      onOpenChange={onOpenChange}
+      placement="top-center"
+      backdrop="opaque"
+      scrollBehavior="inside"
+    >
+      <ModalContent>
+        {(onClose) => (
+          <>
+            <ModalHeader className="flex items-center justify-between text-tiny">
+              历史会话
+              <Button
+                size="sm"
+                variant="flat"
+                className="mr-6"
+                onPress={() => fileRef.current?.click()}
+              >
+                导入 HAR
+              </Button>
+              <input
+                ref={fileRef}
+                type="file"
+                accept=".har,application/json"
+                className="hidden"
+                onChange={importFile}
+              />
+            </ModalHeader>
+            <ModalBody className="pb-4">
+              {sessions.length ? (
+                sessions.map((session) => (
+                  <div
+                    key={session.id}
+                    className="flex items-center gap-2 text-tiny"
+                  >
+                    <span className="flex-1">
+                      {dayjs(session.created).format("YYYY-MM-DD HH:mm:ss")}
+                    </span>
+                    <span className="text-default-400">
+                      {session.requests} 个请求
+                    </span>
+                    <Button
+                      size="sm"
+                      variant="flat"
+                      color="primary"
+                      onPress={() => open(session.id, onClose)}
+                    >
+                      打开
+                    </Button>
+                    <Button
+                      size="sm"
+                      variant="flat"
+                      onPress={() => exportAsHar(session.id)}
+                    >
+                      导出 HAR
+                    </Button>
+                    <Button
+                      size="sm"
+                      variant="light"
+                      color="danger"
+                      onPress={() => remove(session.id)}
+                    >
+                      删除
+                    </Button>
+                  </div>
+                ))
+              ) : (
+                <span className="text-tiny text-default-400">暂无会话</span>
+              )}
+            </ModalBody>
+          </>
+        )}
+      </ModalContent>
+    </Modal>
+  );
+};
@@ -18,11 +18,13 @@ import {
   RightLayoutIcon,
   SecureIcon,
   SunIcon,
+  TableIcon,
 } from "@/Icons";
 import { useConnectionStore } from "@/Store/ConnectionStore";
 import { Button, Input, Tooltip } from "@nextui-org/react";
 import { type ChangeEvent, type MouseEvent, useEffect, useState } from "react";
 import toast from "react-hot-toast";
+import { Sessions } from "./Sessions";
 import { useLayout } from "./useLayout";
 import "./index.css";
 
@@ -40,6 +42,8 @@ export const TopBar = () => {
   const [caInstalled, setCaInstalled] = useState<boolean>(false);
   const [installing, setInstalling] = useState<boolean>(false);
 
+  const [sessionsOpen, setSessionsOpen] = useState<boolean>(false);
+
   const {
     enableSystemProxy,
     turnOn: turnOnSystemProxy,
@@ -239,6 +243,14 @@ export const TopBar = () => {
             />
           </div>
         </Tooltip>
+        <Tooltip size="sm" content="历史会话">
+          <div className="sys-setting-wrap">
+            <TableIcon
+              className="sys-setting cursor-pointer"
+              onClick={() => setSessionsOpen(true)}
+            />
+          </div>
+        </Tooltip>
       </div>
       {status ? (
         <Button
@@ -266,6 +278,7 @@ export const TopBar = () => {
           开启
         </Button>
       )}
+      <Sessions isOpen={sessionsOpen} onOpenChange={setSessionsOpen} />
     </div>
   );
 };
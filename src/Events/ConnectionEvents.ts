@@ -2,6 +2,13 @@ export enum RuleMode {
   Redirect = "Redirect",
   Delay = "Delay",
   Response = "Response",
+  Status = "Status",
+  Header = "Header",
+  Body = "Body",
+  MapLocal = "MapLocal",
+  Playback = "Playback",
+  Script = "Script",
+  Breakpoint = "Breakpoint",
 }
 
 interface BaseConnection {
@@ -9,14 +16,45 @@ interface BaseConnection {
   time: number;
 }
 
-export interface RequestConnection extends BaseConnection {
+/**
+ * 文本 body 为原文, 其余为 base64 编码
+ */
+export interface EncodedBody {
+  body: string;
+  bodyEncoding: "utf8" | "base64";
+  /**
+   * 编码前的字节数
+   */
+  bodyLength: number;
+  /**
+   * Content-Type, 缺失时根据内容推断
+   */
+  mime: string;
+  /**
+   * body 超出预览上限时只有开头部分, 完整内容通过 getBody 加载
+   */
+  truncated?: boolean;
+  /**
+   * 从 session 打开时, 完整 body 所在的 session
+   */
+  session?: string;
+}
+
+export interface RequestConnection extends BaseConnection, EncodedBody {
   method: string;
   uri: string;
-  body: string;
   headers: Record<string, string>;
   version: string;
+  /**
+   * 重放自哪个请求
+   */
+  replayOf?: string;
+  /**
+   * body 无法解码时的原因, 此时 body 按原样透传
+   */
+  decodeError?: string;
 }
-export interface ResponseConnection extends BaseConnection {
+export interface ResponseConnection extends BaseConnection, EncodedBody {
   /**
    * 实际请求的 uri, 比如 A redirect to B, request.uri 是 A, response.uri 是 B
    */
@@ -24,7 +62,6 @@ export interface ResponseConnection extends BaseConnection {
   status: number;
   version: string;
   headers: Record<string, string>;
-  body: string;
   effects?: Record<
     string,
     Array<{
@@ -32,6 +69,63 @@ export interface ResponseConnection extends BaseConnection {
       info: Record<string, string>;
     }>
   >;
+  replayOf?: string;
+  decodeError?: string;
+  /**
+   * body 仍在接收中, 结束时由 ResponseComplete 补全
+   */
+  streaming?: boolean;
+  /**
+   * 响应流中断的原因
+   */
+  streamError?: string;
+}
+
+export interface ResponseComplete extends BaseConnection, EncodedBody {
+  /**
+   * 响应流中断的原因
+   */
+  error?: string;
+}
+
+export type WebsocketDirection = "clientToServer" | "serverToClient";
+
+export interface WebsocketOpen extends BaseConnection {
+  uri: string;
+  status: number;
+  headers: Record<string, string>;
+}
+
+export interface WebsocketMessage extends BaseConnection {
+  direction: WebsocketDirection;
+  opcode: "text" | "binary" | "ping" | "pong" | "close" | "frame";
+  /**
+   * text 帧为原文, 其余为 base64 编码
+   */
+  payload: string;
+  payloadEncoding: "utf8" | "base64";
+  length: number;
+}
+
+export interface WebsocketClose extends BaseConnection {
+  direction?: WebsocketDirection;
+  code?: number;
+  reason?: string;
+}
+
+export interface BreakpointPause extends BaseConnection, EncodedBody {
+  phase: "request" | "response";
+  /**
+   * 仅请求断点有 method, 仅响应断点有 status
+   */
+  method?: string;
+  uri: string;
+  status?: number;
+  headers: Record<string, string | string[]>;
+  /**
+   * 超时(毫秒)后按原样继续
+   */
+  timeout: number;
 }
 
 export interface RequestEvent {
@@ -42,10 +136,37 @@ export interface ResponseEvent {
   NewResponse: ResponseConnection;
 }
 
+export interface ResponseCompleteEvent {
+  ResponseComplete: ResponseComplete;
+}
+
+export interface WebsocketOpenEvent {
+  WebsocketOpen: WebsocketOpen;
+}
+
+export interface WebsocketMessageEvent {
+  WebsocketMessage: WebsocketMessage;
+}
+
+export interface WebsocketCloseEvent {
+  WebsocketClose: WebsocketClose;
+}
+
+export interface BreakpointEvent {
+  Breakpoint: BreakpointPause;
+}
+
 /**
  * Received from rust.
  */
-export type ConnectionEvent = RequestEvent | ResponseEvent;
+export type ConnectionEvent =
+  | RequestEvent
+  | ResponseEvent
+  | ResponseCompleteEvent
+  | WebsocketOpenEvent
+  | WebsocketMessageEvent
+  | WebsocketCloseEvent
+  | BreakpointEvent;
 
 export const isRequestEvent = (
   event: ConnectionEvent,
@@ -58,3 +179,33 @@ export const isResponseEvent = (
 ): event is ResponseEvent => {
   return "NewResponse" in event;
 };
+
+export const isResponseCompleteEvent = (
+  event: ConnectionEvent,
+): event is ResponseCompleteEvent => {
+  return "ResponseComplete" in event;
+};
+
+export const isWebsocketOpenEvent = (
+  event: ConnectionEvent,
+): event is WebsocketOpenEvent => {
+  return "WebsocketOpen" in event;
+};
+
+export const isWebsocketMessageEvent = (
+  event: ConnectionEvent,
+): event is WebsocketMessageEvent => {
+  return "WebsocketMessage" in event;
+};
+
+export const isWebsocketCloseEvent = (
+  event: ConnectionEvent,
+): event is WebsocketCloseEvent => {
+  return "WebsocketClose" in event;
+};
+
+export const isBreakpointEvent = (
+  event: ConnectionEvent,
+): event is BreakpointEvent => {
+  return "Breakpoint" in event;
+};
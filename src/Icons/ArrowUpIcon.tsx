@@ -0,0 +1,26 @@
+import type { FC } from "react";
+import cls from "classnames";
+import type { IconProps } from "./IconProps";
+
+export const ArrowUpIcon: FC<IconProps> = ({
+  className,
+  size,
+  onClick,
+  ...rest
+}) => {
+  return (
+    <svg
+      className={cls("text-foreground", className)}
+      viewBox="0 0 1024 1024"
+      version="1.1"
+      xmlns="http://www.w3.org/2000/svg"
+      fill="currentColor"
+      width={size}
+      height={size}
+      onClick={onClick}
+      {...rest}
+    >
+      <path d="M512 170.666667a42.666667 42.666667 0 0 1 30.165333 12.501333l256 256a42.666667 42.666667 0 0 1-60.330666 60.330667L554.666667 316.330667V810.666667a42.666667 42.666667 0 1 1-85.333334 0V316.330667l-183.168 183.168a42.666667 42.666667 0 0 1-60.330666-60.330667l256-256A42.666667 42.666667 0 0 1 512 170.666667z" />
+    </svg>
+  );
+};
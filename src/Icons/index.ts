@@ -18,3 +18,4 @@ export * from "./AddIcon";
 export * from "./PinIcon";
 export * from "./DeleteIcon";
 export * from "./SharpIcon";
+export * from "./ArrowUpIcon";
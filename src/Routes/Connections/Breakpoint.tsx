@@ -0,0 +1,85 @@
+import { resumeBreakpoint } from "@/Commands/Commands";
+import { useMessageEdit } from "@/Routes/Connections/useMessageEdit";
+import { useBreakpointStore } from "@/Store/BreakpointStore";
+import {
+  Button,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalFooter,
+  ModalHeader,
+} from "@nextui-org/react";
+import { type FC, useEffect } from "react";
+import { toast } from "react-hot-toast";
+
+/**
+ * 编辑被断点暂停的请求/响应, 多个断点按到达顺序依次处理
+ */
+export const Breakpoint: FC = () => {
+  const { breakpoints, removeBreakpoint } = useBreakpointStore();
+  const breakpoint = breakpoints[0];
+
+  const { form, getEdit } = useMessageEdit(breakpoint);
+
+  useEffect(() => {
+    if (!breakpoint) return;
+
+    // the proxy continues as is after the timeout
+    const timer = window.setTimeout(
+      () => {
+        toast.error("断点已超时, 已按原样继续");
+        removeBreakpoint(breakpoint.id);
+      },
+      breakpoint.time + breakpoint.timeout - Date.now(),
+    );
+
+    return () => window.clearTimeout(timer);
+  }, [breakpoint, removeBreakpoint]);
+
+  if (!breakpoint) return null;
+
+  const resume = async (abort: boolean) => {
+    try {
+      await resumeBreakpoint(
+        breakpoint.id,
+        abort ? { action: "abort" } : { action: "continue", ...getEdit() },
+      );
+    } catch (error) {
+      // it has timed out and can't be resumed any more
+      toast.error(error as string);
+    }
+    removeBreakpoint(breakpoint.id);
+  };
+
+  return (
+    <Modal
+      isOpen
+      hideCloseButton
+      isDismissable={false}
+      placement="top-center"
+      backdrop="opaque"
+      size="3xl"
+    >
+      <ModalContent>
+        <ModalHeader className="flex flex-col gap-1 text-tiny">
+          {breakpoint.phase === "request" ? "请求断点" : "响应断点"} -{" "}
+          {breakpoint.uri}
+        </ModalHeader>
+        <ModalBody>{form}</ModalBody>
+        <ModalFooter>
+          <Button
+            color="danger"
+            variant="light"
+            size="sm"
+            onPress={() => resume(true)}
+          >
+            中止
+          </Button>
+          <Button color="primary" size="sm" onPress={() => resume(false)}>
+            继续
+          </Button>
+        </ModalFooter>
+      </ModalContent>
+    </Modal>
+  );
+};
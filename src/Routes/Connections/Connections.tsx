@@ -114,7 +114,17 @@ export const Connections = () => {
           );
         }
         case "request.method": {
-          return get(conn, columnKey);
+          const value = get(conn, columnKey);
+          return conn.request.replayOf ? (
+            <>
+              {value}
+              <Chip size="sm" variant="flat" className="ml-1">
+                重放
+              </Chip>
+            </>
+          ) : (
+            value
+          );
         }
         case "response.version": {
           return get(conn, columnKey) || "-";
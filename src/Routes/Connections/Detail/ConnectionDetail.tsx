@@ -1,3 +1,4 @@
+import { Messages } from "@/Routes/Connections/Detail/Messages";
 import { Request } from "@/Routes/Connections/Detail/Request";
 import { Response } from "@/Routes/Connections/Detail/Response";
 import type { Connection } from "@/Store/ConnectionStore";
@@ -26,6 +27,11 @@ export const ConnectionDetail: FC<{
           <Spinner />
         )}
       </Tab>
+      {connection.websocket && (
+        <Tab key="messages" title="Messages" className="h-full">
+          <Messages websocket={connection.websocket} />
+        </Tab>
+      )}
     </Tabs>
   );
 };
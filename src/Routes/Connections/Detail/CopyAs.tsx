@@ -0,0 +1,49 @@
+import { type SnippetFormat, requestSnippet } from "@/Commands/Commands";
+import type { RequestConnection } from "@/Events/ConnectionEvents";
+import {
+  Button,
+  Dropdown,
+  DropdownItem,
+  DropdownMenu,
+  DropdownTrigger,
+} from "@nextui-org/react";
+import type { FC, Key } from "react";
+import { toast } from "react-hot-toast";
+
+const formats: Array<{ label: string; format: SnippetFormat }> = [
+  { label: "cURL", format: "curl" },
+  { label: "HTTPie", format: "httpie" },
+  { label: "fetch", format: "fetch" },
+];
+
+/**
+ * 复制为可重新发送该请求的命令或代码
+ */
+export const CopyAs: FC<{
+  request: RequestConnection;
+}> = ({ request }) => {
+  const copy = async (format: Key) => {
+    try {
+      const snippet = await requestSnippet(request, format as SnippetFormat);
+      await navigator.clipboard.writeText(snippet);
+      toast.success("已复制");
+    } catch (error) {
+      toast.error("复制失败");
+    }
+  };
+
+  return (
+    <Dropdown>
+      <DropdownTrigger>
+        <Button size="sm" variant="flat">
+          复制为
+        </Button>
+      </DropdownTrigger>
+      <DropdownMenu aria-label="copy as" onAction={copy}>
+        {formats.map(({ label, format }) => (
+          <DropdownItem key={format}>{label}</DropdownItem>
+        ))}
+      </DropdownMenu>
+    </Dropdown>
+  );
+};
@@ -0,0 +1,17 @@
+import { Tooltip } from "@nextui-org/react";
+import React, { type FC } from "react";
+
+export const DecodeError: FC<{
+  error: string;
+}> = ({ error }) => {
+  return (
+    <div className="px-1">
+      <span className="inline-block w-16 text-tiny">decode</span>
+      <Tooltip placement="top-start" content={error}>
+        <span className="break-all text-tiny text-warning">
+          解码失败, body 按原样透传
+        </span>
+      </Tooltip>
+    </div>
+  );
+};
@@ -26,3 +26,25 @@ export const tryStringifyWithSpaces = (str: string): string => {
     return str;
   }
 };
+
+/**
+ * 二进制内容的十六进制视图, 每行 16 字节
+ */
+export const toHexDump = (base64: string): string => {
+  const bytes = Uint8Array.from(atob(base64), (c) => c.charCodeAt(0));
+  const lines: string[] = [];
+
+  for (let offset = 0; offset < bytes.length; offset += 16) {
+    const row = Array.from(bytes.slice(offset, offset + 16));
+    const hex = row.map((b) => b.toString(16).padStart(2, "0")).join(" ");
+    const ascii = row
+      .map((b) => (b >= 0x20 && b < 0x7f ? String.fromCharCode(b) : "."))
+      .join("");
+
+    lines.push(
+      `${offset.toString(16).padStart(8, "0")}  ${hex.padEnd(47)}  ${ascii}`,
+    );
+  }
+
+  return lines.join("\n");
+};
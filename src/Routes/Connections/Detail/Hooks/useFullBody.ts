@@ -0,0 +1,37 @@
+import { getBody } from "@/Commands/Commands";
+import type { EncodedBody } from "@/Events/ConnectionEvents";
+import { useEffect, useState } from "react";
+
+/**
+ * 事件中的 body 可能被截断, 按需加载完整内容
+ */
+export const useFullBody = (
+  connection: EncodedBody & { id: string },
+  phase: "request" | "response",
+) => {
+  const [fullBody, setFullBody] = useState<EncodedBody>();
+  const [loading, setLoading] = useState<boolean>(false);
+
+  useEffect(() => {
+    setFullBody(undefined);
+  }, [connection.id]);
+
+  const loadFullBody = async () => {
+    if (!connection.truncated || loading) return;
+
+    setLoading(true);
+    try {
+      setFullBody(
+        await getBody(connection.id, phase, connection.session),
+      );
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return {
+    body: fullBody ?? connection,
+    loading,
+    loadFullBody,
+  };
+};
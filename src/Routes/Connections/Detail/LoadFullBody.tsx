@@ -0,0 +1,28 @@
+import type { EncodedBody } from "@/Events/ConnectionEvents";
+import { Chip } from "@nextui-org/react";
+import cls from "classnames";
+import React, { type FC } from "react";
+
+export const LoadFullBody: FC<{
+  body: EncodedBody;
+  loading: boolean;
+  onLoad: () => void;
+}> = ({ body, loading, onLoad }) => {
+  if (!body.truncated) return null;
+
+  return (
+    <Chip
+      onClick={onLoad}
+      size="sm"
+      className={cls(
+        "mr-2 cursor-pointer px-2",
+        "hover:bg-default-200",
+        "bg-warning-100",
+      )}
+    >
+      {loading
+        ? "加载中..."
+        : `已截断, 加载完整内容 (${body.bodyLength} 字节)`}
+    </Chip>
+  );
+};
@@ -0,0 +1,69 @@
+import type { WebsocketMessage } from "@/Events/ConnectionEvents";
+import type { WebsocketLog } from "@/Store/ConnectionStore";
+import cls from "classnames";
+import dayjs from "dayjs";
+import React, { type FC } from "react";
+
+export const Messages: FC<{
+  websocket: WebsocketLog;
+}> = ({ websocket }) => {
+  const { open, messages, close } = websocket;
+
+  return (
+    <div className="flex flex-col h-full overflow-auto scrollbar-hide">
+      {open && (
+        <div className="px-1 text-tiny text-default-500">
+          {dayjs(open.time).format("HH:mm:ss:SSS")} connected {open.status}
+        </div>
+      )}
+      {messages.map((message, index) => (
+        <div
+          // biome-ignore lint/suspicious/noArrayIndexKey: messages are append only
+          key={index}
+          className={cls(
+            "px-1 py-1 text-tiny border-b-1 border-default-200 break-all",
+            message.direction === "clientToServer"
+              ? "text-primary"
+              : "text-success",
+          )}
+        >
+          <span className="inline-block mr-2">
+            {message.direction === "clientToServer" ? "↑" : "↓"}
+          </span>
+          <span className="inline-block mr-2 text-default-500">
+            {dayjs(message.time).format("HH:mm:ss:SSS")}
+          </span>
+          <span className="inline-block mr-2 text-default-500">
+            {message.opcode} ({message.length}B)
+          </span>
+          <span className="font-mono">{renderPayload(message)}</span>
+        </div>
+      ))}
+      {close && (
+        <div className="px-1 text-tiny text-default-500">
+          {dayjs(close.time).format("HH:mm:ss:SSS")} closed
+          {close.code !== undefined && close.code !== null
+            ? ` ${close.code}`
+            : ""}
+          {close.reason ? ` ${close.reason}` : ""}
+        </div>
+      )}
+    </div>
+  );
+};
+
+const MAX_PREVIEW_BYTES = 1024;
+
+/**
+ * 二进制帧以 hex 展示, 避免直接渲染乱码
+ */
+function renderPayload(message: WebsocketMessage): string {
+  if (message.payloadEncoding === "utf8") return message.payload;
+
+  const raw = atob(message.payload);
+  const hex = Array.from(raw.slice(0, MAX_PREVIEW_BYTES), (c) =>
+    c.charCodeAt(0).toString(16).padStart(2, "0"),
+  ).join(" ");
+
+  return raw.length > MAX_PREVIEW_BYTES ? `${hex} ...` : hex;
+}
@@ -0,0 +1,75 @@
+import { replayRequest } from "@/Commands/Commands";
+import type { RequestConnection } from "@/Events/ConnectionEvents";
+import { useMessageEdit } from "@/Routes/Connections/useMessageEdit";
+import {
+  Button,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalFooter,
+  ModalHeader,
+  useDisclosure,
+} from "@nextui-org/react";
+import type { FC } from "react";
+import { toast } from "react-hot-toast";
+
+/**
+ * 重放请求, 可先编辑再重放, 重放的请求会作为新的连接出现在列表中
+ */
+export const Replay: FC<{
+  request: RequestConnection;
+}> = ({ request }) => {
+  const { isOpen, onOpen, onOpenChange } = useDisclosure();
+  const { form, getEdit } = useMessageEdit(request);
+
+  const replay = async (edited: boolean) => {
+    try {
+      await replayRequest(request.id, edited ? getEdit() : undefined);
+      toast.success("已重放");
+      return true;
+    } catch (error) {
+      toast.error(error as string);
+      return false;
+    }
+  };
+
+  return (
+    <>
+      <Button size="sm" variant="flat" onPress={() => replay(false)}>
+        重放
+      </Button>
+      <Button size="sm" variant="flat" onPress={onOpen}>
+        编辑后重放
+      </Button>
+      <Modal
+        isOpen={isOpen}
+        onOpenChange={onOpenChange}
+        placement="top-center"
+        backdrop="opaque"
+        size="3xl"
+      >
+        <ModalContent>
+          {(onClose) => (
+            <>
+              <ModalHeader className="flex flex-col gap-1 text-tiny">
+                编辑后重放
+              </ModalHeader>
+              <ModalBody>{form}</ModalBody>
+              <ModalFooter>
+                <Button
+                  color="primary"
+                  size="sm"
+                  onPress={async () => {
+                    if (await replay(true)) onClose();
+                  }}
+                >
+                  重放
+                </Button>
+              </ModalFooter>
+            </>
+          )}
+        </ModalContent>
+      </Modal>
+    </>
+  );
+};
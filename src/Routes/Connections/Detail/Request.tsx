@@ -5,6 +5,7 @@ import type { monaco } from "@/Monaco/Monaco";
 import { Headers } from "@/Routes/Connections/Detail/Headers";
 import {
   isJson,
+  toHexDump,
   tryStringifyWithSpaces,
 } from "@/Routes/Connections/Detail/Helper";
 import { Chip, Snippet, Tab, Tabs } from "@nextui-org/react";
@@ -12,6 +13,11 @@ import cls from "classnames";
 import dayjs from "dayjs";
 import React, { type FC, useLayoutEffect, useRef, useState } from "react";
 import { useDecodeURIComponent } from "./Hooks/useDecodeURIComponent";
+import { useFullBody } from "./Hooks/useFullBody";
+import { CopyAs } from "./CopyAs";
+import { DecodeError } from "./DecodeError";
+import { LoadFullBody } from "./LoadFullBody";
+import { Replay } from "./Replay";
 
 export const Request: FC<{
   request: RequestConnection;
@@ -22,12 +28,11 @@ export const Request: FC<{
 
   const reqMonacoRef = useRef<monaco.editor.IStandaloneCodeEditor>();
 
-  const { isDecoded, decode } = useDecodeURIComponent(
-    reqMonacoRef,
-    request.body,
-  );
+  const { body, loading, loadFullBody } = useFullBody(request, "request");
+
+  const { isDecoded, decode } = useDecodeURIComponent(reqMonacoRef, body.body);
 
-  const isJsonReqBody = isJson(request.body);
+  const isJsonReqBody = isJson(body.body);
   useLayoutEffect(() => {
     if (activeTab !== "body") return;
 
@@ -36,17 +41,24 @@ export const Request: FC<{
 
       if (target) {
         reqMonacoRef.current = createMonacoEditor(target, {
-          value: tryStringifyWithSpaces(request.body),
+          value:
+            body.bodyEncoding === "base64"
+              ? toHexDump(body.body)
+              : tryStringifyWithSpaces(body.body),
           language: isJsonReqBody ? "json" : undefined,
           readonly: true,
           theme,
         });
       }
     }, 100);
-  }, [activeTab, isJsonReqBody, request.body]);
+  }, [activeTab, isJsonReqBody, body.body, body.bodyEncoding]);
 
   return (
     <div className="flex flex-col h-full">
+      <div className="px-1 mb-2 flex flex-row gap-2">
+        <Replay request={request} />
+        <CopyAs request={request} />
+      </div>
       <div className="px-1">
         <span className="inline-block w-16 text-tiny">uri</span>
         <Snippet className="text-tiny" hideSymbol size="sm">
@@ -61,6 +73,7 @@ export const Request: FC<{
         <span className="inline-block w-16 text-tiny">version</span>
         <span className="break-all text-tiny">{request.version}</span>
       </div>
+      {request.decodeError && <DecodeError error={request.decodeError} />}
       <div className="px-1">
         <span className="inline-block w-16 text-tiny">time</span>
         <span className="break-all text-tiny">
@@ -93,8 +106,13 @@ export const Request: FC<{
             >
               decodeURIComponent
             </Chip>
+            <LoadFullBody
+              body={body}
+              loading={loading}
+              onLoad={loadFullBody}
+            />
           </div>
-          {request.body.length !== 0 ? (
+          {body.body.length !== 0 ? (
             <div id="req-body" className="w-full h-full relative" />
           ) : (
             <div>Empty</div>
@@ -1,9 +1,13 @@
 import { createMonacoEditor } from "@/Components/MonacoEditor/MonacoEditor";
 import { useTheme } from "@/Components/TopBar/useTheme";
-import { type ResponseConnection, RuleMode } from "@/Events/ConnectionEvents";
+import {
+  type EncodedBody,
+  type ResponseConnection,
+  RuleMode,
+} from "@/Events/ConnectionEvents";
 import type { monaco } from "@/Monaco/Monaco";
 import { Headers } from "@/Routes/Connections/Detail/Headers";
-import { isJsonp } from "@/Routes/Connections/Detail/Helper";
+import { isJsonp, toHexDump } from "@/Routes/Connections/Detail/Helper";
 import {
   Chip,
   Snippet,
@@ -15,7 +19,10 @@ import {
 import cls from "classnames";
 import dayjs from "dayjs";
 import React, { type FC, useLayoutEffect, useRef, useState } from "react";
+import { DecodeError } from "./DecodeError";
+import { useFullBody } from "./Hooks/useFullBody";
 import { usePretty } from "./Hooks/usePretty";
+import { LoadFullBody } from "./LoadFullBody";
 import { CreateValue } from "@/Routes/Value/CreateValue";
 import { useNavigate } from "react-router-dom";
 
@@ -30,14 +37,9 @@ export const Response: FC<{
 
   const resMonacoRef = useRef<monaco.editor.IStandaloneCodeEditor>();
 
-  const { isPretty, pretty } = usePretty(resMonacoRef, response.body);
+  const { body, loading, loadFullBody } = useFullBody(response, "response");
 
-  const contentTypeKey = Object.keys(response.headers).find(
-    (x) => x.toLowerCase() === "content-type",
-  );
-  const contentType = contentTypeKey
-    ? response.headers[contentTypeKey]
-    : undefined;
+  const { isPretty, pretty } = usePretty(resMonacoRef, body.body);
 
   useLayoutEffect(() => {
     if (activeTab !== "body") return;
@@ -46,10 +48,10 @@ export const Response: FC<{
       const target = document.getElementById("res-body");
 
       if (target) {
-        const { body, language } = getBodyModel(response);
+        const { value, language } = getBodyModel(response, body);
 
         resMonacoRef.current = createMonacoEditor(target, {
-          value: body,
+          value,
           readonly: true,
           lineNumbers: "on",
           language,
@@ -57,7 +59,7 @@ export const Response: FC<{
         });
       }
     }, 100);
-  }, [activeTab]);
+  }, [activeTab, body]);
 
   const [savedResponseValue, setSavedResponseValue] = useState<string>();
   const { isOpen, onOpenChange, onOpen } = useDisclosure();
@@ -88,10 +90,10 @@ export const Response: FC<{
       // 出于编辑状态, 保存值
       if (!resMonacoRef.current) return;
 
-      const body = resMonacoRef.current.getValue();
-      if (body === response.body) return;
+      const value = resMonacoRef.current.getValue();
+      if (value === body.body) return;
 
-      setSavedResponseValue(asResponseValue(response, body));
+      setSavedResponseValue(asResponseValue(response, value));
 
       onOpen();
     }
@@ -99,12 +101,9 @@ export const Response: FC<{
     setBeEditing(!beEditing);
   };
 
-  const notStringLike = !!(
-    contentType &&
-    ["image", "octet-stream", "media"].some((type) =>
-      contentType.includes(type),
-    )
-  );
+  const isBinary = body.bodyEncoding === "base64";
+  // 截断的图片无法显示
+  const isImage = isBinary && !body.truncated && body.mime.startsWith("image");
 
   return (
     <div className="flex flex-col h-full">
@@ -123,9 +122,18 @@ export const Response: FC<{
         <span className="inline-block w-16 text-tiny">version</span>
         <span className="break-all text-tiny">{response.version}</span>
       </div>
+      {response.decodeError && <DecodeError error={response.decodeError} />}
       <div className="px-1">
         <span className="inline-block w-16 text-tiny">status</span>
         <span className="break-all text-tiny">{response.status}</span>
+        {response.streaming && (
+          <span className="ml-2 text-tiny text-warning">body 接收中...</span>
+        )}
+        {response.streamError && (
+          <span className="ml-2 text-tiny text-danger">
+            body 中断: {response.streamError}
+          </span>
+        )}
       </div>
       <div className="px-1">
         <span className="inline-block w-16 text-tiny">time</span>
@@ -159,28 +167,33 @@ export const Response: FC<{
             >
               格式化
             </Chip>
-            <Chip
-              onClick={onEditClick}
-              size="sm"
-              className={cls(
-                "mr-2 cursor-pointer px-2",
-                "hover:bg-default-200",
-                beEditing ? "bg-success-300" : "bg-default-100",
-              )}
-            >
-              {beEditing ? "保存为值文件" : "编辑"}
-            </Chip>
+            {/* 二进制内容和截断的内容无法编辑为值文件 */}
+            {!isBinary && !body.truncated && (
+              <Chip
+                onClick={onEditClick}
+                size="sm"
+                className={cls(
+                  "mr-2 cursor-pointer px-2",
+                  "hover:bg-default-200",
+                  beEditing ? "bg-success-300" : "bg-default-100",
+                )}
+              >
+                {beEditing ? "保存为值文件" : "编辑"}
+              </Chip>
+            )}
+            <LoadFullBody
+              body={body}
+              loading={loading}
+              onLoad={loadFullBody}
+            />
           </div>
-          {notStringLike && (
-            <MediaResponse uri={response.uri} contentType={contentType} />
-          )}
+          {isImage && <MediaResponse uri={response.uri} body={body} />}
           <div
             id="res-body"
             className="w-full h-full relative border border-transparent data-[editing=true]:border-dashed data-[editing=true]:border-blue9"
             data-editing={beEditing}
             style={{
-              display:
-                !notStringLike && response.body.length !== 0 ? "block" : "none",
+              display: !isImage && body.body.length !== 0 ? "block" : "none",
             }}
           />
         </Tab>
@@ -196,12 +209,9 @@ export const Response: FC<{
 
 const MediaResponse: FC<{
   uri: string;
-  contentType: string;
-}> = ({ uri, contentType }) => {
-  if (contentType.startsWith("image")) {
-    return <img src={uri} alt={uri} />;
-  }
-  return <span>unsupport media</span>;
+  body: EncodedBody;
+}> = ({ uri, body }) => {
+  return <img src={`data:${body.mime};base64,${body.body}`} alt={uri} />;
 };
 
 function getBodyLanguage(response: ResponseConnection): string {
@@ -225,20 +235,27 @@ function getBodyLanguage(response: ResponseConnection): string {
   return "text";
 }
 
-function getBodyModel(response: ResponseConnection) {
+function getBodyModel(response: ResponseConnection, body: EncodedBody) {
+  if (body.bodyEncoding === "base64") {
+    return {
+      value: toHexDump(body.body),
+      language: "text",
+    };
+  }
+
   const language = getBodyLanguage(response);
 
-  const jsonpBody = isJsonp(response.body);
+  const jsonpBody = isJsonp(body.body);
 
   if (jsonpBody) {
     return {
-      body: jsonpBody,
+      value: jsonpBody,
       language: "json",
     };
   }
 
   return {
-    body: response.body,
+    value: body.body,
     language,
   };
 }
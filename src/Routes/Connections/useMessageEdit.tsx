@@ -0,0 +1,114 @@
+import type { MessageEdit } from "@/Commands/Commands";
+import { Input, Textarea } from "@nextui-org/react";
+import { useEffect, useState } from "react";
+
+/**
+ * 可编辑的请求/响应, 请求有 method, 响应有 status
+ */
+export interface EditableMessage {
+  method?: string;
+  uri: string;
+  status?: number;
+  headers: Record<string, string | string[]>;
+  body: string;
+  bodyEncoding?: "utf8" | "base64";
+}
+
+const formatHeaders = (headers: EditableMessage["headers"]) =>
+  Object.entries(headers)
+    .flatMap(([name, value]) =>
+      (Array.isArray(value) ? value : [value]).map((v) => `${name}: ${v}`),
+    )
+    .join("\n");
+
+const parseHeaders = (text: string): Array<[string, string]> =>
+  text
+    .split("\n")
+    .filter((line) => line.trim())
+    .map((line) => {
+      const index = line.indexOf(":");
+      return index === -1
+        ? [line.trim(), ""]
+        : [line.slice(0, index).trim(), line.slice(index + 1).trim()];
+    });
+
+export const useMessageEdit = (message?: EditableMessage) => {
+  const [method, setMethod] = useState<string>("");
+  const [uri, setUri] = useState<string>("");
+  const [status, setStatus] = useState<string>("");
+  const [headers, setHeaders] = useState<string>("");
+  const [body, setBody] = useState<string>("");
+
+  useEffect(() => {
+    if (!message) return;
+
+    setMethod(message.method ?? "");
+    setUri(message.uri);
+    setStatus(message.status?.toString() ?? "");
+    setHeaders(formatHeaders(message.headers));
+    setBody(message.body);
+  }, [message]);
+
+  // rust sends null for the missing one
+  const isRequest = typeof message?.method === "string";
+
+  // untouched parts are left out, binary bodies are sent back in base64 as they came
+  const getEdit = (): MessageEdit => ({
+    headers:
+      message && headers !== formatHeaders(message.headers)
+        ? parseHeaders(headers)
+        : undefined,
+    ...(body !== message?.body
+      ? { body, bodyEncoding: message?.bodyEncoding ?? "utf8" }
+      : {}),
+    ...(isRequest ? { method, uri } : { status: Number(status) }),
+  });
+
+  const form = (
+    <>
+      {isRequest ? (
+        <div className="flex flex-row gap-2">
+          <Input
+            size="sm"
+            className="w-32"
+            label="Method"
+            value={method}
+            onValueChange={setMethod}
+          />
+          <Input size="sm" label="URI" value={uri} onValueChange={setUri} />
+        </div>
+      ) : (
+        <Input
+          size="sm"
+          label="Status"
+          value={status}
+          onValueChange={setStatus}
+        />
+      )}
+      <Textarea
+        size="sm"
+        label="Headers"
+        minRows={4}
+        maxRows={10}
+        value={headers}
+        onValueChange={setHeaders}
+      />
+      <Textarea
+        size="sm"
+        label="Body"
+        minRows={4}
+        maxRows={16}
+        value={body}
+        onValueChange={setBody}
+        isDisabled={message?.bodyEncoding === "base64"}
+        description={
+          message?.bodyEncoding === "base64"
+            ? "二进制内容 (base64), 不可编辑"
+            : undefined
+        }
+      />
+    </>
+  );
+
+  return { form, getEdit };
+};
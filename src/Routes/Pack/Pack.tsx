@@ -1,10 +1,13 @@
 import {
+  type ProcessorPackTransfer,
   removeProcessorPack,
+  reorderProcessorPacks,
+  updateProcessPackContinue,
   updateProcessPackStatus,
 } from "@/Commands/Commands";
 import cls from "classnames";
-import { AddIcon, DeleteIcon } from "@/Icons";
-import { Button, Switch, useDisclosure } from "@nextui-org/react";
+import { AddIcon, ArrowUpIcon, DeleteIcon } from "@/Icons";
+import { Button, Switch, Tooltip, useDisclosure } from "@nextui-org/react";
 import { type ChangeEvent, type FC, type MouseEvent, useEffect } from "react";
 import toast from "react-hot-toast";
 import { NavLink, Outlet, useNavigate, useParams } from "react-router-dom";
@@ -60,6 +63,44 @@ export const Pack: FC = () => {
     }
   };
 
+  // 排在前面的规则组优先匹配
+  const movePackUp = async (evt: MouseEvent<HTMLButtonElement>) => {
+    evt.stopPropagation();
+    evt.preventDefault();
+
+    const idx = packs.findIndex(
+      (x) => x.packName === evt.currentTarget.dataset.packname,
+    );
+    if (idx <= 0) return;
+
+    const reordered = [...packs];
+    [reordered[idx - 1], reordered[idx]] = [reordered[idx], reordered[idx - 1]];
+
+    try {
+      await reorderProcessorPacks(reordered.map((x) => x.packName));
+      packStore.setPacks(reordered);
+    } catch (error) {
+      toast.error(`调整顺序失败: ${error}`);
+    }
+  };
+
+  const toggleContinue = async (
+    evt: MouseEvent<HTMLButtonElement>,
+    pack: ProcessorPackTransfer,
+  ) => {
+    evt.stopPropagation();
+    evt.preventDefault();
+
+    const continueMatching = !pack.continueMatching;
+
+    try {
+      await updateProcessPackContinue(pack.packName, continueMatching);
+      packStore.updatePackContinue(pack.packName, continueMatching);
+    } catch (error) {
+      toast.error(`修改失败: ${error}`);
+    }
+  };
+
   const onCreatedSuccess = (name: string) => {
     navigate(`/pack/${name}`);
   };
@@ -97,6 +138,37 @@ export const Pack: FC = () => {
               >
                 <span>{pack.packName}</span>
                 <div className="flex-row flex items-center">
+                  <Tooltip
+                    content={
+                      pack.continueMatching
+                        ? "命中后继续匹配后面的规则组"
+                        : "命中后停止匹配后面的规则组"
+                    }
+                  >
+                    <Button
+                      disableRipple
+                      disableAnimation
+                      className={cls(
+                        "bg-transparent !text-tiny !outline-0 hover:text-primary-400 h-4 !w-fit min-w-2 px-0 mr-2",
+                        pack.continueMatching
+                          ? "text-success"
+                          : "text-default-400",
+                      )}
+                      onClick={(evt) => toggleContinue(evt, pack)}
+                    >
+                      {pack.continueMatching ? "继续" : "停止"}
+                    </Button>
+                  </Tooltip>
+                  <Button
+                    isIconOnly
+                    disableRipple
+                    disableAnimation
+                    className="bg-transparent !text-tiny !outline-0 hover:text-primary-400 h-4 !w-fit min-w-2 mr-2"
+                    data-packname={pack.packName}
+                    onClick={movePackUp}
+                  >
+                    <ArrowUpIcon size={14} className="text-default-600" />
+                  </Button>
                   <Switch
                     className="mr-2"
                     classNames={{
@@ -11,6 +11,7 @@ export const usePackStore = create<{
   setPacks: (packs: ProcessorPackTransfer[]) => void;
   removePack: (packName: string) => void;
   updatePackStatus: (packName: string, status: boolean) => void;
+  updatePackContinue: (packName: string, continueMatching: boolean) => void;
 }>((set) => ({
   packs: [],
   setPacks: (packs: ProcessorPackTransfer[]) => set((state) => ({ packs })),
@@ -20,6 +21,7 @@ export const usePackStore = create<{
         {
           packName,
           enable: enable ?? false,
+          continueMatching: false,
         },
         ...state.packs,
       ],
@@ -33,12 +35,18 @@ export const usePackStore = create<{
       packs: state.packs.map((pack) =>
         pack.packName === packName
           ? {
-              packName,
+              ...pack,
               enable: status,
             }
           : pack,
       ),
     })),
+  updatePackContinue: (packName: string, continueMatching: boolean) =>
+    set((state) => ({
+      packs: state.packs.map((pack) =>
+        pack.packName === packName ? { ...pack, continueMatching } : pack,
+      ),
+    })),
 }));
 
 getProcessorPacks().then((packs) => usePackStore.setState({ packs }));
@@ -1,4 +1,5 @@
 import { Sidebar } from "@/Components/Sidebar/Sidebar";
+import { Breakpoint } from "@/Routes/Connections/Breakpoint";
 import React, { type FC } from "react";
 import { Outlet } from "react-router-dom";
 
@@ -16,6 +17,7 @@ export const Root: FC = () => {
       >
         <Outlet />
       </div>
+      <Breakpoint />
     </div>
   );
 };
@@ -69,4 +69,32 @@ const ruleModes = [
     label: "延时",
     mode: RuleMode.Delay,
   },
+  {
+    label: "状态码",
+    mode: RuleMode.Status,
+  },
+  {
+    label: "请求/响应头",
+    mode: RuleMode.Header,
+  },
+  {
+    label: "请求/响应体",
+    mode: RuleMode.Body,
+  },
+  {
+    label: "本地映射",
+    mode: RuleMode.MapLocal,
+  },
+  {
+    label: "HAR 回放",
+    mode: RuleMode.Playback,
+  },
+  {
+    label: "脚本",
+    mode: RuleMode.Script,
+  },
+  {
+    label: "断点",
+    mode: RuleMode.Breakpoint,
+  },
 ];
@@ -1,4 +1,8 @@
-import { getProcessorContent, setProcessor } from "@/Commands/Commands";
+import {
+  type RuleDiagnostic,
+  getProcessorContent,
+  setProcessor,
+} from "@/Commands/Commands";
 import { createMonacoEditor } from "@/Components/MonacoEditor/MonacoEditor";
 import { useEditorTheme } from "@/Components/MonacoEditor/useEditorTheme";
 import { RuleMode } from "@/Events/ConnectionEvents";
@@ -35,12 +39,36 @@ export const useRuleEditor = (
         const value = editorRef.current.getValue();
         if (value === valueRef.current) return;
 
+        const model = editorRef.current.getModel();
+
         try {
           await setProcessor(currentPackName, serializationKey, value);
           valueRef.current = value;
+          if (model) {
+            monaco.editor.setModelMarkers(model, RULE_MARKER_OWNER, []);
+          }
           toast.success("保存规则成功");
         } catch (error) {
-          toast.error("保存规则失败");
+          const diagnostics = getRuleDiagnostics(error);
+          if (model && diagnostics.length) {
+            monaco.editor.setModelMarkers(
+              model,
+              RULE_MARKER_OWNER,
+              diagnostics.map((x) => ({
+                severity: monaco.MarkerSeverity.Error,
+                message: x.message,
+                startLineNumber: x.line,
+                startColumn: model.getLineFirstNonWhitespaceColumn(x.line),
+                endLineNumber: x.line,
+                endColumn: model.getLineMaxColumn(x.line),
+              })),
+            );
+            toast.error(
+              `规则第 ${diagnostics[0].line} 行有误: ${diagnostics[0].message}`,
+            );
+          } else {
+            toast.error("保存规则失败");
+          }
         }
       }
     };
@@ -96,9 +124,22 @@ export const useRuleEditor = (
   };
 };
 
+const RULE_MARKER_OWNER = "rule-validation";
+
+const getRuleDiagnostics = (error: any): RuleDiagnostic[] => {
+  return error?.cause?.diagnostics ?? [];
+};
+
 const getRuleModeSerializationKey = (mode: RuleMode) => {
   if (mode === RuleMode.Delay) return "Delay";
   if (mode === RuleMode.Redirect) return "Redirect";
   if (mode === RuleMode.Response) return "Response";
+  if (mode === RuleMode.Status) return "Status";
+  if (mode === RuleMode.Header) return "Header";
+  if (mode === RuleMode.Body) return "Body";
+  if (mode === RuleMode.MapLocal) return "MapLocal";
+  if (mode === RuleMode.Playback) return "Playback";
+  if (mode === RuleMode.Script) return "Script";
+  if (mode === RuleMode.Breakpoint) return "Breakpoint";
   throw new TypeError("Unsupported rule");
 };
@@ -0,0 +1,17 @@
+import type { BreakpointPause } from "@/Events/ConnectionEvents";
+import { create } from "zustand";
+
+export const useBreakpointStore = create<{
+  // paused exchanges in arrival order
+  breakpoints: BreakpointPause[];
+  addBreakpoint: (breakpoint: BreakpointPause) => void;
+  removeBreakpoint: (id: string) => void;
+}>((set) => ({
+  breakpoints: [],
+  addBreakpoint: (breakpoint) =>
+    set(({ breakpoints }) => ({ breakpoints: [...breakpoints, breakpoint] })),
+  removeBreakpoint: (id) =>
+    set(({ breakpoints }) => ({
+      breakpoints: breakpoints.filter((x) => x.id !== id),
+    })),
+}));
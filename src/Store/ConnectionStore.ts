@@ -2,16 +2,32 @@ import {
   type ConnectionEvent,
   type RequestConnection,
   type ResponseConnection,
+  type WebsocketClose,
+  type WebsocketMessage,
+  type WebsocketOpen,
+  isBreakpointEvent,
   isRequestEvent,
+  isResponseCompleteEvent,
   isResponseEvent,
+  isWebsocketCloseEvent,
+  isWebsocketMessageEvent,
+  isWebsocketOpenEvent,
 } from "@/Events/ConnectionEvents";
+import { useBreakpointStore } from "@/Store/BreakpointStore";
 import { listen } from "@tauri-apps/api/event";
 import { create } from "zustand";
 
+export interface WebsocketLog {
+  open?: WebsocketOpen;
+  messages: WebsocketMessage[];
+  close?: WebsocketClose;
+}
+
 export interface Connection {
   id: string;
   request: RequestConnection;
   response?: ResponseConnection;
+  websocket?: WebsocketLog;
 }
 
 export const useConnectionStore = create<{
@@ -26,19 +42,23 @@ let cacheConnQueue: RequestConnection[] = [];
 const UPDATE_INTERVAL: number = 200;
 
 let updateTimer: number | undefined = undefined;
+const flushConnQueue = () => {
+  if (cacheConnQueue.length) {
+    const reqs = cacheConnQueue.map<Connection>((x) => ({
+      id: x.id,
+      request: x,
+    }));
+    useConnectionStore.setState(({ connections }) => ({
+      connections: [...reqs, ...connections],
+    }));
+
+    cacheConnQueue = [];
+  }
+};
+
 const startUpdateConnStateLoop = () => {
   updateTimer = window.setTimeout(() => {
-    if (cacheConnQueue.length) {
-      const reqs = cacheConnQueue.map<Connection>((x) => ({
-        id: x.id,
-        request: x,
-      }));
-      useConnectionStore.setState(({ connections }) => ({
-        connections: [...reqs, ...connections],
-      }));
-
-      cacheConnQueue = [];
-    }
+    flushConnQueue();
 
     startUpdateConnStateLoop();
   }, UPDATE_INTERVAL);
@@ -46,6 +66,24 @@ const startUpdateConnStateLoop = () => {
 
 startUpdateConnStateLoop();
 
+const updateWebsocketLog = (
+  id: string,
+  update: (log: WebsocketLog) => WebsocketLog,
+) => {
+  // websocket events may arrive before the upgrade request is flushed into the store
+  if (cacheConnQueue.some((x) => x.id === id)) {
+    flushConnQueue();
+  }
+
+  useConnectionStore.setState(({ connections }) => ({
+    connections: connections.map((x) =>
+      x.id === id
+        ? { ...x, websocket: update(x.websocket ?? { messages: [] }) }
+        : x,
+    ),
+  }));
+};
+
 const processConnections = (event: ConnectionEvent) => {
   if (isRequestEvent(event)) {
     cacheConnQueue.push(event.NewRequest);
@@ -66,6 +104,7 @@ const processConnections = (event: ConnectionEvent) => {
         id: conn.id,
         request: conn.request,
         response: resConn,
+        websocket: conn.websocket,
       };
 
       return {
@@ -74,6 +113,60 @@ const processConnections = (event: ConnectionEvent) => {
         ),
       };
     });
+  } else if (isResponseCompleteEvent(event)) {
+    const { id, time, error, ...body } = event.ResponseComplete;
+
+    useConnectionStore.setState(({ connections }) => ({
+      connections: connections.map((x) =>
+        x.id === id && x.response
+          ? {
+              ...x,
+              response: {
+                ...x.response,
+                ...body,
+                streaming: false,
+                streamError: error,
+              },
+            }
+          : x,
+      ),
+    }));
+  } else if (isWebsocketOpenEvent(event)) {
+    const open = event.WebsocketOpen;
+    updateWebsocketLog(open.id, (log) => ({ ...log, open }));
+  } else if (isWebsocketMessageEvent(event)) {
+    const message = event.WebsocketMessage;
+    updateWebsocketLog(message.id, (log) => ({
+      ...log,
+      messages: [...log.messages, message],
+    }));
+  } else if (isWebsocketCloseEvent(event)) {
+    const close = event.WebsocketClose;
+    updateWebsocketLog(close.id, (log) => ({ ...log, close }));
+  } else if (isBreakpointEvent(event)) {
+    useBreakpointStore.getState().addBreakpoint(event.Breakpoint);
+  }
+};
+
+/**
+ * 用历史会话的事件替换当前的请求数据
+ */
+export const openConnections = (events: ConnectionEvent[]) => {
+  cacheConnQueue = [];
+  useConnectionStore.setState({
+    connections: events
+      .filter(isRequestEvent)
+      .map<Connection>((x) => ({
+        id: x.NewRequest.id,
+        request: x.NewRequest,
+      }))
+      .reverse(),
+  });
+
+  for (const event of events) {
+    if (!isRequestEvent(event) && !isBreakpointEvent(event)) {
+      processConnections(event);
+    }
   }
 };
 
@@ -13,6 +13,15 @@ const readAppSetting = (
   return {
     theme: "dark",
     layout: "right",
+    bodyPreviewLimit: 512 * 1024,
+    preserveContentEncoding: false,
+    upstreamProxies: [],
+    socks5Port: null,
+    transparentPort: null,
+    transparentAddress: "127.0.0.1",
+    recordSessions: true,
+    maxSessions: 20,
+    maxSessionBytes: 1024 * 1024 * 1024,
   };
 };
 